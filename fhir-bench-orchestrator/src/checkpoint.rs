@@ -0,0 +1,250 @@
+//! Checkpoints the results of benchmark runs as they progress, so that a run that's interrupted or crashes
+//! can be resumed, rather than having to start over (full runs at realistic population sizes take hours).
+
+use crate::servers::ServerPlugin;
+use crate::test_framework::{FrameworkResults, ServerOperationLog, ServerResult};
+use crate::AppState;
+use eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+/// The contents of a checkpoint file.
+#[derive(Deserialize, Serialize)]
+struct Checkpoint {
+    /// The [crate::sample_data::SampleData::fingerprint] of the sample data that the run was using, which
+    /// must match for it to be resumed.
+    sample_data_fingerprint: String,
+
+    /// The [FrameworkResults] completed so far, including the [crate::config::AppConfig] that the run was
+    /// using, which must also match for it to be resumed.
+    results: FrameworkResults,
+}
+
+/// Writes checkpoints for a benchmark run, per [crate::config::AppConfig.checkpoint].
+pub struct Checkpointer {
+    /// The checkpoint file to write to.
+    path: PathBuf,
+
+    /// The [Checkpoint] most recently written.
+    checkpoint: Mutex<Checkpoint>,
+}
+
+impl Checkpointer {
+    /// Constructs a new [Checkpointer], if [crate::config::AppConfig.checkpoint] is enabled, and returns
+    /// the [FrameworkResults] that the run should start from: either the ones loaded from the checkpoint, if
+    /// [crate::config::AppConfig.resume] is enabled, or new ones otherwise.
+    ///
+    /// Parameters:
+    /// * `app_state`: the application's [AppState]
+    ///
+    /// Returns the [Checkpointer] (if enabled) and the [FrameworkResults] to start from, or an error if the
+    /// run should be resumed but can't be.
+    pub fn new(app_state: &AppState) -> Result<(Option<Checkpointer>, FrameworkResults)> {
+        let new_results = || {
+            FrameworkResults::new(
+                &app_state.config,
+                &app_state.workload_plan,
+                &app_state.server_plugins,
+            )
+        };
+        let path = match &app_state.config.checkpoint {
+            Some(path) => path.clone(),
+            None => return Ok((None, new_results())),
+        };
+
+        let sample_data_fingerprint = app_state.workload_plan.sample_data_fingerprint.clone();
+        let results = if app_state.config.resume {
+            let checkpoint = read_checkpoint(&path)?;
+            verify_resumable(app_state, &checkpoint, &sample_data_fingerprint)?;
+            info!(checkpoint = ?path, "Resuming benchmark run from checkpoint.");
+
+            let mut results = checkpoint.results;
+            results.completed = None;
+            results.interrupted = None;
+            for server_plugin in &app_state.server_plugins {
+                if results.get_mut(server_plugin.server_name()).is_none() {
+                    results
+                        .servers
+                        .push(ServerResult::new(server_plugin.server_name().clone()));
+                }
+            }
+            results
+        } else {
+            new_results()
+        };
+
+        let checkpointer = Checkpointer {
+            path,
+            checkpoint: Mutex::new(Checkpoint {
+                sample_data_fingerprint,
+                results: results.clone(),
+            }),
+        };
+        Ok((Some(checkpointer), results))
+    }
+
+    /// Writes a checkpoint with the specified [ServerResult] (which replaces any previous one for that
+    /// server). Any errors are logged, rather than halting the run.
+    ///
+    /// Parameters:
+    /// * `server_result`: the [ServerResult] to write
+    pub fn save_server(&self, server_result: &ServerResult) {
+        let mut checkpoint = self.checkpoint.lock().expect("Unable to lock checkpoint.");
+        match checkpoint.results.get_mut(&server_result.server) {
+            Some(checkpoint_server_result) => *checkpoint_server_result = server_result.clone(),
+            None => checkpoint.results.servers.push(server_result.clone()),
+        }
+
+        if let Err(err) = write_checkpoint(&self.path, &checkpoint) {
+            warn!("Unable to write checkpoint to '{:?}': {:?}", self.path, err);
+        }
+    }
+
+    /// Writes a checkpoint with the specified [ServerResult], updated to include the specified
+    /// [ServerOperationLog]s.
+    ///
+    /// Parameters:
+    /// * `server_result`: the [ServerResult] to write
+    /// * `operations`: the [ServerOperationLog]s for the operations completed so far for the server
+    pub fn save_operations(&self, server_result: &ServerResult, operations: &[ServerOperationLog]) {
+        self.save_server(&ServerResult {
+            operations: Some(operations.to_vec()),
+            ..server_result.clone()
+        });
+    }
+}
+
+/// Returns `true` if the specified [ServerResult] from a resumed checkpoint is already finished with, and
+/// so should be skipped: either its server was shut down after being tested, or it failed to launch.
+///
+/// Parameters:
+/// * `server_result`: the [ServerResult] to check
+pub fn is_server_finished(server_result: &ServerResult) -> bool {
+    server_result.shutdown.is_some()
+        || server_result
+            .launch
+            .as_ref()
+            .map(|launch| !launch.is_ok())
+            .unwrap_or(false)
+}
+
+/// Reads the specified checkpoint file.
+///
+/// Parameters:
+/// * `path`: the checkpoint file to read
+fn read_checkpoint(path: &Path) -> Result<Checkpoint> {
+    let checkpoint = std::fs::read_to_string(path)
+        .with_context(|| format!("Unable to read checkpoint '{:?}'.", path))?;
+    serde_json::from_str(&checkpoint)
+        .with_context(|| format!("Unable to parse checkpoint '{:?}'.", path))
+}
+
+/// Writes the specified checkpoint file, via a temporary file, so that a crash partway through writing
+/// won't corrupt the previous checkpoint.
+///
+/// Parameters:
+/// * `path`: the checkpoint file to write
+/// * `checkpoint`: the [Checkpoint] to write
+fn write_checkpoint(path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, serde_json::to_string_pretty(checkpoint)?)?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Verifies that the specified [Checkpoint] can be resumed with the current config and sample data.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `checkpoint`: the [Checkpoint] to be resumed
+/// * `sample_data_fingerprint`: the [crate::sample_data::SampleData::fingerprint] of the current sample data
+fn verify_resumable(
+    app_state: &AppState,
+    checkpoint: &Checkpoint,
+    sample_data_fingerprint: &str,
+) -> Result<()> {
+    if serde_json::to_value(&checkpoint.results.config)? != serde_json::to_value(&app_state.config)?
+    {
+        return Err(eyre!(
+            "Unable to resume: the checkpoint was created with a different config."
+        ));
+    }
+    if checkpoint.sample_data_fingerprint != sample_data_fingerprint {
+        return Err(eyre!(
+            "Unable to resume: the checkpoint was created with different sample data ('{}' vs. '{}').",
+            checkpoint.sample_data_fingerprint,
+            sample_data_fingerprint
+        ));
+    }
+
+    Ok(())
+}
+
+/// Unit tests for the checkpoints.
+#[cfg(test)]
+mod tests {
+    use super::Checkpoint;
+    use crate::config::AppConfig;
+    use crate::test_framework::workload_plan::WorkloadPlan;
+    use crate::test_framework::{
+        FrameworkOperationLog, FrameworkOperationResult, FrameworkResults, ServerResult,
+    };
+    use chrono::prelude::*;
+
+    /// Verifies that checkpoints can be written and then read back in.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn write_and_read_checkpoint() {
+        let config = AppConfig::new().expect("Unable to load config.");
+        let workload_plan = WorkloadPlan {
+            seed: 42,
+            sample_data_fingerprint: "0badf00d".into(),
+            organizations: vec!["org-a".into()],
+            patients: vec!["patient-a.json".into()],
+        };
+        let checkpoint = Checkpoint {
+            sample_data_fingerprint: "0badf00d".into(),
+            results: FrameworkResults::new(&config, &workload_plan, &[]),
+        };
+
+        let temp_dir = tempfile::tempdir().expect("Unable to create temp dir.");
+        let path = temp_dir.path().join("checkpoint.json");
+        super::write_checkpoint(&path, &checkpoint).expect("Unable to write checkpoint.");
+        let actual = super::read_checkpoint(&path).expect("Unable to read checkpoint.");
+
+        assert_eq!("0badf00d", actual.sample_data_fingerprint);
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            serde_json::to_value(&actual.results.config).unwrap()
+        );
+        assert_eq!(workload_plan, actual.results.workload_plan);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    /// Verifies that [super::is_server_finished] only skips servers that were tested or failed to launch.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn is_server_finished() {
+        let log = |outcome| FrameworkOperationLog {
+            started: Utc.ymd(2020, 1, 1).and_hms(15, 0, 0),
+            completed: Utc.ymd(2020, 1, 1).and_hms(15, 0, 1),
+            outcome,
+        };
+
+        let mut server_result = ServerResult::new("Fake HAPI".into());
+        assert!(!super::is_server_finished(&server_result));
+
+        server_result.launch = Some(log(FrameworkOperationResult::Ok()));
+        server_result.operations = Some(vec![]);
+        assert!(!super::is_server_finished(&server_result));
+
+        server_result.shutdown = Some(log(FrameworkOperationResult::Ok()));
+        assert!(super::is_server_finished(&server_result));
+
+        let mut server_result = ServerResult::new("Fake HAPI".into());
+        server_result.launch = Some(log(FrameworkOperationResult::Errs(vec!["boom".into()])));
+        assert!(super::is_server_finished(&server_result));
+    }
+}
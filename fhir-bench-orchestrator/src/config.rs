@@ -1,11 +1,13 @@
 //! Application configuration.
 
-use crate::util::serde_duration_millis;
+use crate::errors::AppError;
+use crate::util::{serde_duration_millis, serde_duration_millis_option};
 use chrono::Duration;
 use eyre::{eyre, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 /// The environment variable key for the [AppConfig.iterations] setting.
 pub const ENV_KEY_ITERATIONS: &str = "FHIR_BENCH_ITERATIONS";
@@ -16,13 +18,111 @@ pub const ENV_KEY_OPERATION_TIMEOUT: &str = "FHIR_BENCH_OPERATION_TIMEOUT_MS";
 /// The environment variable key for the [AppConfig.concurrency_levels] setting.
 pub const ENV_KEY_CONCURRENCY_LEVELS: &str = "FHIR_BENCH_CONCURRENCY_LEVELS";
 
+/// The environment variable key for the [AppConfig.operation_overrides] setting, as a semicolon-separated
+/// list of `operation.setting=value` entries, where the setting is one of `iterations`,
+/// `operation_timeout_ms`, or `concurrency_levels` (as a comma-separated list), e.g.
+/// `metadata.operation_timeout_ms=1000;scenario_open_chart.concurrency_levels=1,4`.
+pub const ENV_KEY_OPERATION_OVERRIDES: &str = "FHIR_BENCH_OPERATION_OVERRIDES";
+
 /// The environment variable key for the [AppConfig.population_size] setting.
 pub const ENV_KEY_POPULATION_SIZE: &str = "FHIR_BENCH_POPULATION_SIZE";
 
+/// The environment variable key for the [AppConfig.expected_interval] setting (in milliseconds).
+pub const ENV_KEY_EXPECTED_INTERVAL: &str = "FHIR_BENCH_EXPECTED_INTERVAL_MS";
+
+/// The environment variable key for the [AppConfig.measurement_duration] setting (in milliseconds).
+pub const ENV_KEY_MEASUREMENT_DURATION: &str = "FHIR_BENCH_MEASUREMENT_DURATION_MS";
+
+/// The environment variable key for the [AppConfig.min_iterations] setting.
+pub const ENV_KEY_MIN_ITERATIONS: &str = "FHIR_BENCH_MIN_ITERATIONS";
+
+/// The environment variable key for the [AppConfig.max_iterations] setting.
+pub const ENV_KEY_MAX_ITERATIONS: &str = "FHIR_BENCH_MAX_ITERATIONS";
+
+/// The environment variable key for the [AppConfig.warmup_iterations] setting.
+pub const ENV_KEY_WARMUP_ITERATIONS: &str = "FHIR_BENCH_WARMUP_ITERATIONS";
+
+/// The environment variable key for the [AppConfig.warmup_duration] setting (in milliseconds).
+pub const ENV_KEY_WARMUP_DURATION: &str = "FHIR_BENCH_WARMUP_DURATION_MS";
+
+/// The environment variable key for the [AppConfig.interval_window] setting (in milliseconds).
+pub const ENV_KEY_INTERVAL_WINDOW: &str = "FHIR_BENCH_INTERVAL_WINDOW_MS";
+
+/// The environment variable key for the [AppConfig.trials] setting.
+pub const ENV_KEY_TRIALS: &str = "FHIR_BENCH_TRIALS";
+
+/// The environment variable key for the [ThinkTime.mean] setting (in milliseconds). Setting this enables
+/// [AppConfig.think_time].
+pub const ENV_KEY_THINK_TIME: &str = "FHIR_BENCH_THINK_TIME_MS";
+
+/// The environment variable key for the [ThinkTime.distribution] setting, which must be one of `constant`,
+/// `uniform`, or `exponential`.
+pub const ENV_KEY_THINK_TIME_DISTRIBUTION: &str = "FHIR_BENCH_THINK_TIME_DISTRIBUTION";
+
+/// The environment variable key for the [AppConfig.scenario_mix] setting, as a comma-separated list of
+/// `scenario=weight` entries, e.g. `scenario_open_chart=70,read_patient=30`.
+pub const ENV_KEY_SCENARIO_MIX: &str = "FHIR_BENCH_SCENARIO_MIX";
+
+/// The environment variable key for the [AppConfig.workload_seed] setting.
+pub const ENV_KEY_WORKLOAD_SEED: &str = "FHIR_BENCH_WORKLOAD_SEED";
+
+/// The environment variable key for the [AppConfig.replay] setting's [ReplayConfig.file], which enables it.
+pub const ENV_KEY_REPLAY_FILE: &str = "FHIR_BENCH_REPLAY_FILE";
+
+/// The environment variable key for the [AppConfig.replay] setting's [ReplayConfig.speed]: `recorded` (the
+/// default) or `max`.
+pub const ENV_KEY_REPLAY_SPEED: &str = "FHIR_BENCH_REPLAY_SPEED";
+
+/// The environment variable key for the [AppConfig.compression] setting, as a comma-separated list of
+/// [ContentEncoding]s, e.g. `identity,gzip,br`.
+pub const ENV_KEY_COMPRESSION: &str = "FHIR_BENCH_COMPRESSION";
+
+/// The environment variable key for the [AppConfig.transport] setting, which must be one of `http1`,
+/// `http2`, `http2-alpn`, or `new-connection`.
+pub const ENV_KEY_TRANSPORT: &str = "FHIR_BENCH_TRANSPORT";
+
+/// The environment variable key for the maximum number of idle connections that each virtual user keeps
+/// open to the server, which is only supported for the `http1` [AppConfig.transport].
+pub const ENV_KEY_TRANSPORT_POOL_SIZE: &str = "FHIR_BENCH_TRANSPORT_POOL_SIZE";
+
+/// The environment variable key for the [CircuitBreakerConfig.window] setting. Setting this enables the
+/// [AppConfig.circuit_breaker].
+pub const ENV_KEY_CIRCUIT_BREAKER_WINDOW: &str = "FHIR_BENCH_CIRCUIT_BREAKER_WINDOW";
+
+/// The environment variable key for the [CircuitBreakerConfig.max_failure_rate] setting.
+pub const ENV_KEY_CIRCUIT_BREAKER_MAX_FAILURE_RATE: &str =
+    "FHIR_BENCH_CIRCUIT_BREAKER_MAX_FAILURE_RATE";
+
+/// The environment variable key for the [AppConfig.servers_file] setting.
+pub const ENV_KEY_SERVERS_FILE: &str = "FHIR_BENCH_SERVERS_FILE";
+
+/// The environment variable key for the [AppConfig.checkpoint] setting.
+pub const ENV_KEY_CHECKPOINT: &str = "FHIR_BENCH_CHECKPOINT";
+
+/// The environment variable key for the [AppConfig.resume] setting, which must be `true` or `false`.
+pub const ENV_KEY_RESUME: &str = "FHIR_BENCH_RESUME";
+
+/// The environment variable key for the [SaturationConfig.slo_p99] setting (in milliseconds). Setting this
+/// enables the [AppConfig.saturation] mode.
+pub const ENV_KEY_SATURATION_SLO_P99: &str = "FHIR_BENCH_SATURATION_SLO_P99_MS";
+
+/// The environment variable key for the [SaturationConfig.max_error_rate] setting.
+pub const ENV_KEY_SATURATION_MAX_ERROR_RATE: &str = "FHIR_BENCH_SATURATION_MAX_ERROR_RATE";
+
+/// The environment variable key for the [SaturationConfig.step_factor] setting.
+pub const ENV_KEY_SATURATION_STEP_FACTOR: &str = "FHIR_BENCH_SATURATION_STEP_FACTOR";
+
+/// The environment variable key for the [SaturationConfig.max_concurrency] setting.
+pub const ENV_KEY_SATURATION_MAX_CONCURRENCY: &str = "FHIR_BENCH_SATURATION_MAX_CONCURRENCY";
+
+/// The environment variable key for the [SaturationConfig.resolution] setting.
+pub const ENV_KEY_SATURATION_RESOLUTION: &str = "FHIR_BENCH_SATURATION_RESOLUTION";
+
 /// Represents the application's configuration.
 #[derive(Clone, Deserialize, Serialize)]
 pub struct AppConfig {
-    /// The maximum number of iterations to exercise each operation for, during a benchmark run.
+    /// The maximum number of iterations to exercise each operation for, during a benchmark run. This is
+    /// ignored if [AppConfig.measurement_duration] is set.
     pub iterations: u32,
 
     /// The maximum amount of time to let any individual operation being benchmarked run for.
@@ -33,8 +133,516 @@ pub struct AppConfig {
     /// specified number of concurrent users.
     pub concurrency_levels: Vec<u32>,
 
+    /// The [OperationOverrides] for any operations that need different settings than the rest, e.g. a longer
+    /// [AppConfig.operation_timeout] for large transaction `Bundle`s.
+    pub operation_overrides: Vec<OperationOverrides>,
+
     /// The maximum synthetic patient population size to benchmark with.
     pub population_size: u32,
+
+    /// The interval that each simulated user is expected to issue requests at, if any. When set, every
+    /// measurement will also produce a latency histogram that has been corrected for coordinated omission:
+    /// any iteration that takes longer than this will be treated as having also delayed the requests that
+    /// would otherwise have been sent while it was stalled. When not set, the corrected latencies will just
+    /// match the raw ones.
+    #[serde(with = "serde_duration_millis_option")]
+    pub expected_interval: Option<Duration>,
+
+    /// If set, each measurement will run iterations for this long, rather than for a fixed number of
+    /// [AppConfig.iterations].
+    #[serde(with = "serde_duration_millis_option")]
+    pub measurement_duration: Option<Duration>,
+
+    /// The minimum number of iterations to run for each measurement, even if that takes longer than the
+    /// [AppConfig.measurement_duration]. Only used if that is set.
+    pub min_iterations: Option<u32>,
+
+    /// The maximum number of iterations to run for each measurement, even if that ends it before the
+    /// [AppConfig.measurement_duration] has elapsed. Only used if that is set.
+    pub max_iterations: Option<u32>,
+
+    /// The number of warmup iterations to run before each measurement, if any. Warmup iterations are run
+    /// just like any other, but are not included in the measurement's results.
+    pub warmup_iterations: Option<u32>,
+
+    /// How long to run warmup iterations for before each measurement, if at all. If this and
+    /// [AppConfig.warmup_iterations] are both set, the warmup will continue until both are satisfied.
+    #[serde(with = "serde_duration_millis_option")]
+    pub warmup_duration: Option<Duration>,
+
+    /// The length of each time window that measurements are broken down into, for their time series of
+    /// interval metrics.
+    #[serde(with = "serde_duration_millis")]
+    pub interval_window: Duration,
+
+    /// The number of times to repeat each operation's measurement at each of the
+    /// [AppConfig.concurrency_levels]. The trials are interleaved, running every concurrency level once
+    /// before moving on to the next trial, so that any drift in the server's performance is spread across
+    /// all of them. This is ignored in [AppConfig.saturation] mode.
+    pub trials: u32,
+
+    /// If set, each virtual user will pause for this long between its iterations, as a real user would
+    /// between their requests.
+    pub think_time: Option<ThinkTime>,
+
+    /// If not empty, an additional mixed-workload operation will be run, where each iteration runs one of
+    /// these scenarios (either built-in or loaded from the `scenarios/` directory), picked at random in
+    /// proportion to its [ScenarioMixEntry.weight]. This models the contention between different kinds of
+    /// traffic that a real server has to deal with.
+    pub scenario_mix: Vec<ScenarioMixEntry>,
+
+    /// The seed that the run's [crate::test_framework::workload_plan::WorkloadPlan] is generated from, which
+    /// decides which sample data each iteration uses and in what order. Every server gets the same plan, and
+    /// re-running with the same seed (and sample data) will replay it exactly. Defaults to `0`.
+    pub workload_seed: u64,
+
+    /// If set, an additional traffic replay operation will be run, which replays a log of recorded FHIR
+    /// requests (e.g. anonymized production traffic) against the server.
+    pub replay: Option<ReplayConfig>,
+
+    /// If not empty, each measurement will be repeated for each of these [ContentEncoding]s, which will be
+    /// requested via `Accept-Encoding` and (where supported) used to compress request bodies. Otherwise, no
+    /// encoding will be requested.
+    pub compression: Vec<ContentEncoding>,
+
+    /// The [Transport] that the HTTP clients will use to connect to the server.
+    pub transport: Transport,
+
+    /// If set, each measurement will be halted early if too many of its recent iterations fail, with the
+    /// rest of its iterations marked as skipped, rather than waiting out every failing iteration.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    /// If set, each operation will be run in saturation search mode, which ignores
+    /// [AppConfig.concurrency_levels] and instead ramps up the concurrency level until the operation no
+    /// longer meets the configured service level objective.
+    pub saturation: Option<SaturationConfig>,
+
+    /// The TOML file to load the definitions of the FHIR servers to benchmark from (see
+    /// [crate::servers::create_server_plugins]), if not the shipped `server_builds/servers.toml`.
+    pub servers_file: Option<PathBuf>,
+
+    /// If set, a checkpoint of the run's results will be written to this file after each operation is
+    /// tested for each server, so that the run can be resumed from it if it's interrupted or crashes. This
+    /// isn't included in the results, as it doesn't affect them.
+    #[serde(skip)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// If `true`, the run will resume from the [AppConfig.checkpoint], skipping all of the work that was
+    /// already completed. This isn't included in the results, as it doesn't affect them.
+    #[serde(skip)]
+    pub resume: bool,
+}
+
+/// Overrides some of the [AppConfig] settings for a single operation.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OperationOverrides {
+    /// The name of the operation that these overrides apply to.
+    pub operation: String,
+
+    /// Overrides [AppConfig.iterations], if set.
+    pub iterations: Option<u32>,
+
+    /// Overrides [AppConfig.operation_timeout], if set.
+    #[serde(with = "serde_duration_millis_option")]
+    pub operation_timeout: Option<Duration>,
+
+    /// Overrides [AppConfig.concurrency_levels], if set.
+    pub concurrency_levels: Option<Vec<u32>>,
+}
+
+impl OperationOverrides {
+    /// Parses the [AppConfig.operation_overrides] from the format described for
+    /// [ENV_KEY_OPERATION_OVERRIDES], merging all of the entries for each operation together.
+    ///
+    /// Parameters:
+    /// * `value`: the value to parse
+    ///
+    /// Returns the [OperationOverrides] for each operation, in the order that they were first listed.
+    pub fn parse_all(value: &str) -> std::result::Result<Vec<OperationOverrides>, AppError> {
+        let mut operation_overrides: Vec<OperationOverrides> = vec![];
+        for entry in value.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let invalid = || AppError::InvalidOperationOverride(entry.into());
+            let (key, setting_value) = entry.split_once('=').ok_or_else(invalid)?;
+            let (operation, setting) = key.trim().rsplit_once('.').ok_or_else(invalid)?;
+            if operation.is_empty() {
+                return Err(invalid());
+            }
+
+            let index = match operation_overrides
+                .iter()
+                .position(|o| o.operation == operation)
+            {
+                Some(index) => index,
+                None => {
+                    operation_overrides.push(OperationOverrides {
+                        operation: operation.into(),
+                        iterations: None,
+                        operation_timeout: None,
+                        concurrency_levels: None,
+                    });
+                    operation_overrides.len() - 1
+                }
+            };
+            let overrides = &mut operation_overrides[index];
+            let setting_value = setting_value.trim();
+            match setting {
+                "iterations" => {
+                    overrides.iterations = Some(setting_value.parse().map_err(|_| invalid())?)
+                }
+                "operation_timeout_ms" => {
+                    let operation_timeout: u32 = setting_value.parse().map_err(|_| invalid())?;
+                    overrides.operation_timeout =
+                        Some(Duration::milliseconds(operation_timeout as i64));
+                }
+                "concurrency_levels" => {
+                    let concurrency_levels: std::result::Result<Vec<u32>, _> = setting_value
+                        .split(',')
+                        .map(|level| level.trim().parse::<u32>())
+                        .collect();
+                    overrides.concurrency_levels = Some(concurrency_levels.map_err(|_| invalid())?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(operation_overrides)
+    }
+
+    /// Checks that each of the specified [OperationOverrides] is for a known operation, as overrides for
+    /// anything else (e.g. a misspelled operation) would otherwise be silently ignored.
+    ///
+    /// Parameters:
+    /// * `operation_overrides`: the [AppConfig.operation_overrides] to check
+    /// * `operations`: the names of all of the operations that can be run, including any loaded scenarios
+    ///
+    /// Returns an error for the first override that's for an unknown operation, if any.
+    pub fn check_all(
+        operation_overrides: &[OperationOverrides],
+        operations: &[&str],
+    ) -> std::result::Result<(), AppError> {
+        match operation_overrides
+            .iter()
+            .find(|overrides| !operations.contains(&overrides.operation.as_str()))
+        {
+            Some(overrides) => Err(AppError::UnknownOverrideOperation(
+                overrides.operation.clone(),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Configures the saturation search mode, which finds the highest level of concurrency that each operation
+/// can sustain while still meeting a latency service level objective (SLO).
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SaturationConfig {
+    /// The p99 latency that each concurrency level must stay at or under to pass. This is compared against
+    /// the p99 latency that has been corrected for coordinated omission, per [AppConfig.expected_interval].
+    #[serde(with = "serde_duration_millis")]
+    pub slo_p99: Duration,
+
+    /// The ratio of failed iterations (from `0.0` to `1.0`) that each concurrency level must stay at or
+    /// under to pass.
+    pub max_error_rate: f64,
+
+    /// How much to multiply the concurrency level by after each passing step (it will always be increased
+    /// by at least one), until a step fails.
+    pub step_factor: f64,
+
+    /// The highest concurrency level to try, after which the search will stop even if the SLO is still
+    /// being met.
+    pub max_concurrency: u32,
+
+    /// Once a step fails, the search bisects between the highest passing and lowest failing concurrency
+    /// levels, until they're no more than this many users apart.
+    pub resolution: u32,
+}
+
+impl SaturationConfig {
+    /// Constructs a new [SaturationConfig], after checking that its settings are in range.
+    ///
+    /// Parameters:
+    /// * `slo_p99`: the [SaturationConfig.slo_p99]
+    /// * `max_error_rate`: the [SaturationConfig.max_error_rate], which must be from `0.0` to `1.0`
+    /// * `step_factor`: the [SaturationConfig.step_factor], which must be a finite value greater than `1.0`
+    /// * `max_concurrency`: the [SaturationConfig.max_concurrency], which must be greater than zero
+    /// * `resolution`: the [SaturationConfig.resolution], which must be greater than zero
+    pub fn new(
+        slo_p99: Duration,
+        max_error_rate: f64,
+        step_factor: f64,
+        max_concurrency: u32,
+        resolution: u32,
+    ) -> std::result::Result<Self, AppError> {
+        if !(0.0..=1.0).contains(&max_error_rate) {
+            return Err(AppError::SettingOutOfRange(
+                ENV_KEY_SATURATION_MAX_ERROR_RATE.into(),
+                max_error_rate.to_string(),
+                "a value from 0.0 to 1.0".into(),
+            ));
+        }
+        if !step_factor.is_finite() || step_factor <= 1.0 {
+            return Err(AppError::SettingOutOfRange(
+                ENV_KEY_SATURATION_STEP_FACTOR.into(),
+                step_factor.to_string(),
+                "a finite value greater than 1.0".into(),
+            ));
+        }
+        if max_concurrency == 0 {
+            return Err(AppError::SettingOutOfRange(
+                ENV_KEY_SATURATION_MAX_CONCURRENCY.into(),
+                max_concurrency.to_string(),
+                "a value greater than zero".into(),
+            ));
+        }
+        if resolution == 0 {
+            return Err(AppError::SettingOutOfRange(
+                ENV_KEY_SATURATION_RESOLUTION.into(),
+                resolution.to_string(),
+                "a value greater than zero".into(),
+            ));
+        }
+
+        Ok(SaturationConfig {
+            slo_p99,
+            max_error_rate,
+            step_factor,
+            max_concurrency,
+            resolution,
+        })
+    }
+}
+
+/// Configures the circuit breaker, which halts measurements early once the server is failing nearly every
+/// request, as there's no point waiting out the [AppConfig.operation_timeout] for all of the rest.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CircuitBreakerConfig {
+    /// The number of most-recently completed iterations to calculate the failure rate over. The circuit
+    /// breaker won't trip until at least this many iterations have completed.
+    pub window: u32,
+
+    /// The ratio of failed iterations (from `0.0` to `1.0`) in the window that will trip the circuit
+    /// breaker, once exceeded.
+    pub max_failure_rate: f64,
+}
+
+impl CircuitBreakerConfig {
+    /// Constructs a new [CircuitBreakerConfig], after checking that its settings are in range.
+    ///
+    /// Parameters:
+    /// * `window`: the [CircuitBreakerConfig.window], which must be greater than zero
+    /// * `max_failure_rate`: the [CircuitBreakerConfig.max_failure_rate], which must be from `0.0` to `1.0`
+    pub fn new(window: u32, max_failure_rate: f64) -> std::result::Result<Self, AppError> {
+        if window == 0 {
+            return Err(AppError::SettingOutOfRange(
+                ENV_KEY_CIRCUIT_BREAKER_WINDOW.into(),
+                window.to_string(),
+                "a value greater than zero".into(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&max_failure_rate) {
+            return Err(AppError::SettingOutOfRange(
+                ENV_KEY_CIRCUIT_BREAKER_MAX_FAILURE_RATE.into(),
+                max_failure_rate.to_string(),
+                "a value from 0.0 to 1.0".into(),
+            ));
+        }
+
+        Ok(CircuitBreakerConfig {
+            window,
+            max_failure_rate,
+        })
+    }
+}
+
+/// Configures how long each virtual user pauses between its iterations.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ThinkTime {
+    /// The average think time.
+    #[serde(with = "serde_duration_millis")]
+    pub mean: Duration,
+
+    /// The [ThinkTimeDistribution] that think times are drawn from.
+    pub distribution: ThinkTimeDistribution,
+}
+
+/// Enumerates the supported distributions of [ThinkTime]s.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ThinkTimeDistribution {
+    /// Every think time is exactly the [ThinkTime.mean].
+    Constant,
+
+    /// Think times are drawn uniformly from between zero and twice the [ThinkTime.mean].
+    Uniform,
+
+    /// Think times are drawn from an exponential distribution, which models users that each act
+    /// independently of each other, at random.
+    Exponential,
+}
+
+impl FromStr for ThinkTimeDistribution {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "constant" => Ok(ThinkTimeDistribution::Constant),
+            "uniform" => Ok(ThinkTimeDistribution::Uniform),
+            "exponential" => Ok(ThinkTimeDistribution::Exponential),
+            _ => Err(AppError::UnsupportedThinkTimeDistribution(value.into())),
+        }
+    }
+}
+
+/// Enumerates the HTTP content encodings that can be requested via [AppConfig.compression].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ContentEncoding {
+    /// No compression.
+    Identity,
+
+    /// Gzip compression, which will also be used to compress request bodies.
+    Gzip,
+
+    /// Deflate (zlib) compression, which will also be used to compress request bodies.
+    Deflate,
+
+    /// Brotli compression, which will also be used to compress request bodies.
+    Brotli,
+}
+
+impl ContentEncoding {
+    /// Returns the `Accept-Encoding`/`Content-Encoding` header value for this [ContentEncoding].
+    pub fn header_value(&self) -> &'static str {
+        match self {
+            ContentEncoding::Identity => "identity",
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Deflate => "deflate",
+            ContentEncoding::Brotli => "br",
+        }
+    }
+}
+
+impl FromStr for ContentEncoding {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "identity" => Ok(ContentEncoding::Identity),
+            "gzip" => Ok(ContentEncoding::Gzip),
+            "deflate" => Ok(ContentEncoding::Deflate),
+            "br" => Ok(ContentEncoding::Brotli),
+            _ => Err(AppError::UnsupportedContentEncoding(value.into())),
+        }
+    }
+}
+
+/// Configures the traffic replay operation, per [AppConfig.replay].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ReplayConfig {
+    /// The file of recorded requests to replay: either NDJSON (one request per line) or, if its extension is
+    /// `.har`, an HTTP Archive.
+    pub file: PathBuf,
+
+    /// How quickly to replay the recorded requests.
+    pub speed: ReplaySpeed,
+}
+
+/// Enumerates the speeds that recorded requests can be replayed at, per [ReplayConfig.speed].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ReplaySpeed {
+    /// Each request is sent at its recorded time, relative to the start of the recording (as far as the
+    /// measurement's `concurrent_users` can keep up). The recording loops if more iterations are needed.
+    Recorded,
+
+    /// Each request is sent as soon as a virtual user is ready to send it, ignoring the recorded times.
+    AsFastAsPossible,
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "recorded" => Ok(ReplaySpeed::Recorded),
+            "max" => Ok(ReplaySpeed::AsFastAsPossible),
+            _ => Err(AppError::UnsupportedReplaySpeed(value.into())),
+        }
+    }
+}
+
+/// Enumerates the HTTP transports that can be configured via [AppConfig.transport].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Transport {
+    /// HTTP/1.1, with each virtual user keeping its connections alive for reuse by its later requests.
+    Http1KeepAlive {
+        /// The maximum number of idle connections that each virtual user keeps open to the server, or
+        /// `None` for no limit.
+        pool_size: Option<u32>,
+    },
+
+    /// HTTP/2, which is always used: with prior knowledge for `http` servers, and as the only protocol
+    /// offered via ALPN for `https` servers, so the server must support it.
+    Http2PriorKnowledge,
+
+    /// HTTP/2 or HTTP/1.1, as negotiated with `https` servers via ALPN (as browsers do), so HTTP/2 is only
+    /// used if the server supports it. As `http` servers can't negotiate, HTTP/1.1 is used for those.
+    Http2Alpn,
+
+    /// HTTP/1.1, with a new TCP (and TLS, if used) connection opened for every request, rather than reusing
+    /// them.
+    NewConnectionPerRequest,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::Http1KeepAlive { pool_size: None }
+    }
+}
+
+impl FromStr for Transport {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "http1" => Ok(Transport::Http1KeepAlive { pool_size: None }),
+            "http2" => Ok(Transport::Http2PriorKnowledge),
+            "http2-alpn" => Ok(Transport::Http2Alpn),
+            "new-connection" => Ok(Transport::NewConnectionPerRequest),
+            _ => Err(AppError::UnsupportedTransport(value.into())),
+        }
+    }
+}
+
+/// Configures one of the scenarios in the [AppConfig.scenario_mix].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScenarioMixEntry {
+    /// The name of the scenario to run, which must be either one of the built-in scenarios or one loaded
+    /// from the `scenarios/` directory.
+    pub scenario: String,
+
+    /// The relative share of the mix's iterations to run this scenario for.
+    pub weight: u32,
+}
+
+impl FromStr for ScenarioMixEntry {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || AppError::InvalidScenarioMixEntry(value.into());
+        let separator = value.rfind('=').ok_or_else(invalid)?;
+        let scenario = value[..separator].trim();
+        let weight: u32 = value[separator + 1..]
+            .trim()
+            .parse()
+            .map_err(|_| invalid())?;
+        if scenario.is_empty() || weight == 0 {
+            return Err(invalid());
+        }
+
+        Ok(ScenarioMixEntry {
+            scenario: scenario.into(),
+            weight,
+        })
+    }
 }
 
 impl AppConfig {
@@ -70,6 +678,13 @@ impl AppConfig {
         let concurrency_levels = concurrency_levels
             .context(format!("Unable to parse {}.", ENV_KEY_CONCURRENCY_LEVELS))?;
 
+        // Parse operation_overrides.
+        let operation_overrides = match env::var(ENV_KEY_OPERATION_OVERRIDES) {
+            Ok(operation_overrides) => OperationOverrides::parse_all(&operation_overrides)
+                .context(format!("Unable to parse {}.", ENV_KEY_OPERATION_OVERRIDES))?,
+            Err(_) => vec![],
+        };
+
         // Parse population_size.
         let population_size: std::result::Result<String, std::env::VarError> =
             env::var(ENV_KEY_POPULATION_SIZE).or_else(|_| Ok(String::from("100")));
@@ -78,20 +693,237 @@ impl AppConfig {
             .parse()
             .context(format!("Unable to parse {}.", ENV_KEY_POPULATION_SIZE))?;
 
+        // Parse expected_interval.
+        let expected_interval: Option<u32> = parse_env_optional(ENV_KEY_EXPECTED_INTERVAL)?;
+        let expected_interval = expected_interval
+            .map(|expected_interval| Duration::milliseconds(expected_interval as i64));
+
+        // Parse measurement_duration, min_iterations, and max_iterations.
+        let measurement_duration: Option<u32> = parse_env_optional(ENV_KEY_MEASUREMENT_DURATION)?;
+        let measurement_duration = measurement_duration
+            .map(|measurement_duration| Duration::milliseconds(measurement_duration as i64));
+        let min_iterations: Option<u32> = parse_env_optional(ENV_KEY_MIN_ITERATIONS)?;
+        let max_iterations: Option<u32> = parse_env_optional(ENV_KEY_MAX_ITERATIONS)?;
+        if let (Some(min_iterations), Some(max_iterations)) = (min_iterations, max_iterations) {
+            if min_iterations > max_iterations {
+                return Err(eyre!(
+                    "{} must not be greater than {}.",
+                    ENV_KEY_MIN_ITERATIONS,
+                    ENV_KEY_MAX_ITERATIONS
+                ));
+            }
+        }
+
+        // Parse warmup_iterations and warmup_duration.
+        let warmup_iterations: Option<u32> = parse_env_optional(ENV_KEY_WARMUP_ITERATIONS)?;
+        let warmup_duration: Option<u32> = parse_env_optional(ENV_KEY_WARMUP_DURATION)?;
+        let warmup_duration =
+            warmup_duration.map(|warmup_duration| Duration::milliseconds(warmup_duration as i64));
+
+        // Parse interval_window.
+        let interval_window: u32 = parse_env_optional(ENV_KEY_INTERVAL_WINDOW)?.unwrap_or(1000);
+        if interval_window == 0 {
+            return Err(eyre!(
+                "{} must be greater than zero.",
+                ENV_KEY_INTERVAL_WINDOW
+            ));
+        }
+        let interval_window = Duration::milliseconds(interval_window as i64);
+
+        // Parse trials.
+        let trials: u32 = parse_env_optional(ENV_KEY_TRIALS)?.unwrap_or(1);
+        if trials == 0 {
+            return Err(eyre!("{} must be greater than zero.", ENV_KEY_TRIALS));
+        }
+
+        // Parse think_time.
+        let think_time: Option<u32> = parse_env_optional(ENV_KEY_THINK_TIME)?;
+        let think_time = match think_time {
+            Some(think_time) => Some(ThinkTime {
+                mean: Duration::milliseconds(think_time as i64),
+                distribution: parse_env_optional(ENV_KEY_THINK_TIME_DISTRIBUTION)?
+                    .unwrap_or(ThinkTimeDistribution::Exponential),
+            }),
+            None => None,
+        };
+
+        // Parse scenario_mix.
+        let scenario_mix: std::result::Result<Vec<ScenarioMixEntry>, _> =
+            match env::var(ENV_KEY_SCENARIO_MIX) {
+                Ok(scenario_mix) => scenario_mix.split(',').map(str::parse).collect(),
+                Err(_) => Ok(vec![]),
+            };
+        let scenario_mix =
+            scenario_mix.context(format!("Unable to parse {}.", ENV_KEY_SCENARIO_MIX))?;
+
+        // Parse workload_seed.
+        let workload_seed: u64 = parse_env_optional(ENV_KEY_WORKLOAD_SEED)?.unwrap_or(0);
+
+        // Parse replay.
+        let replay_file: Option<PathBuf> = parse_env_optional(ENV_KEY_REPLAY_FILE)?;
+        let replay = match replay_file {
+            Some(file) => Some(ReplayConfig {
+                file,
+                speed: parse_env_optional(ENV_KEY_REPLAY_SPEED)?.unwrap_or(ReplaySpeed::Recorded),
+            }),
+            None => None,
+        };
+
+        // Parse compression.
+        let compression: std::result::Result<Vec<ContentEncoding>, _> =
+            match env::var(ENV_KEY_COMPRESSION) {
+                Ok(compression) => compression.split(',').map(str::parse).collect(),
+                Err(_) => Ok(vec![]),
+            };
+        let compression =
+            compression.context(format!("Unable to parse {}.", ENV_KEY_COMPRESSION))?;
+
+        // Parse transport.
+        let transport: Transport = parse_env_optional(ENV_KEY_TRANSPORT)?.unwrap_or_default();
+        let transport_pool_size: Option<u32> = parse_env_optional(ENV_KEY_TRANSPORT_POOL_SIZE)?;
+        let transport = match (transport, transport_pool_size) {
+            (Transport::Http1KeepAlive { .. }, pool_size) => {
+                Transport::Http1KeepAlive { pool_size }
+            }
+            (transport, None) => transport,
+            (_, Some(_)) => {
+                return Err(eyre!(
+                    "{} is only supported for the 'http1' {}.",
+                    ENV_KEY_TRANSPORT_POOL_SIZE,
+                    ENV_KEY_TRANSPORT
+                ))
+            }
+        };
+
+        // Parse circuit_breaker.
+        let circuit_breaker_window: Option<u32> =
+            parse_env_optional(ENV_KEY_CIRCUIT_BREAKER_WINDOW)?;
+        let circuit_breaker = match circuit_breaker_window {
+            Some(window) => Some(CircuitBreakerConfig::new(
+                window,
+                parse_env_optional(ENV_KEY_CIRCUIT_BREAKER_MAX_FAILURE_RATE)?.unwrap_or(0.9),
+            )?),
+            None => None,
+        };
+
+        // Parse saturation.
+        let saturation_slo_p99: Option<u32> = parse_env_optional(ENV_KEY_SATURATION_SLO_P99)?;
+        let saturation = match saturation_slo_p99 {
+            Some(saturation_slo_p99) => {
+                let max_error_rate: f64 =
+                    parse_env_optional(ENV_KEY_SATURATION_MAX_ERROR_RATE)?.unwrap_or(0.01);
+                let step_factor: f64 =
+                    parse_env_optional(ENV_KEY_SATURATION_STEP_FACTOR)?.unwrap_or(2.0);
+                let max_concurrency: u32 =
+                    parse_env_optional(ENV_KEY_SATURATION_MAX_CONCURRENCY)?.unwrap_or(1024);
+                let resolution: u32 =
+                    parse_env_optional(ENV_KEY_SATURATION_RESOLUTION)?.unwrap_or(1);
+                Some(SaturationConfig::new(
+                    Duration::milliseconds(saturation_slo_p99 as i64),
+                    max_error_rate,
+                    step_factor,
+                    max_concurrency,
+                    resolution,
+                )?)
+            }
+            None => None,
+        };
+
+        // Parse servers_file.
+        let servers_file: Option<PathBuf> = parse_env_optional(ENV_KEY_SERVERS_FILE)?;
+
+        // Parse checkpoint and resume.
+        let checkpoint: Option<PathBuf> = parse_env_optional(ENV_KEY_CHECKPOINT)?;
+        let resume: bool = parse_env_optional(ENV_KEY_RESUME)?.unwrap_or(false);
+        if resume && checkpoint.is_none() {
+            return Err(eyre!(
+                "{} requires {} to be set.",
+                ENV_KEY_RESUME,
+                ENV_KEY_CHECKPOINT
+            ));
+        }
+
         Ok(AppConfig {
             iterations,
             operation_timeout,
             concurrency_levels,
+            operation_overrides,
             population_size,
+            expected_interval,
+            measurement_duration,
+            min_iterations,
+            max_iterations,
+            warmup_iterations,
+            warmup_duration,
+            interval_window,
+            trials,
+            think_time,
+            scenario_mix,
+            workload_seed,
+            replay,
+            compression,
+            transport,
+            circuit_breaker,
+            saturation,
+            servers_file,
+            checkpoint,
+            resume,
         })
     }
 
+    /// Returns a copy of this [AppConfig] with any [AppConfig.operation_overrides] for the specified
+    /// operation applied.
+    ///
+    /// Parameters:
+    /// * `operation`: the name of the operation to get the effective [AppConfig] for
+    pub fn for_operation(&self, operation: &str) -> AppConfig {
+        let mut config = self.clone();
+        if let Some(overrides) = self
+            .operation_overrides
+            .iter()
+            .find(|overrides| overrides.operation == operation)
+        {
+            if let Some(iterations) = overrides.iterations {
+                config.iterations = iterations;
+            }
+            if let Some(operation_timeout) = overrides.operation_timeout {
+                config.operation_timeout = operation_timeout;
+            }
+            if let Some(concurrency_levels) = &overrides.concurrency_levels {
+                config.concurrency_levels = concurrency_levels.clone();
+            }
+        }
+        config
+    }
+
     /// Returns the root directory for the benchmarks project; the Git repo's top-level directory.
     pub fn benchmark_dir(&self) -> Result<PathBuf> {
         benchmark_dir()
     }
 }
 
+/// Reads and parses the specified environment variable, if it's present.
+///
+/// Parameters:
+/// * `key`: the name of the environment variable to read
+///
+/// Returns the parsed value, or `None` if the environment variable was not set.
+fn parse_env_optional<T>(key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env::var(key) {
+        Ok(value) => Ok(Some(
+            value
+                .parse()
+                .with_context(|| format!("Unable to parse {}.", key))?,
+        )),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Unable to read {}.", key)),
+    }
+}
+
 /// Returns the root directory for the benchmarks project; the Git repo's top-level directory.
 pub fn benchmark_dir() -> Result<PathBuf> {
     // For now, this is hard-coded to check a couple of likely scenarios:
@@ -122,3 +954,104 @@ pub fn benchmark_dir() -> Result<PathBuf> {
         ))
     }
 }
+
+/// Unit tests for the application configuration.
+#[cfg(test)]
+mod tests {
+    use super::{CircuitBreakerConfig, OperationOverrides, SaturationConfig};
+    use chrono::Duration;
+
+    /// Verifies that [OperationOverrides::parse_all] parses and merges overrides as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn parse_operation_overrides() {
+        let overrides = OperationOverrides::parse_all(
+            "metadata.operation_timeout_ms=1000; POST /Organization.iterations=50;\
+            metadata.concurrency_levels=1, 4",
+        )
+        .expect("Unable to parse overrides.");
+        assert_eq!(
+            vec![
+                OperationOverrides {
+                    operation: "metadata".into(),
+                    iterations: None,
+                    operation_timeout: Some(Duration::milliseconds(1000)),
+                    concurrency_levels: Some(vec![1, 4]),
+                },
+                OperationOverrides {
+                    operation: "POST /Organization".into(),
+                    iterations: Some(50),
+                    operation_timeout: None,
+                    concurrency_levels: None,
+                },
+            ],
+            overrides
+        );
+
+        for invalid in [
+            "metadata=1",
+            "metadata.iterations",
+            "metadata.foo=1",
+            ".iterations=1",
+        ] {
+            assert!(
+                OperationOverrides::parse_all(invalid).is_err(),
+                "Expected error for '{}'.",
+                invalid
+            );
+        }
+
+        assert!(
+            OperationOverrides::check_all(&overrides, &["metadata", "POST /Organization"]).is_ok()
+        );
+        assert!(OperationOverrides::check_all(&overrides, &["metadata"]).is_err());
+    }
+
+    /// Verifies that [CircuitBreakerConfig::new] rejects settings that are out of range.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn circuit_breaker_config_ranges() {
+        let circuit_breaker = CircuitBreakerConfig::new(20, 0.5).expect("Unable to configure.");
+        assert_eq!(20, circuit_breaker.window);
+        assert!(CircuitBreakerConfig::new(20, 0.0).is_ok());
+        assert!(CircuitBreakerConfig::new(20, 1.0).is_ok());
+
+        assert!(CircuitBreakerConfig::new(0, 0.5).is_err());
+        for invalid in [5.0, -1.0, f64::NAN] {
+            assert!(
+                CircuitBreakerConfig::new(20, invalid).is_err(),
+                "Expected error for '{}'.",
+                invalid
+            );
+        }
+    }
+
+    /// Verifies that [SaturationConfig::new] rejects settings that are out of range.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn saturation_config_ranges() {
+        let slo_p99 = Duration::milliseconds(100);
+        let saturation =
+            SaturationConfig::new(slo_p99, 0.01, 2.0, 1024, 1).expect("Unable to configure.");
+        assert_eq!(1024, saturation.max_concurrency);
+        assert!(SaturationConfig::new(slo_p99, 0.0, 1.5, 1, 1).is_ok());
+        assert!(SaturationConfig::new(slo_p99, 1.0, 1.5, 1, 1).is_ok());
+
+        for invalid in [5.0, -1.0, f64::NAN] {
+            assert!(
+                SaturationConfig::new(slo_p99, invalid, 2.0, 1024, 1).is_err(),
+                "Expected error for max_error_rate '{}'.",
+                invalid
+            );
+        }
+        for invalid in [1.0, 0.5, -2.0, f64::NAN, f64::INFINITY] {
+            assert!(
+                SaturationConfig::new(slo_p99, 0.01, invalid, 1024, 1).is_err(),
+                "Expected error for step_factor '{}'.",
+                invalid
+            );
+        }
+        assert!(SaturationConfig::new(slo_p99, 0.01, 2.0, 0, 1).is_err());
+        assert!(SaturationConfig::new(slo_p99, 0.01, 2.0, 1024, 0).is_err());
+    }
+}
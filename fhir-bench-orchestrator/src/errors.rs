@@ -14,4 +14,45 @@ pub enum AppError {
     /// Represents an error caused by an attempt to lookup an unknown server.
     #[error("unknown server '{0}'")]
     UnknownServerError(crate::servers::ServerName),
+
+    /// Represents an error caused by an attempt to configure an unknown think time distribution.
+    #[error("unsupported think time distribution '{0}'")]
+    UnsupportedThinkTimeDistribution(String),
+
+    /// Represents an error caused by an attempt to configure an invalid scenario mix entry.
+    #[error(
+        "invalid scenario mix entry '{0}': expected 'scenario=weight', with a non-zero weight"
+    )]
+    InvalidScenarioMixEntry(String),
+
+    /// Represents an error caused by an attempt to configure an unknown content encoding.
+    #[error("unsupported content encoding '{0}': expected 'identity', 'gzip', 'deflate', or 'br'")]
+    UnsupportedContentEncoding(String),
+
+    /// Represents an error caused by an attempt to configure an unknown HTTP transport.
+    #[error(
+        "unsupported transport '{0}': expected 'http1', 'http2', 'http2-alpn', or 'new-connection'"
+    )]
+    UnsupportedTransport(String),
+
+    /// Represents an error caused by an attempt to configure an invalid per-operation override.
+    #[error(
+        "invalid operation override '{0}': expected 'operation.setting=value', where the setting is \
+        'iterations', 'operation_timeout_ms', or 'concurrency_levels'"
+    )]
+    InvalidOperationOverride(String),
+
+    /// Represents an error caused by an attempt to configure a per-operation override for an unknown
+    /// operation.
+    #[error("operation override for unknown operation '{0}'")]
+    UnknownOverrideOperation(String),
+
+    /// Represents an error caused by an attempt to configure a numeric setting with a value outside of its
+    /// valid range.
+    #[error("invalid {0} '{1}': expected {2}")]
+    SettingOutOfRange(String, String, String),
+
+    /// Represents an error caused by an attempt to configure an unknown traffic replay speed.
+    #[error("unsupported replay speed '{0}': expected 'recorded' or 'max'")]
+    UnsupportedReplaySpeed(String),
 }
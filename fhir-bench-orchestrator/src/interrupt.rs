@@ -0,0 +1,82 @@
+//! Handles interruptions of the benchmark run (i.e. `SIGINT`/Ctrl-C and `SIGTERM`), so that the run can stop
+//! its measurements, shut down any servers that it launched, and still output the results that it has so
+//! far, rather than leaving `docker-compose` stacks running and losing everything.
+
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Tracks whether or not the benchmark run has been interrupted. Clones of it share the same state.
+#[derive(Clone)]
+pub struct Interrupt {
+    /// Receives the name of the signal that interrupted the run, once one has been received.
+    receiver: watch::Receiver<Option<&'static str>>,
+}
+
+impl Interrupt {
+    /// Starts listening for interruption signals in the background. After the first signal, the run will
+    /// be given a chance to clean up; a second one will exit the application immediately.
+    pub fn listen() -> Interrupt {
+        let (sender, receiver) = watch::channel(None);
+        tokio::spawn(async move {
+            let signal = wait_for_signal().await;
+            warn!(
+                "Received {}: stopping the benchmark run and shutting down servers. Send it again to exit \
+                immediately.",
+                signal
+            );
+            let _ = sender.send(Some(signal));
+
+            let signal = wait_for_signal().await;
+            warn!("Received {} again: exiting immediately.", signal);
+            std::process::exit(130);
+        });
+
+        Interrupt { receiver }
+    }
+
+    /// Returns the name of the signal that interrupted the run, or `None` if it hasn't been.
+    pub fn signal(&self) -> Option<&'static str> {
+        *self.receiver.borrow()
+    }
+
+    /// Waits until the run is interrupted (returning immediately, if it already has been).
+    ///
+    /// Returns the name of the signal that interrupted the run.
+    pub async fn interrupted(&self) -> &'static str {
+        let mut receiver = self.receiver.clone();
+        loop {
+            if let Some(signal) = *receiver.borrow() {
+                return signal;
+            }
+            if receiver.changed().await.is_err() {
+                // The listener has gone away, so no interruption can ever arrive.
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Waits for the next `SIGINT` or (on Unix) `SIGTERM`.
+///
+/// Returns the name of the signal that was received.
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Unable to listen for SIGTERM.");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = sigterm.recv() => "SIGTERM",
+    }
+}
+
+/// Waits for the next Ctrl-C.
+///
+/// Returns the name of the signal that was received.
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Unable to listen for Ctrl-C.");
+    "Ctrl-C"
+}
@@ -1,28 +1,43 @@
 //! TODO
 
+// The `json!(...)` literals in our serialization tests are large enough to need this.
+#![recursion_limit = "256"]
+
+mod checkpoint;
 pub mod config;
 pub mod errors;
+mod interrupt;
 mod sample_data;
 pub mod servers;
 pub mod test_framework;
 mod util;
 
-use crate::config::AppConfig;
+use crate::checkpoint::Checkpointer;
+use crate::config::{AppConfig, OperationOverrides};
 use crate::errors::AppError;
+use crate::interrupt::Interrupt;
 use crate::sample_data::SampleData;
 use crate::servers::{ServerHandle, ServerPlugin};
+use crate::test_framework::replay::RecordedRequest;
+use crate::test_framework::scenario::Scenario;
+use crate::test_framework::workload_plan::WorkloadPlan;
 use crate::test_framework::{FrameworkOperationLog, FrameworkOperationResult, FrameworkResults};
 use chrono::prelude::*;
 use eyre::{eyre, Result, WrapErr};
 use servers::ServerPluginWrapper;
+use std::sync::Arc;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{fmt, fmt::format::FmtSpan, EnvFilter};
 
 /// Represents the application's context/state.
+#[derive(Clone)]
 pub struct AppState {
     pub config: AppConfig,
     pub server_plugins: Vec<ServerPluginWrapper>,
-    pub sample_data: SampleData,
+    pub sample_data: Arc<SampleData>,
+    pub workload_plan: Arc<WorkloadPlan>,
+    pub scenarios: Vec<Scenario>,
+    pub recorded_requests: Arc<Vec<RecordedRequest>>,
 }
 
 impl AppState {
@@ -32,6 +47,18 @@ impl AppState {
             .iter()
             .find(|p| p.server_name().0 == server_name)
     }
+
+    /// Returns a copy of this [AppState] whose [AppConfig] has the overrides for the specified operation
+    /// applied, per [AppConfig::for_operation].
+    ///
+    /// Parameters:
+    /// * `operation`: the name of the operation to get the effective [AppState] for
+    pub fn for_operation(&self, operation: &str) -> AppState {
+        AppState {
+            config: self.config.for_operation(operation),
+            ..self.clone()
+        }
+    }
 }
 
 /// The library crate's primary entry point: this does all the things.
@@ -63,15 +90,29 @@ pub async fn run_bench_orchestrator() -> Result<()> {
     // Verify that pre-requisites are present.
     verify_prereqs()?;
 
-    // Test each selected FHIR server implementation.
-    let mut framework_results = FrameworkResults::new(&app_state.config, &app_state.server_plugins);
+    // From here on, handle interruptions by cleaning up and outputting whatever results are available.
+    let interrupt = Interrupt::listen();
+
+    // Test each selected FHIR server implementation, checkpointing (or resuming from a checkpoint) if enabled.
+    let (checkpointer, mut framework_results) = Checkpointer::new(&app_state)?;
     for server_plugin in &app_state.server_plugins {
+        // Don't launch any more servers once interrupted.
+        if interrupt.signal().is_some() {
+            break;
+        }
+
         // Store results for the test here.
-        let mut server_result = framework_results
+        let server_result = framework_results
             .get_mut(server_plugin.server_name())
             .ok_or_else(|| AppError::UnknownServerError(server_plugin.server_name().clone()))?;
 
-        // Launch the implementation's server, etc. This will likely take a while.
+        // Skip any servers that were already finished with in a resumed checkpoint.
+        if checkpoint::is_server_finished(server_result) {
+            continue;
+        }
+
+        /* Launch the implementation's server, etc. This will likely take a while. This isn't interrupted
+        partway, as there'd then be no handle to shut the server down with. */
         let launch_started = Utc::now();
         let launch_result = server_plugin.launch(&app_state).await;
         let launch_completed = Utc::now();
@@ -98,15 +139,35 @@ pub async fn run_bench_orchestrator() -> Result<()> {
         if server_result.launch.as_ref().unwrap().is_ok() {
             let server_handle: &dyn ServerHandle = &*server_handle.unwrap();
 
-            // Run the tests against the server.
-            let operations = test_framework::run_operations(&app_state, server_handle)
-                .await
-                .with_context(|| {
-                    format!(
-                        "Error when running operations for server '{}'.",
-                        server_plugin.server_name()
-                    )
-                })?;
+            /* Run the tests against the server, unless interrupted during its launch. If interrupted while
+            they're running, the measurement in progress will be abandoned, but any operations that have
+            already completed will be kept. */
+            let mut operations = server_result.operations.take().unwrap_or_default();
+            let checkpoint_server_result = server_result.clone();
+            let checkpoint = |operations: &[_]| {
+                if let Some(checkpointer) = &checkpointer {
+                    checkpointer.save_operations(&checkpoint_server_result, operations);
+                }
+            };
+            if interrupt.signal().is_none() {
+                let operations_future = test_framework::run_operations(
+                    &app_state,
+                    server_handle,
+                    &mut operations,
+                    &checkpoint,
+                );
+                tokio::select! {
+                    operations_result = operations_future => {
+                        operations_result.with_context(|| {
+                            format!(
+                                "Error when running operations for server '{}'.",
+                                server_plugin.server_name()
+                            )
+                        })?;
+                    }
+                    _ = interrupt.interrupted() => {}
+                }
+            }
             server_result.operations = Some(operations);
 
             // Shutdown and cleanup the server and its resources.
@@ -126,10 +187,26 @@ pub async fn run_bench_orchestrator() -> Result<()> {
                 },
             });
         }
+
+        // Checkpoint the server as finished with, unless the run was interrupted partway through it.
+        if let Some(checkpointer) = &checkpointer {
+            if interrupt.signal().is_none() {
+                checkpointer.save_server(server_result);
+            }
+        }
     }
 
     // Output results.
-    framework_results.completed = Some(Utc::now());
+    match interrupt.signal() {
+        None => framework_results.completed = Some(Utc::now()),
+        Some(signal) => {
+            framework_results.interrupted = Some(format!(
+                "The benchmark run was interrupted by {} at {}, so these results are incomplete.",
+                signal,
+                Utc::now().to_rfc3339()
+            ))
+        }
+    }
     output_results(&framework_results);
 
     Ok(())
@@ -148,10 +225,37 @@ async fn create_app_state() -> Result<AppState> {
         .await
         .context("Error when generating sample data.")?;
 
+    // Plan the workload that every server will be benchmarked with.
+    let workload_plan = WorkloadPlan::generate(config.workload_seed, &sample_data)
+        .context("Error when generating workload plan.")?;
+
+    // Load any user-defined scenarios.
+    let scenarios =
+        test_framework::scenario::load_scenarios(&config.benchmark_dir()?.join("scenarios"))
+            .context("Error when loading scenarios.")?;
+
+    // Load the recorded traffic to replay, if any.
+    let recorded_requests = match &config.replay {
+        Some(replay) => test_framework::replay::load_recorded_requests(&replay.file)
+            .context("Error when loading replay file.")?,
+        None => vec![],
+    };
+
+    // Make sure that every per-operation override is for an operation that can actually be run.
+    let mut operation_names: Vec<&str> = test_framework::built_in_operation_names();
+    operation_names.extend(scenarios.iter().map(|scenario| scenario.name.as_str()));
+    OperationOverrides::check_all(&config.operation_overrides, &operation_names)?;
+
+    // Make sure that the scenario mix only mixes scenarios that can actually be run.
+    test_framework::check_scenario_mix(&config.scenario_mix, &scenarios)?;
+
     Ok(AppState {
         config,
         server_plugins,
-        sample_data,
+        sample_data: Arc::new(sample_data),
+        workload_plan: Arc::new(workload_plan),
+        scenarios,
+        recorded_requests: Arc::new(recorded_requests),
     })
 }
 
@@ -160,7 +264,7 @@ fn verify_prereqs() -> Result<()> {
     use std::process::Command;
 
     let docker_compose_output = Command::new("docker-compose")
-        .args(&["--help"])
+        .args(["--help"])
         .output()
         .context("Failed to run 'docker-compose --help'.")?;
     if !docker_compose_output.status.success() {
@@ -5,7 +5,10 @@ use eyre::{eyre, Context, Result};
 use serde_json::json;
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use std::{collections::HashSet, io::BufReader};
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader},
+};
 use tokio::process::Command;
 use tracing::{debug, info_span, trace, Instrument};
 
@@ -93,7 +96,7 @@ impl SampleResourceIter {
 
         let result = self.sample_resources.as_mut().and_then(|s| s.pop());
         let result_trace = result.as_ref().map(|r| &r.metadata);
-        tracing::Span::current().record("result", &tracing::field::debug(result_trace));
+        tracing::Span::current().record("result", tracing::field::debug(result_trace));
         Ok(result)
     }
 }
@@ -221,6 +224,49 @@ impl SampleData {
     pub fn iter_orgs(&self) -> impl Iterator<Item = SampleResource> {
         SampleResourceIter::new(self, "Organization".to_string())
     }
+
+    /// Returns a fingerprint of this [SampleData]: a CRC32 checksum of the names and contents of all of its
+    /// files, which will change if the sample data is regenerated differently.
+    pub fn fingerprint(&self) -> Result<String> {
+        let mut bundle_files = self.bundle_files();
+        bundle_files.sort();
+
+        let mut hasher = crc32fast::Hasher::new();
+        for bundle_file in bundle_files {
+            if let Some(file_name) = bundle_file.file_name() {
+                hasher.update(file_name.to_string_lossy().as_bytes());
+            }
+            let mut reader = BufReader::new(
+                File::open(&bundle_file)
+                    .with_context(|| format!("Unable to open '{:?}'.", bundle_file))?,
+            );
+            loop {
+                let buffer = reader.fill_buf()?;
+                if buffer.is_empty() {
+                    break;
+                }
+                hasher.update(buffer);
+                let length = buffer.len();
+                reader.consume(length);
+            }
+        }
+
+        Ok(format!("{:08x}", hasher.finalize()))
+    }
+
+    /// Returns the paths to all of the sample patient `Bundle` files available in this [SampleData].
+    pub fn patient_files(&self) -> &[PathBuf] {
+        &self.patients
+    }
+
+    /// Returns the paths to all of the sample FHIR `Bundle` files available in this [SampleData], ordered
+    /// such that they can be loaded into a server one after another: the `Organization` and `Practitioner`
+    /// `Bundle`s come first, as each patient's `Bundle` may refer to them.
+    pub fn bundle_files(&self) -> Vec<PathBuf> {
+        let mut bundle_files = vec![self.hospitals.clone(), self.practitioners.clone()];
+        bundle_files.append(&mut self.patients.clone());
+        bundle_files
+    }
 }
 
 /// Generates the sample data needed by the application, as specified/configured in [AppConfig].
@@ -306,13 +352,13 @@ pub async fn generate_data(
         return Err(eyre!(format!("unable to read file: '{:?}'", synthea_bin)));
     }
     let synthea_process = Command::new(synthea_bin)
-        .args(&[
+        .args([
             "-p",
             &population_size.to_string(),
             "-t",
             target_dir.to_str().expect("Invalid target directory."),
         ])
-        .current_dir(&synthea_dir)
+        .current_dir(synthea_dir)
         .output()
         .instrument(info_span!("Running Synthea"))
         .await
@@ -0,0 +1,260 @@
+//! Loads the definitions of the FHIR servers to benchmark from a TOML file, so that additional servers (e.g.
+//! internal forks, or other image versions) can be benchmarked without rebuilding the orchestrator. The
+//! shipped definitions are in `server_builds/servers.toml`: see the `README.md` in that directory.
+
+use crate::sample_data::SampleResource;
+use eyre::{eyre, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The contents of a server definitions file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerDefinitionsFile {
+    /// The [ServerDefinition]s in the file.
+    #[serde(default)]
+    servers: Vec<ServerDefinitionToml>,
+}
+
+/// A [ServerDefinition], as it appears in a server definitions file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServerDefinitionToml {
+    /// See [ServerDefinition.name].
+    name: String,
+
+    /// See [ServerDefinition.launch_script], though this is relative to the definitions file's directory.
+    launch_script: PathBuf,
+
+    /// See [ServerDefinition.base_url], though this need not have a trailing `/`.
+    base_url: String,
+
+    /// See [ServerDefinition.auth].
+    #[serde(default)]
+    auth: Option<ServerAuth>,
+
+    /// See [ServerDefinition.readiness_path].
+    #[serde(default)]
+    readiness_path: Option<String>,
+
+    /// See [ServerDefinition.fudges].
+    #[serde(default)]
+    fudges: Vec<ServerFudge>,
+}
+
+/// Defines a FHIR server implementation to benchmark, which is launched and managed via Docker Compose.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerDefinition {
+    /// The name that uniquely identifies the server implementation in the results.
+    pub name: String,
+
+    /// The shell script that wraps the `docker compose` command for the server implementation.
+    pub launch_script: PathBuf,
+
+    /// The base [Url] to use for all requests to the server, once launched, which has a trailing `/`.
+    pub base_url: Url,
+
+    /// The [ServerAuth] to use for all requests to the server, if any.
+    pub auth: Option<ServerAuth>,
+
+    /// The path (relative to `base_url`) to poll until it returns a successful response, to tell when the
+    /// server is ready after launching. If not set, the server is ready once its `metadata` endpoint returns
+    /// its `CapabilityStatement`.
+    pub readiness_path: Option<String>,
+
+    /// The [ServerFudge]s to apply to the sample data sent to the server.
+    pub fudges: Vec<ServerFudge>,
+}
+
+/// Enumerates the ways that requests can authenticate to a server, per [ServerDefinition.auth].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ServerAuth {
+    /// HTTP Basic authentication.
+    Basic { username: String, password: String },
+
+    /// A bearer token, sent via the `Authorization` header.
+    Bearer { token: String },
+}
+
+impl ServerAuth {
+    /// Applies this [ServerAuth] to the specified request.
+    ///
+    /// Parameters:
+    /// * `request_builder`: the [reqwest::RequestBuilder] for the request
+    pub fn apply(&self, request_builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            ServerAuth::Basic { username, password } => {
+                request_builder.basic_auth(username, Some(password))
+            }
+            ServerAuth::Bearer { token } => request_builder.bearer_auth(token),
+        }
+    }
+}
+
+/// Enumerates the known workarounds for non-compliant servers that can be applied to the sample data sent to
+/// them, per [ServerDefinition.fudges]. Any that are used should be documented in the project's
+/// `doc/server-compliance.md` file.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerFudge {
+    /// Removes the `id` from sample resources before they're `POST`ed, for servers that reject resources
+    /// with an `id` (rather than ignoring it, as the FHIR specification requires).
+    RemoveResourceId,
+}
+
+impl ServerFudge {
+    /// Applies this [ServerFudge] to the specified [SampleResource].
+    ///
+    /// Parameters:
+    /// * `sample_resource`: the [SampleResource] to fudge
+    pub fn apply(&self, mut sample_resource: SampleResource) -> SampleResource {
+        match self {
+            ServerFudge::RemoveResourceId => {
+                if let Some(resource) = sample_resource.resource_json.as_object_mut() {
+                    resource.remove("id");
+                }
+            }
+        }
+        sample_resource
+    }
+}
+
+/// Loads the [ServerDefinition]s from the specified TOML file.
+///
+/// Parameters:
+/// * `servers_file`: the server definitions file to load, whose `launch_script`s are relative to its
+///   directory
+///
+/// Returns the [ServerDefinition]s, in the order they were listed, or an error if any of them were invalid.
+pub fn load_server_definitions(servers_file: &Path) -> Result<Vec<ServerDefinition>> {
+    let servers = std::fs::read_to_string(servers_file)
+        .with_context(|| format!("Unable to read server definitions '{:?}'.", servers_file))?;
+    let servers_dir = servers_file.parent().unwrap_or_else(|| Path::new(""));
+    parse_server_definitions(&servers, servers_dir)
+        .with_context(|| format!("Invalid server definitions file '{:?}'.", servers_file))
+}
+
+/// Parses and validates TOML [ServerDefinition]s.
+///
+/// Parameters:
+/// * `servers`: the TOML to parse
+/// * `servers_dir`: the directory that the definitions' `launch_script`s are relative to
+///
+/// Returns the parsed [ServerDefinition]s, or an error if any of them were invalid.
+fn parse_server_definitions(servers: &str, servers_dir: &Path) -> Result<Vec<ServerDefinition>> {
+    let servers_file: ServerDefinitionsFile = toml::from_str(servers)?;
+
+    let mut definitions: Vec<ServerDefinition> = vec![];
+    for server in servers_file.servers {
+        if definitions.iter().any(|other| other.name == server.name) {
+            return Err(eyre!(
+                "Server name '{}' is used more than once.",
+                server.name
+            ));
+        }
+
+        // Relative URLs are resolved against the base URL's last `/`, so make sure that it has one.
+        let mut base_url = server.base_url.clone();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let base_url = Url::parse(&base_url)
+            .with_context(|| format!("Server '{}' has an invalid base URL.", server.name))?;
+
+        definitions.push(ServerDefinition {
+            launch_script: servers_dir.join(&server.launch_script),
+            base_url,
+            auth: server.auth,
+            readiness_path: server.readiness_path,
+            fudges: server.fudges,
+            name: server.name,
+        });
+    }
+
+    Ok(definitions)
+}
+
+/// Unit tests for the server definitions.
+#[cfg(test)]
+mod tests {
+    use super::{ServerAuth, ServerDefinition, ServerFudge};
+    use std::path::Path;
+    use url::Url;
+
+    /// Verifies that [super::parse_server_definitions] parses server definitions as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn parse_server_definitions() {
+        let servers = r#"
+[[servers]]
+name = "hapi_fork"
+launch_script = "hapi_fork/docker_compose_hapi_fork.sh"
+base_url = "http://localhost:8081/fhir"
+auth = { type = "bearer", token = "secret" }
+readiness_path = "actuator/health"
+fudges = ["remove_resource_id"]
+"#;
+        let definitions = super::parse_server_definitions(servers, Path::new("/servers"))
+            .expect("Unable to parse server definitions.");
+        assert_eq!(
+            vec![ServerDefinition {
+                name: "hapi_fork".into(),
+                launch_script: Path::new("/servers/hapi_fork/docker_compose_hapi_fork.sh").into(),
+                base_url: Url::parse("http://localhost:8081/fhir/").unwrap(),
+                auth: Some(ServerAuth::Bearer {
+                    token: "secret".into()
+                }),
+                readiness_path: Some("actuator/health".into()),
+                fudges: vec![ServerFudge::RemoveResourceId],
+            }],
+            definitions
+        );
+
+        let duplicate = r#"
+[[servers]]
+name = "a"
+launch_script = "a.sh"
+base_url = "http://localhost:8080/"
+
+[[servers]]
+name = "a"
+launch_script = "b.sh"
+base_url = "http://localhost:8081/"
+"#;
+        assert!(super::parse_server_definitions(duplicate, Path::new("/")).is_err());
+    }
+
+    /// Verifies that the shipped `server_builds/servers.toml` defines the expected servers.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn load_shipped_server_definitions() {
+        let servers_file = crate::config::benchmark_dir()
+            .expect("Unable to find benchmark directory.")
+            .join("server_builds")
+            .join("servers.toml");
+        let definitions = super::load_server_definitions(&servers_file)
+            .expect("Unable to load server definitions.");
+
+        let names: Vec<&str> = definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            vec!["firely_spark", "hapi_jpaserver_starter", "ibm_fhir"],
+            names
+        );
+        for definition in &definitions {
+            assert!(
+                definition.launch_script.is_file(),
+                "Missing launch script: '{:?}'.",
+                definition.launch_script
+            );
+        }
+        assert_eq!(
+            Some(ServerAuth::Basic {
+                username: "fhiruser".into(),
+                password: "change-password".into()
+            }),
+            definitions[2].auth
+        );
+    }
+}
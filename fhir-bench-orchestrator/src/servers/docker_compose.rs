@@ -4,8 +4,11 @@
 //! script that wraps `docker-compose` with any setup, environment variables, etc. needed to run things
 //! correctly for that FHIR server.
 
+use super::definitions::{ServerAuth, ServerDefinition, ServerFudge};
 use super::ServerPluginWrapper;
-use crate::servers::{ServerHandle, ServerName, ServerPlugin};
+use crate::config::Transport;
+use crate::sample_data::SampleResource;
+use crate::servers::{ConnectTimer, ServerHandle, ServerName, ServerPlugin};
 use crate::AppState;
 use async_trait::async_trait;
 use eyre::{eyre, Context, Result};
@@ -23,8 +26,9 @@ pub struct DockerComposeServerPlugin {
     server_name: ServerName,
     server_script: PathBuf,
     base_url: Url,
-    request_builder_factory:
-        fn(client: reqwest::Client, method: http::Method, url: Url) -> reqwest::RequestBuilder,
+    auth: Option<ServerAuth>,
+    readiness_path: Option<String>,
+    fudges: Vec<ServerFudge>,
 }
 
 impl DockerComposeServerPlugin {
@@ -44,27 +48,16 @@ impl DockerComposeServerPlugin {
     /// implementation.
     ///
     /// Parameters:
-    /// * `server_name`: the [ServerName] that will uniquely identify the FHIR Server implemenation
-    /// * `server_script`: a [PathBuf] to the shell script that wraps the `docker compose` command for this
-    ///   particular FHIR Server implementation
-    /// * `base_url`: the base [Url] that should be used for all requests to the FHIR Server, once launched
-    /// * `request_builder_factory`: a function that can produce the [reqwest::RequestBuilder] to use when
-    ///   querying the FHIR Server, once launched
-    pub fn new(
-        server_name: ServerName,
-        server_script: PathBuf,
-        base_url: Url,
-        request_builder_factory: fn(
-            client: reqwest::Client,
-            method: http::Method,
-            url: Url,
-        ) -> reqwest::RequestBuilder,
-    ) -> DockerComposeServerPlugin {
+    /// * `definition`: the [ServerDefinition] of the FHIR Server implementation, whose `launch_script` wraps
+    ///   the `docker compose` command for it
+    pub fn new(definition: ServerDefinition) -> DockerComposeServerPlugin {
         DockerComposeServerPlugin {
-            server_name,
-            server_script,
-            base_url,
-            request_builder_factory,
+            server_name: definition.name.as_str().into(),
+            server_script: definition.launch_script,
+            base_url: definition.base_url,
+            auth: definition.auth,
+            readiness_path: definition.readiness_path,
+            fudges: definition.fudges,
         }
     }
 }
@@ -78,6 +71,14 @@ impl ServerPlugin for DockerComposeServerPlugin {
     async fn launch(&self, app_state: &AppState) -> Result<Box<dyn ServerHandle>> {
         launch_server(app_state, self).await
     }
+
+    fn fudge_sample_resource(&self, sample_resource: SampleResource) -> SampleResource {
+        self.fudges
+            .iter()
+            .fold(sample_resource, |sample_resource, fudge| {
+                fudge.apply(sample_resource)
+            })
+    }
 }
 
 /// Runs the specified Docker Compose subcommand with the specified argument, for the specified FHIR Server
@@ -132,7 +133,7 @@ async fn launch_server(
     /*
      * Build and launch the server.
      */
-    run_docker_compose(server_plugin, &["up", "--detach"]).with_context(|| {
+    run_docker_compose(server_plugin, ["up", "--detach"]).with_context(|| {
         format!(
             "Running '{} up --detach' failed.",
             server_plugin
@@ -151,9 +152,11 @@ async fn launch_server(
     let server_plugin = app_state
         .find_server_plugin(server_plugin.server_name().as_str())
         .expect("Unable to find server plugin");
-    let http_client = super::client_default()?;
+    let transport = app_state.config.transport;
+    let http_client = super::client_default(&transport, None)?;
     let server_handle = DockerComposeServerHandle {
         server_plugin: server_plugin.clone(),
+        transport,
         http_client,
     };
 
@@ -186,11 +189,20 @@ async fn wait_for_ready(
         let mut ready = false;
         let mut probe = None;
 
+        let server_plugin = server_plugin_downcast(server_handle);
         while !ready {
-            probe = Some(
-                crate::test_framework::metadata::check_metadata_operation(app_state, server_handle)
-                    .await,
-            );
+            probe = Some(match &server_plugin.readiness_path {
+                Some(readiness_path) => {
+                    check_readiness_path(app_state, server_handle, readiness_path).await
+                }
+                None => {
+                    crate::test_framework::metadata::check_metadata_operation(
+                        app_state,
+                        server_handle,
+                    )
+                    .await
+                }
+            });
             ready = probe.as_ref().expect("probe result missing").is_ok();
 
             if !ready {
@@ -217,9 +229,48 @@ async fn wait_for_ready(
     }
 }
 
+/// Makes a request to the specified readiness path of the server, per [ServerDefinition.readiness_path],
+/// and verifies that it returns a successful response.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [DockerComposeServerHandle] to test
+/// * `readiness_path`: the path to request, relative to the server's base URL
+///
+/// Returns [Result::Ok] if the server is ready, or [Result::Err] if it isn't.
+async fn check_readiness_path(
+    app_state: &AppState,
+    server_handle: &DockerComposeServerHandle,
+    readiness_path: &str,
+) -> Result<()> {
+    let url = server_handle.base_url().join(readiness_path)?;
+    let request = server_handle
+        .request_builder(server_handle.client()?, http::Method::GET, url.clone())
+        .send();
+    let response = tokio::time::timeout(
+        app_state
+            .config
+            .operation_timeout
+            .to_std()
+            .expect("unable to convert Duration"),
+        request,
+    )
+    .await??;
+
+    if !response.status().is_success() {
+        return Err(eyre!(
+            "Readiness check of '{}' failed, with status '{}'.",
+            url,
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
 /// Represents a running instance of a [DockerComposeServerPlugin] instance.
 struct DockerComposeServerHandle {
     server_plugin: ServerPluginWrapper,
+    transport: Transport,
     http_client: reqwest::Client,
 }
 
@@ -238,6 +289,10 @@ impl ServerHandle for DockerComposeServerHandle {
         Ok(self.http_client.clone())
     }
 
+    fn new_client(&self, connect_timer: ConnectTimer) -> Result<reqwest::Client> {
+        super::client_default(&self.transport, Some(connect_timer))
+    }
+
     fn request_builder(
         &self,
         client: reqwest::Client,
@@ -245,12 +300,16 @@ impl ServerHandle for DockerComposeServerHandle {
         url: Url,
     ) -> reqwest::RequestBuilder {
         let server_plugin = server_plugin_downcast(self);
-        (server_plugin.request_builder_factory)(client, method, url)
+        let request_builder = super::request_builder_default(client, method, url);
+        match &server_plugin.auth {
+            Some(auth) => auth.apply(request_builder),
+            None => request_builder,
+        }
     }
 
     fn emit_logs(&self) -> Result<String> {
         let server_plugin = server_plugin_downcast(self);
-        match run_docker_compose(server_plugin, &["logs", "--no-color"]).with_context(|| {
+        match run_docker_compose(server_plugin, ["logs", "--no-color"]).with_context(|| {
             format!(
                 "Running '{} up --detach' failed.",
                 server_plugin
@@ -278,7 +337,7 @@ impl ServerHandle for DockerComposeServerHandle {
         let server_plugin = server_plugin_downcast(self);
 
         let docker_down_output =
-            run_docker_compose(server_plugin, &["down"]).with_context(|| {
+            run_docker_compose(server_plugin, ["down"]).with_context(|| {
                 format!(
                     "Running '{} down' failed.",
                     server_plugin
@@ -1,15 +1,22 @@
 //! TODO
 
 use crate::{
-    config::AppConfig, sample_data::SampleResource,
-    servers::docker_compose::DockerComposeServerPlugin, AppState,
+    config::{AppConfig, Transport},
+    sample_data::SampleResource,
+    servers::docker_compose::DockerComposeServerPlugin,
+    AppState,
 };
 use async_trait::async_trait;
 use eyre::Result;
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tracing::info;
 use url::Url;
 
+mod definitions;
 mod docker_compose;
 
 /// Represents the unique name of a FHIR server implementation.
@@ -64,6 +71,15 @@ pub trait ServerHandle: Sync {
     /// for every call to this method, as this will allow the use of HTTP connection pooling.
     fn client(&self) -> Result<reqwest::Client>;
 
+    /// Returns a new [reqwest::Client], configured just like the one from [ServerHandle::client()], but with
+    /// its own connection pool, for use by a single virtual user.
+    ///
+    /// Parameters:
+    /// * `connect_timer`: the [ConnectTimer] to record the client's new connections in
+    fn new_client(&self, connect_timer: ConnectTimer) -> Result<reqwest::Client> {
+        client_default(&Transport::default(), Some(connect_timer))
+    }
+
     /// Creates a new [reqwest::RequestBuilder] that is properly configured for making HTTP(S)]
     /// requests to the server, e.g. authentication headers are set, etc.
     ///
@@ -109,31 +125,121 @@ pub trait ServerHandle: Sync {
 ///
 /// Note: this is intended for use in [ServerHandle] implementations; other code should not use it directly,
 /// and should instead use [ServerHandle::request_builder()].
-pub fn client_default() -> Result<reqwest::Client> {
+///
+/// Parameters:
+/// * `transport`: the [Transport] that the client should use to connect to the server
+/// * `connect_timer`: the [ConnectTimer] to record the client's new connections in, if any
+pub fn client_default(
+    transport: &Transport,
+    connect_timer: Option<ConnectTimer>,
+) -> Result<reqwest::Client> {
     let client_builder = reqwest::ClientBuilder::new();
 
     // Any server using HTTPS will be using a self-signed cert.
     let client_builder = client_builder.danger_accept_invalid_certs(true);
 
+    // Unless told otherwise, the client offers both HTTP/2 and HTTP/1.1 via ALPN, and uses what's negotiated.
+    let client_builder = match transport {
+        Transport::Http1KeepAlive { pool_size: None } => client_builder.http1_only(),
+        Transport::Http1KeepAlive {
+            pool_size: Some(pool_size),
+        } => client_builder
+            .http1_only()
+            .pool_max_idle_per_host(*pool_size as usize),
+        // Over TLS, this offers only HTTP/2 via ALPN, rather than assuming it.
+        Transport::Http2PriorKnowledge => client_builder.http2_prior_knowledge(),
+        Transport::Http2Alpn => client_builder,
+        // With no idle connections allowed in the pool, each one is closed once its response is complete.
+        Transport::NewConnectionPerRequest => client_builder.http1_only().pool_max_idle_per_host(0),
+    };
+
+    let client_builder = match connect_timer {
+        Some(connect_timer) => client_builder.connector_layer(connect_timer),
+        None => client_builder,
+    };
+
     Ok(client_builder.build()?)
 }
 
-/// Creates a new [reqwest::RequestBuilder] that is properly configured for making HTTP(S)]
-/// requests to the server, e.g. authentication headers are set, etc.
-///
-/// Note: this is intended for use in [ServerHandle] implementations; other code should not use it directly,
-/// and should instead use [ServerHandle::request_builder()].
+/// Records how long a [reqwest::Client] spends establishing each of its new connections (i.e. resolving,
+/// connecting to, and handshaking with the server), until that time is taken by the request it was for.
+/// Clones of it share the same recorded time.
 ///
-/// Parameters:
-/// * `client`: the [reqwest::Client] to use
-/// * `method`: the [http::Method] to call
-/// * `url`: the full/absolute [Url] to call
-fn request_builder_default(
-    client: reqwest::Client,
-    method: http::Method,
-    url: Url,
-) -> reqwest::RequestBuilder {
-    client.request(method, url)
+/// This is a [tower::Layer] for the client's connector, per [reqwest::ClientBuilder::connector_layer()].
+#[derive(Clone, Debug, Default)]
+pub struct ConnectTimer {
+    /// The total time spent establishing the new connections that haven't been taken yet, if any.
+    connecting: Arc<Mutex<Option<Duration>>>,
+}
+
+impl ConnectTimer {
+    /// Adds the time spent establishing a new connection to the time recorded so far.
+    ///
+    /// Parameters:
+    /// * `connect`: how long it took to establish the new connection
+    fn record(&self, connect: Duration) {
+        let mut connecting = self
+            .connecting
+            .lock()
+            .expect("Unable to lock connect timer.");
+        *connecting = Some(connecting.unwrap_or_default() + connect);
+    }
+
+    /// Returns the total time spent establishing new connections since this was last called, or `None` if no
+    /// new connections were established in the meantime (i.e. pooled connections were reused).
+    pub fn take(&self) -> Option<Duration> {
+        self.connecting
+            .lock()
+            .expect("Unable to lock connect timer.")
+            .take()
+    }
+}
+
+impl<S> tower::Layer<S> for ConnectTimer {
+    type Service = TimedConnector<S>;
+
+    fn layer(&self, inner: S) -> TimedConnector<S> {
+        TimedConnector {
+            inner,
+            connect_timer: self.clone(),
+        }
+    }
+}
+
+/// Wraps a [reqwest::Client]'s connector, recording how long each of its connections took to establish in a
+/// [ConnectTimer].
+#[derive(Clone)]
+pub struct TimedConnector<S> {
+    /// The connector being wrapped.
+    inner: S,
+
+    /// The [ConnectTimer] to record each new connection in.
+    connect_timer: ConnectTimer,
+}
+
+impl<S, R> tower::Service<R> for TimedConnector<S>
+where
+    S: tower::Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let connect_timer = self.connect_timer.clone();
+        let started = Instant::now();
+        let connecting = self.inner.call(request);
+        Box::pin(async move {
+            let connection = connecting.await?;
+            connect_timer.record(started.elapsed());
+            Ok(connection)
+        })
+    }
 }
 
 /// Creates a new [reqwest::RequestBuilder] that is properly configured for making HTTP(S)]
@@ -146,12 +252,12 @@ fn request_builder_default(
 /// * `client`: the [reqwest::Client] to use
 /// * `method`: the [http::Method] to call
 /// * `url`: the full/absolute [Url] to call
-pub fn request_builder_ibm_fhir(
+fn request_builder_default(
     client: reqwest::Client,
     method: http::Method,
     url: Url,
 ) -> reqwest::RequestBuilder {
-    request_builder_default(client, method, url).basic_auth("fhiruser", Some("change-password"))
+    client.request(method, url)
 }
 
 /// [ServerPlugin] implementations each represent a supported FHIR server implementation that can be started
@@ -240,44 +346,106 @@ impl ServerPlugin for ServerPluginWrapper {
             }
         }
     }
+
+    fn fudge_sample_resource(&self, sample_resource: SampleResource) -> SampleResource {
+        match self {
+            ServerPluginWrapper::DockerComposeServerPlugin(server_plugin) => {
+                server_plugin.fudge_sample_resource(sample_resource)
+            }
+        }
+    }
 }
 
-/// Declares (and provides instances of) all of the [ServerPlugin]s that are available to the application.
+/// Declares (and provides instances of) all of the [ServerPlugin]s that are available to the application,
+/// as defined in the [AppConfig.servers_file] (or the shipped `server_builds/servers.toml`, by default).
 pub fn create_server_plugins(config: &AppConfig) -> Result<Vec<ServerPluginWrapper>> {
-    /*
-     * Design note: Why are these wrapped in Arcs? Great question! Each ServerHandle needs an owned copy of
-     * them and we can't have the trait extend Copy or Clone, as that would make it not object safe.
-     */
-    Ok(vec![
-        ServerPluginWrapper::DockerComposeServerPlugin(DockerComposeServerPlugin::new(
-            "firely_spark".into(),
-            config
-                .benchmark_dir()?
-                .join("server_builds")
-                .join("firely_spark")
-                .join("docker_compose_firely_spark.sh"),
-            Url::parse("http://localhost:5555/fhir/").expect("Unable to parse URL."),
-            request_builder_default,
-        )),
-        ServerPluginWrapper::DockerComposeServerPlugin(DockerComposeServerPlugin::new(
-            "hapi_jpaserver_starter".into(),
-            config
-                .benchmark_dir()?
-                .join("server_builds")
-                .join("hapi_jpaserver_starter")
-                .join("docker_compose_hapi_jpaserver_starter.sh"),
-            Url::parse("http://localhost:8080/fhir/").expect("Unable to parse URL."),
-            request_builder_default,
-        )),
-        ServerPluginWrapper::DockerComposeServerPlugin(DockerComposeServerPlugin::new(
-            "ibm_fhir".into(),
-            config
-                .benchmark_dir()?
-                .join("server_builds")
-                .join("ibm_fhir")
-                .join("docker_compose_ibm_fhir.sh"),
-            Url::parse("https://localhost:9443/fhir-server/api/v4/").expect("Unable to parse URL."),
-            request_builder_ibm_fhir,
-        )),
-    ])
+    let servers_file = match &config.servers_file {
+        Some(servers_file) => servers_file.clone(),
+        None => config
+            .benchmark_dir()?
+            .join("server_builds")
+            .join("servers.toml"),
+    };
+
+    Ok(definitions::load_server_definitions(&servers_file)?
+        .into_iter()
+        .map(|definition| {
+            ServerPluginWrapper::DockerComposeServerPlugin(DockerComposeServerPlugin::new(
+                definition,
+            ))
+        })
+        .collect())
+}
+
+/// Unit tests for the HTTP clients.
+#[cfg(test)]
+mod tests {
+    use super::ConnectTimer;
+    use crate::config::Transport;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Starts a minimal HTTP/1.1 server on a random local port, which serves the same response to every
+    /// request, keeping each connection open for more of them.
+    ///
+    /// Returns the URL of the server.
+    async fn serve_ok() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 1024];
+                    while let Ok(read) = stream.read(&mut request).await {
+                        if read == 0 {
+                            break;
+                        }
+                        let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                        stream.write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    /// Verifies that [super::client_default] records each new connection in its [ConnectTimer], but not the
+    /// reused ones.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn connect_timer() {
+        let url = serve_ok().await;
+        for (transport, connects) in [
+            (Transport::Http1KeepAlive { pool_size: None }, 1),
+            (Transport::NewConnectionPerRequest, 3),
+        ] {
+            let connect_timer = ConnectTimer::default();
+            let client = super::client_default(&transport, Some(connect_timer.clone())).unwrap();
+            let mut connected = 0;
+            for _ in 0..3 {
+                let response = client.get(&url).send().await.unwrap();
+                assert_eq!("ok", response.text().await.unwrap());
+                if connect_timer.take().is_some() {
+                    connected += 1;
+                }
+            }
+            assert_eq!(
+                connects, connected,
+                "Unexpected connects for {:?}.",
+                transport
+            );
+        }
+    }
+
+    /// Verifies that the [Transport::Http2Alpn] falls back to HTTP/1.1 for servers that can't negotiate.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn http2_alpn_cleartext() {
+        assert_eq!(Transport::Http2Alpn, "http2-alpn".parse().unwrap());
+
+        let url = serve_ok().await;
+        let client = super::client_default(&Transport::Http2Alpn, None).unwrap();
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(http::Version::HTTP_11, response.version());
+    }
 }
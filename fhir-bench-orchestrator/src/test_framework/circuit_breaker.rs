@@ -0,0 +1,149 @@
+//! Contains the circuit breaker, which halts measurements early once the server is failing nearly every
+//! iteration: a server that has collapsed would otherwise have every one of a measurement's remaining
+//! iterations wait out the full [crate::config::AppConfig.operation_timeout].
+
+use crate::config::CircuitBreakerConfig;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+/// A flag that's shared with the code starting a measurement's iterations, which is set once its
+/// [ServerOperationCircuitBreaker] trips, so that no more iterations are started.
+#[derive(Clone, Default)]
+pub(super) struct CircuitBreakerTrip(Arc<AtomicBool>);
+
+impl CircuitBreakerTrip {
+    /// Returns `true` if the circuit breaker has tripped, in which case no more iterations should be started.
+    pub fn is_tripped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Tracks the outcomes of a measurement's most recent iterations, and trips once too many of them have
+/// failed, per its [CircuitBreakerConfig].
+pub(super) struct ServerOperationCircuitBreaker {
+    /// The [CircuitBreakerConfig] specifying when to trip.
+    config: CircuitBreakerConfig,
+
+    /// Whether or not each of the most recent iterations failed, oldest first.
+    outcomes: VecDeque<bool>,
+
+    /// The number of failures in `outcomes`.
+    failures: u32,
+
+    /// The failure rate that the circuit breaker tripped at, if it has.
+    tripped_failure_rate: Option<f64>,
+
+    /// The [CircuitBreakerTrip] flag that's set once this trips.
+    trip: CircuitBreakerTrip,
+}
+
+impl ServerOperationCircuitBreaker {
+    /// Constructs a new [ServerOperationCircuitBreaker].
+    ///
+    /// Parameters:
+    /// * `config`: the [CircuitBreakerConfig] specifying when to trip
+    pub fn new(config: &CircuitBreakerConfig) -> ServerOperationCircuitBreaker {
+        ServerOperationCircuitBreaker {
+            config: config.clone(),
+            outcomes: VecDeque::with_capacity(config.window as usize),
+            failures: 0,
+            tripped_failure_rate: None,
+            trip: CircuitBreakerTrip::default(),
+        }
+    }
+
+    /// Records the outcome of a single iteration, tripping the circuit breaker if the failure rate over the
+    /// window is now too high.
+    ///
+    /// Parameters:
+    /// * `operation_name`: the name of the operation being measured, which is used when logging
+    /// * `failed`: whether or not the iteration failed
+    pub fn record(&mut self, operation_name: &str, failed: bool) {
+        if self.tripped_failure_rate.is_some() {
+            return;
+        }
+
+        self.outcomes.push_back(failed);
+        if failed {
+            self.failures += 1;
+        }
+        if self.outcomes.len() > self.config.window as usize {
+            if let Some(true) = self.outcomes.pop_front() {
+                self.failures -= 1;
+            }
+        }
+
+        if self.outcomes.len() == self.config.window as usize {
+            let failure_rate = f64::from(self.failures) / f64::from(self.config.window);
+            if failure_rate > self.config.max_failure_rate {
+                warn!(
+                    operation = operation_name,
+                    failure_rate, "Circuit breaker tripped: halting measurement."
+                );
+                self.tripped_failure_rate = Some(failure_rate);
+                self.trip.0.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Returns the [CircuitBreakerTrip] flag that will be set once this trips.
+    pub fn trip(&self) -> CircuitBreakerTrip {
+        self.trip.clone()
+    }
+
+    /// Returns the failure rate that the circuit breaker tripped at, or `None` if it hasn't.
+    pub fn tripped_failure_rate(&self) -> Option<f64> {
+        self.tripped_failure_rate
+    }
+}
+
+/// Unit tests for the circuit breaker.
+#[cfg(test)]
+mod tests {
+    use super::ServerOperationCircuitBreaker;
+    use crate::config::CircuitBreakerConfig;
+
+    /// Verifies that [ServerOperationCircuitBreaker] only trips once the failure rate over a full window is
+    /// too high.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn circuit_breaker_trips() {
+        let mut circuit_breaker = ServerOperationCircuitBreaker::new(&CircuitBreakerConfig {
+            window: 4,
+            max_failure_rate: 0.5,
+        });
+        let trip = circuit_breaker.trip();
+
+        // Not enough iterations to fill the window yet, even though they've all failed.
+        for _ in 0..3 {
+            circuit_breaker.record("test", true);
+        }
+        assert!(!trip.is_tripped());
+
+        let mut circuit_breaker = ServerOperationCircuitBreaker::new(&CircuitBreakerConfig {
+            window: 4,
+            max_failure_rate: 0.5,
+        });
+        let trip = circuit_breaker.trip();
+        for _ in 0..3 {
+            circuit_breaker.record("test", false);
+        }
+
+        // The window slides along: 1 and then 2 of the last 4 failing is still within the limit.
+        circuit_breaker.record("test", true);
+        assert!(!trip.is_tripped());
+        circuit_breaker.record("test", true);
+        assert!(!trip.is_tripped());
+
+        // 3 of the last 4 failing is not.
+        circuit_breaker.record("test", true);
+        assert!(trip.is_tripped());
+        assert_eq!(Some(0.75), circuit_breaker.tripped_failure_rate());
+
+        // Once tripped, it stays tripped.
+        circuit_breaker.record("test", false);
+        assert_eq!(Some(0.75), circuit_breaker.tripped_failure_rate());
+    }
+}
@@ -0,0 +1,585 @@
+//! Contains the code to run the update contention operation, which has every virtual user update the same
+//! small set of resources at once, using optimistic locking (`If-Match`), to verify that the server doesn't
+//! lose any of those updates under load.
+
+use super::{
+    elapsed_since, measure_operation, run_measurements, run_operation_with_timeout,
+    ServerOperationContention, ServerOperationFailureKind, ServerOperationIterationFailed,
+    ServerOperationIterationStarting, ServerOperationIterationState,
+    ServerOperationIterationSucceeded, ServerOperationMeasurementLength,
+    ServerOperationMeasurementRecorder,
+};
+use crate::config::ContentEncoding;
+use crate::servers::ServerHandle;
+use crate::test_framework::payload::{encode_request, read_response_body, ServerOperationPayload};
+use crate::test_framework::phases::ServerOperationPhases;
+use crate::test_framework::users::{run_virtual_users, VirtualUser};
+use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
+use crate::AppState;
+use eyre::{eyre, Result};
+use serde_json::json;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+use tracing::{trace_span, warn, Instrument};
+use url::Url;
+
+pub(super) static SERVER_OP_NAME_UPDATE_CONTENTION: &str = "update_contention";
+
+/// The URL of the extension that the update contention operation keeps each resource's update counter in.
+static CONTENTION_COUNTER_URL: &str =
+    "https://github.com/karlmdavis/fhir-benchmarks/contention-counter";
+
+/// The number of resources that all of the virtual users contend to update.
+const CONTENTION_RESOURCES: usize = 4;
+
+/// The maximum number of times that an iteration will retry its update after a version conflict, before
+/// giving up.
+const CONTENTION_MAX_RETRIES: u32 = 10;
+
+/// Verifies and benchmarks the update contention operation, after creating the resources for it to contend
+/// over.
+pub async fn benchmark_update_contention(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> ServerOperationLog {
+    let resource_urls = match create_contention_resources(server_handle).await {
+        Ok(resource_urls) => resource_urls,
+        Err(err) => {
+            let mut server_op_log =
+                ServerOperationLog::new(SERVER_OP_NAME_UPDATE_CONTENTION.into());
+            server_op_log.errors.push(format!("{:?}", err));
+            return server_op_log;
+        }
+    };
+
+    run_measurements(
+        app_state,
+        SERVER_OP_NAME_UPDATE_CONTENTION,
+        |concurrent_users, compression| {
+            benchmark_update_contention_for_users(
+                app_state,
+                server_handle,
+                &resource_urls,
+                concurrent_users,
+                compression,
+            )
+        },
+    )
+    .await
+}
+
+/// Creates the `Organization`s for the update contention operation to contend over, each with its update
+/// counter starting at zero.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+///
+/// Returns the URL of each resource created.
+async fn create_contention_resources(server_handle: &dyn ServerHandle) -> Result<Vec<Url>> {
+    let client = server_handle.client()?;
+    let create_url = server_handle.base_url().join("Organization")?;
+    let mut resource_urls = Vec::with_capacity(CONTENTION_RESOURCES);
+    for index in 0..CONTENTION_RESOURCES {
+        let organization = json!({
+            "resourceType": "Organization",
+            "extension": [{ "url": CONTENTION_COUNTER_URL, "valueInteger": 0 }],
+            "name": format!("Update Contention {}", index),
+        });
+        let response = server_handle
+            .request_builder(client.clone(), http::Method::POST, create_url.clone())
+            .header("Accept", "application/fhir+json")
+            .header("Content-Type", "application/fhir+json")
+            .header("Prefer", "return=representation")
+            .body(organization.to_string())
+            .send()
+            .instrument(trace_span!("POST request", url = %create_url))
+            .await?;
+
+        let response_status = response.status();
+        let response_body = response.text().await?;
+        if !response_status.is_success() {
+            return Err(eyre!(
+                "The POST to '{}' failed, with status '{}' and body: '{}'",
+                create_url,
+                response_status,
+                response_body
+            ));
+        }
+        let created: serde_json::Value = serde_json::from_str(&response_body)?;
+        let id = created["id"]
+            .as_str()
+            .ok_or_else(|| eyre!("The created Organization had no ID: '{}'", response_body))?;
+        resource_urls.push(
+            server_handle
+                .base_url()
+                .join(&format!("Organization/{}", id))?,
+        );
+    }
+
+    Ok(resource_urls)
+}
+
+/// Tracks the optimistic-locking outcomes across all of the iterations in a warmup or measurement.
+struct ContentionCounters {
+    /// The number of successful updates to each resource.
+    updates_succeeded: Vec<AtomicU32>,
+
+    /// The number of `412 Precondition Failed` (or `409 Conflict`) responses.
+    precondition_failures: AtomicU32,
+
+    /// The number of times that an update was retried after a version conflict.
+    retries: AtomicU32,
+
+    /// The number of updates that were sent, but whose response was never received.
+    updates_unknown: AtomicU32,
+}
+
+/// Runs a single iteration of the update contention operation: reading the resource, incrementing its
+/// counter, and then updating it with `If-Match`, retrying from the top after any version conflicts.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `user`: the [VirtualUser] to run the operation as
+/// * `resource_url`: the URL of the resource to update
+/// * `resource_index`: the index of that resource, for the [ContentionCounters]
+/// * `counters`: the [ContentionCounters] to record the iteration's outcomes in
+/// * `operation_state`: the initial state machine for this operation iteration
+///
+/// Returns the final [ServerOperationIterationState] containing information about the operation's
+/// success or failure.
+async fn run_operation_update_contention(
+    server_handle: &dyn ServerHandle,
+    user: &VirtualUser,
+    resource_url: &Url,
+    resource_index: usize,
+    counters: &ContentionCounters,
+    operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
+) -> std::result::Result<
+    ServerOperationIterationState<ServerOperationIterationSucceeded>,
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+> {
+    let client = match user.client() {
+        Ok(client) => client,
+        Err(err) => {
+            return Err(operation_state
+                .completed()
+                .failed(ServerOperationFailureKind::ConnectionError, err));
+        }
+    };
+    let failed_request = |err: reqwest::Error| {
+        operation_state.clone().completed().failed(
+            ServerOperationFailureKind::from_request_error(&err),
+            eyre!("HTTP request failed: '{}'", err),
+        )
+    };
+    // The body either couldn't be received, or couldn't be decoded (which is the server's fault).
+    let failed_body = |err: eyre::Error| {
+        let kind = match err.downcast_ref::<reqwest::Error>() {
+            Some(request_err) => ServerOperationFailureKind::from_request_error(request_err),
+            None => ServerOperationFailureKind::VerificationFailure,
+        };
+        operation_state
+            .clone()
+            .completed()
+            .failed(kind, err.wrap_err("Unable to retrieve response body."))
+    };
+
+    let mut payload = ServerOperationPayload::default();
+    let mut phases = ServerOperationPhases::default();
+    let mut attempt = 0;
+    loop {
+        // Read the resource's current version and counter.
+        let (request_builder, request_payload) = encode_request(
+            server_handle
+                .request_builder(client.clone(), http::Method::GET, resource_url.clone())
+                .header("Accept", "application/fhir+json"),
+            user.compression(),
+            None,
+        );
+        let sent = Instant::now();
+        let response = request_builder
+            .send()
+            .instrument(trace_span!("GET request", url = %resource_url))
+            .await
+            .map_err(failed_request)?;
+        let response_status = response.status();
+        let response = read_response_body(
+            response,
+            user.compression(),
+            user.connect_timer(),
+            request_payload,
+            sent,
+        )
+        .await
+        .map_err(failed_body)?;
+        payload.add(&response.payload);
+        phases.add(&response.phases);
+        let response_body = response.body;
+        if !response_status.is_success() {
+            let error = eyre!(
+                "The GET to '{}' failed, with status '{}' and body: '{}'",
+                resource_url,
+                response_status,
+                response_body
+            );
+            return Err(operation_state.completed().failed_response(
+                response_status,
+                &response_body,
+                error,
+            ));
+        }
+        let mut resource: serde_json::Value = match serde_json::from_str(&response_body) {
+            Ok(resource) => resource,
+            Err(err) => {
+                return Err(operation_state.completed().failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!(err).wrap_err(format!(
+                        "The resource at '{}' could not be parsed: '{}'",
+                        resource_url, response_body
+                    )),
+                ))
+            }
+        };
+        let version = match resource["meta"]["versionId"].as_str() {
+            Some(version) => version.to_string(),
+            None => {
+                return Err(operation_state.completed().failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!(
+                        "The resource at '{}' had no version: '{}'",
+                        resource_url,
+                        response_body
+                    ),
+                ))
+            }
+        };
+        let counter = match parse_counter(&resource) {
+            Some(counter) => counter,
+            None => {
+                return Err(operation_state.completed().failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!(
+                        "The resource at '{}' had no counter: '{}'",
+                        resource_url,
+                        response_body
+                    ),
+                ))
+            }
+        };
+        resource["extension"] =
+            json!([{ "url": CONTENTION_COUNTER_URL, "valueInteger": counter + 1 }]);
+
+        // Try to update it, but only if no one else has in the meantime.
+        let resource = resource.to_string();
+        let (request_builder, request_payload) = encode_request(
+            server_handle
+                .request_builder(client.clone(), http::Method::PUT, resource_url.clone())
+                .header("Accept", "application/fhir+json")
+                .header("Content-Type", "application/fhir+json")
+                .header("If-Match", format!("W/\"{}\"", version)),
+            user.compression(),
+            Some(resource),
+        );
+        /* Until its response arrives, the update's outcome is unknown: if the request fails or times out
+         * (which drops this future), the server may or may not have applied it. */
+        counters.updates_unknown.fetch_add(1, Ordering::SeqCst);
+        let sent = Instant::now();
+        let response = request_builder
+            .send()
+            .instrument(trace_span!("PUT request", url = %resource_url))
+            .await
+            .map_err(failed_request)?;
+        counters.updates_unknown.fetch_sub(1, Ordering::SeqCst);
+        let response_status = response.status();
+        if response_status.is_success() {
+            counters.updates_succeeded[resource_index].fetch_add(1, Ordering::SeqCst);
+        }
+        let response = read_response_body(
+            response,
+            user.compression(),
+            user.connect_timer(),
+            request_payload,
+            sent,
+        )
+        .await
+        .map_err(failed_body)?;
+        payload.add(&response.payload);
+        phases.add(&response.phases);
+        let response_body = response.body;
+
+        if response_status.is_success() {
+            return Ok(operation_state
+                .completed_at(response.received)
+                .succeeded()
+                .with_payload(payload)
+                .with_phases(phases));
+        }
+        let is_conflict = response_status == http::StatusCode::PRECONDITION_FAILED
+            || response_status == http::StatusCode::CONFLICT;
+        if is_conflict {
+            counters
+                .precondition_failures
+                .fetch_add(1, Ordering::SeqCst);
+        }
+        if !is_conflict || attempt >= CONTENTION_MAX_RETRIES {
+            let error = eyre!(
+                "The PUT to '{}' failed after {} retries, with status '{}' and body: '{}'",
+                resource_url,
+                attempt,
+                response_status,
+                response_body
+            );
+            return Err(operation_state.completed().failed_response(
+                response_status,
+                &response_body,
+                error,
+            ));
+        }
+
+        attempt += 1;
+        counters.retries.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Returns the value of the update counter extension in the specified resource, if any.
+///
+/// Parameters:
+/// * `resource`: the resource JSON to check
+fn parse_counter(resource: &serde_json::Value) -> Option<u64> {
+    resource["extension"]
+        .as_array()?
+        .iter()
+        .find(|extension| extension["url"] == CONTENTION_COUNTER_URL)?["valueInteger"]
+        .as_u64()
+}
+
+/// Reads the current value of the update counter of each of the specified resources.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `resource_urls`: the URL of each resource to read
+///
+/// Returns the current counter value for each resource, in order.
+async fn read_counters(
+    server_handle: &dyn ServerHandle,
+    resource_urls: &[Url],
+) -> Result<Vec<u64>> {
+    let client = server_handle.client()?;
+    let mut counters = Vec::with_capacity(resource_urls.len());
+    for resource_url in resource_urls {
+        let response = server_handle
+            .request_builder(client.clone(), http::Method::GET, resource_url.clone())
+            .header("Accept", "application/fhir+json")
+            .send()
+            .await?
+            .error_for_status()?;
+        let resource: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        counters.push(
+            parse_counter(&resource)
+                .ok_or_else(|| eyre!("The resource at '{}' had no counter.", resource_url))?,
+        );
+    }
+
+    Ok(counters)
+}
+
+/// Verifies and benchmarks the update contention operation for the specified number of concurrent users.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `resource_urls`: the URL of each resource to contend over
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+///
+/// Returns a [ServerOperationMeasurement] with the results.
+#[tracing::instrument(level = "info", skip(app_state, server_handle, resource_urls))]
+async fn benchmark_update_contention_for_users(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    resource_urls: &[Url],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+) -> ServerOperationMeasurement {
+    let (measurement, contention) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || {
+            ServerOperationMeasurementRecorder::new(
+                &app_state.config,
+                SERVER_OP_NAME_UPDATE_CONTENTION,
+            )
+        },
+        |length, mut recorder| async move {
+            let contention = run_operations_update_contention(
+                app_state,
+                server_handle,
+                resource_urls,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, contention)
+        },
+    )
+    .await;
+    ServerOperationMeasurement {
+        contention: Some(contention),
+        ..measurement
+    }
+}
+
+/// Runs iterations of the update contention operation for the specified number of concurrent users, until
+/// the specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `resource_urls`: the URL of each resource to contend over
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+///
+/// Returns the [ServerOperationContention] observed across those iterations.
+async fn run_operations_update_contention(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    resource_urls: &[Url],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) -> ServerOperationContention {
+    let counters = ContentionCounters {
+        updates_succeeded: resource_urls.iter().map(|_| AtomicU32::new(0)).collect(),
+        precondition_failures: AtomicU32::new(0),
+        retries: AtomicU32::new(0),
+        updates_unknown: AtomicU32::new(0),
+    };
+    let counters_before = read_counters(server_handle, resource_urls).await;
+
+    /*
+     * Build an iterator: One element (the index of the resource to update) for each iteration to run. The
+     * iterator is lazy, so whether or not to start each iteration is only decided once a virtual user is
+     * ready to run it.
+     */
+    let circuit_breaker = recorder.circuit_breaker();
+    let started = Instant::now();
+    let iterations = (0..)
+        .take_while(|iteration| {
+            !circuit_breaker.is_tripped()
+                && length.should_start_iteration(*iteration, elapsed_since(started))
+        })
+        .map(|iteration| usize::try_from(iteration).unwrap() % resource_urls.len());
+
+    /*
+     * Run those iterations with `concurrent_users` virtual users, recording the outcome of each iteration.
+     */
+    let counters_ref = &counters;
+    run_virtual_users(
+        server_handle,
+        concurrent_users,
+        compression,
+        app_state.config.think_time.as_ref(),
+        iterations,
+        |user, resource_index| async move {
+            let operation_state = ServerOperationIterationState::new();
+            let operation = run_operation_update_contention(
+                server_handle,
+                &user,
+                &resource_urls[resource_index],
+                resource_index,
+                counters_ref,
+                operation_state.clone(),
+            );
+            run_operation_with_timeout(app_state, operation_state, operation).await
+        },
+        |operation_result| recorder.record(operation_result),
+    )
+    .await;
+
+    /*
+     * Check for lost updates: every successful update should have incremented its resource's counter, so
+     * any shortfall is an update that the server accepted but then overwrote.
+     */
+    let updates_succeeded: Vec<u32> = counters
+        .updates_succeeded
+        .iter()
+        .map(|updates| updates.load(Ordering::SeqCst))
+        .collect();
+    let counters_after = read_counters(server_handle, resource_urls).await;
+    let lost_updates = match (counters_before, counters_after) {
+        (Ok(counters_before), Ok(counters_after)) => Some(count_lost_updates(
+            &counters_before,
+            &counters_after,
+            &updates_succeeded,
+        )),
+        (Err(err), _) | (_, Err(err)) => {
+            warn!("Unable to check for lost updates: '{:?}'", err);
+            None
+        }
+    };
+
+    ServerOperationContention {
+        resources: resource_urls.len() as u32,
+        updates_succeeded: updates_succeeded.iter().sum(),
+        precondition_failures: counters.precondition_failures.load(Ordering::SeqCst),
+        retries: counters.retries.load(Ordering::SeqCst),
+        updates_unknown: counters.updates_unknown.load(Ordering::SeqCst),
+        lost_updates,
+    }
+}
+
+/// Counts the updates that were lost: accepted by the server, but not reflected in the final resources. Any
+/// updates whose outcome is unknown aren't counted as accepted, so if the server applied them anyway, they'll
+/// mask as many lost updates.
+///
+/// Parameters:
+/// * `counters_before`: the counter of each resource before the iterations ran
+/// * `counters_after`: the counter of each resource after the iterations ran
+/// * `updates_succeeded`: the number of successful updates to each resource
+///
+/// Returns the total number of lost updates, across all of the resources.
+fn count_lost_updates(
+    counters_before: &[u64],
+    counters_after: &[u64],
+    updates_succeeded: &[u32],
+) -> u32 {
+    counters_before
+        .iter()
+        .zip(counters_after)
+        .zip(updates_succeeded)
+        .map(|((before, after), updates)| {
+            (before + u64::from(*updates)).saturating_sub(*after) as u32
+        })
+        .sum()
+}
+
+/// Unit tests for the update contention operation.
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    /// Verifies that [super::parse_counter] and [super::count_lost_updates] work as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn count_lost_updates() {
+        let resource = json!({
+            "resourceType": "Organization",
+            "extension": [{ "url": super::CONTENTION_COUNTER_URL, "valueInteger": 42 }],
+        });
+        assert_eq!(Some(42), super::parse_counter(&resource));
+        assert_eq!(
+            None,
+            super::parse_counter(&json!({ "resourceType": "Organization" }))
+        );
+
+        assert_eq!(0, super::count_lost_updates(&[0, 10], &[5, 20], &[5, 10]));
+        assert_eq!(3, super::count_lost_updates(&[0, 10], &[4, 18], &[5, 10]));
+    }
+}
@@ -0,0 +1,477 @@
+//! Contains the code to run the index lag operation: how long new resources take to be searchable.
+
+use super::{
+    elapsed_since, measure_operation, run_measurements, run_operation_with_timeout,
+    ServerOperationFailureKind, ServerOperationIterationFailed, ServerOperationIterationStarting,
+    ServerOperationIterationState, ServerOperationIterationSucceeded,
+    ServerOperationMeasurementLength, ServerOperationMeasurementRecorder,
+};
+use crate::config::ContentEncoding;
+use crate::servers::{ConnectTimer, ServerHandle};
+use crate::test_framework::payload::{
+    encode_request, read_response_body, ServerOperationPayload, ServerOperationResponse,
+};
+use crate::test_framework::phases::ServerOperationPhases;
+use crate::test_framework::users::{run_virtual_users, VirtualUser};
+use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
+use crate::AppState;
+use eyre::eyre;
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{trace_span, Instrument};
+use url::Url;
+
+pub(super) static SERVER_OP_NAME_INDEX_LAG: &str = "index_lag";
+
+/// The identifier system used for the `Organization`s created by the index lag operation.
+static INDEX_LAG_IDENTIFIER_SYSTEM: &str =
+    "https://github.com/karlmdavis/fhir-benchmarks/index-lag";
+
+/// How long to wait between each search, while waiting for a newly-created resource to show up in them.
+const INDEX_LAG_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// The name of the step that creates the resource.
+static STEP_CREATE: &str = "create";
+
+/// The name of the step that reads the resource back by its ID.
+static STEP_READ: &str = "read";
+
+/// The name of the step that polls the search until the resource shows up, whose latency is the index lag.
+static STEP_SEARCH: &str = "search_lag";
+
+/// Verifies and benchmarks the index lag operation: creating an `Organization`, and then both reading it
+/// back by ID and searching for it until it shows up. The `search_lag` step's latencies are the index lag.
+pub async fn benchmark_index_lag(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> ServerOperationLog {
+    /* The identifiers of the created Organizations are drawn from the run's workload plan, so that they're
+     * reproducible, but from a single generator for all of the operation's measurements, so that they're
+     * never reused against the same server. */
+    let identifiers = Mutex::new(app_state.workload_plan.rng(SERVER_OP_NAME_INDEX_LAG));
+    run_measurements(
+        app_state,
+        SERVER_OP_NAME_INDEX_LAG,
+        |concurrent_users, compression| {
+            benchmark_index_lag_for_users(
+                app_state,
+                server_handle,
+                &identifiers,
+                concurrent_users,
+                compression,
+            )
+        },
+    )
+    .await
+}
+
+/// Runs a single iteration of the index lag operation and verifies its result.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `user`: the [VirtualUser] to run the operation as
+/// * `identifier`: the identifier value to create the `Organization` with, which no other iteration uses
+/// * `operation_state`: the initial state machine for this operation iteration
+///
+/// Returns the final [ServerOperationIterationState] containing information about the operation's
+/// success or failure.
+async fn run_operation_index_lag(
+    server_handle: &dyn ServerHandle,
+    user: &VirtualUser,
+    identifier: String,
+    operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
+) -> std::result::Result<
+    ServerOperationIterationState<ServerOperationIterationSucceeded>,
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+> {
+    let client = match user.client() {
+        Ok(client) => client,
+        Err(err) => {
+            return Err(operation_state
+                .completed()
+                .failed(ServerOperationFailureKind::ConnectionError, err));
+        }
+    };
+    let failed = |kind: ServerOperationFailureKind, error: eyre::Error| {
+        operation_state.clone().completed().failed(kind, error)
+    };
+    let mut payload = ServerOperationPayload::default();
+    let mut phases = ServerOperationPhases::default();
+
+    // Create the Organization.
+    let organization = json!({
+        "resourceType": "Organization",
+        "identifier": [{ "system": INDEX_LAG_IDENTIFIER_SYSTEM, "value": identifier }],
+        "name": format!("Index Lag {}", identifier),
+    });
+    let create_url = server_handle
+        .base_url()
+        .join("Organization")
+        .expect("Error parsing URL.");
+    let create_started = Instant::now();
+    let (created, _, create_completed) = send_request(
+        server_handle,
+        client.clone(),
+        user.compression(),
+        user.connect_timer(),
+        http::Method::POST,
+        create_url,
+        Some(organization.to_string()),
+        &operation_state,
+        &mut payload,
+        &mut phases,
+    )
+    .await?;
+    let id = match created["id"].as_str() {
+        Some(id) => id.to_string(),
+        None => {
+            return Err(failed(
+                ServerOperationFailureKind::VerificationFailure,
+                eyre!("The created Organization had no ID: '{}'", created),
+            ))
+        }
+    };
+
+    /* Read-your-writes: the Organization must be readable by its ID right away. This runs alongside the
+     * search polling (with its own payload and phases), so that it doesn't delay the first search. */
+    let mut read_payload = ServerOperationPayload::default();
+    let mut read_phases = ServerOperationPhases::default();
+    let read = async {
+        let read_url = server_handle
+            .base_url()
+            .join(&format!("Organization/{}", id))
+            .expect("Error parsing URL.");
+        let read_started = Instant::now();
+        let (read, _, read_received) = send_request(
+            server_handle,
+            client.clone(),
+            user.compression(),
+            user.connect_timer(),
+            http::Method::GET,
+            read_url.clone(),
+            None,
+            &operation_state,
+            &mut read_payload,
+            &mut read_phases,
+        )
+        .await?;
+        let read_duration = read_received - read_started;
+        if read["identifier"][0]["value"] != identifier.as_str() {
+            return Err(failed(
+                ServerOperationFailureKind::VerificationFailure,
+                eyre!(
+                    "The Organization read back from '{}' did not match the one created: '{}'",
+                    read_url,
+                    read
+                ),
+            ));
+        }
+        Ok((read_duration, read_received))
+    };
+
+    // Poll the search until the Organization shows up in it (the operation timeout will end this, if not).
+    let search = async {
+        let mut search_url = server_handle
+            .base_url()
+            .join("Organization")
+            .expect("Error parsing URL.");
+        search_url.query_pairs_mut().append_pair(
+            "identifier",
+            &format!("{}|{}", INDEX_LAG_IDENTIFIER_SYSTEM, identifier),
+        );
+        loop {
+            let (bundle, search_sent, search_received) = send_request(
+                server_handle,
+                client.clone(),
+                user.compression(),
+                user.connect_timer(),
+                http::Method::GET,
+                search_url.clone(),
+                None,
+                &operation_state,
+                &mut payload,
+                &mut phases,
+            )
+            .await?;
+            if bundle_contains(&bundle, &id) {
+                return Ok((search_lag(create_completed, search_sent), search_received));
+            }
+            tokio::time::sleep(INDEX_LAG_POLL_INTERVAL).await;
+        }
+    };
+
+    let ((read_duration, read_received), (search_lag, search_received)) =
+        futures::future::try_join(read, search).await?;
+    payload.add(&read_payload);
+    phases.add(&read_phases);
+
+    Ok(operation_state
+        .completed_at(std::cmp::max(read_received, search_received))
+        .succeeded_with_steps(vec![
+            (STEP_CREATE.into(), create_completed - create_started),
+            (STEP_READ.into(), read_duration),
+            (STEP_SEARCH.into(), search_lag),
+        ])
+        .with_payload(payload)
+        .with_phases(phases))
+}
+
+/// Returns whether or not the specified search result `Bundle` contains the resource with the specified ID.
+///
+/// Parameters:
+/// * `bundle`: the search result `Bundle` to check
+/// * `id`: the ID of the resource to look for
+fn bundle_contains(bundle: &serde_json::Value, id: &str) -> bool {
+    bundle["entry"]
+        .as_array()
+        .map(|entries| entries.iter().any(|entry| entry["resource"]["id"] == id))
+        .unwrap_or(false)
+}
+
+/// Returns the index lag of a created resource: the time from when its create response was received until
+/// the first search that found it was sent. This excludes that search's own response time, which would
+/// otherwise add the search's latency to every server's lag.
+///
+/// Parameters:
+/// * `create_completed`: when the create response was received, in monotonic time
+/// * `search_sent`: when the first search that found the resource was sent, in monotonic time
+fn search_lag(create_completed: Instant, search_sent: Instant) -> std::time::Duration {
+    search_sent.saturating_duration_since(create_completed)
+}
+
+/// Sends a single request for the index lag operation, and parses its JSON response.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `client`: the [reqwest::Client] to use
+/// * `compression`: the [ContentEncoding] for the request to ask for, if any
+/// * `connect_timer`: the [ConnectTimer] of `client`
+/// * `method`: the HTTP method to use
+/// * `url`: the URL to call
+/// * `body`: the JSON request body to send, if any
+/// * `operation_state`: the initial state machine for the operation iteration, which is used to build the
+///   failure state if the request fails
+/// * `payload`: the [ServerOperationPayload] for the operation iteration, which the request and response
+///   sizes will be added to
+/// * `phases`: the [ServerOperationPhases] for the operation iteration, which the request's latency phases
+///   will be added to
+///
+/// Returns the parsed JSON response, when the request was sent, and when its response was received (before
+/// it was decoded), both in monotonic time, or the final [ServerOperationIterationState] for the operation
+/// iteration, if the request failed.
+#[allow(clippy::too_many_arguments)]
+async fn send_request(
+    server_handle: &dyn ServerHandle,
+    client: reqwest::Client,
+    compression: Option<ContentEncoding>,
+    connect_timer: &ConnectTimer,
+    method: http::Method,
+    url: Url,
+    body: Option<String>,
+    operation_state: &ServerOperationIterationState<ServerOperationIterationStarting>,
+    payload: &mut ServerOperationPayload,
+    phases: &mut ServerOperationPhases,
+) -> std::result::Result<
+    (serde_json::Value, Instant, Instant),
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+> {
+    let mut request_builder = server_handle
+        .request_builder(client, method.clone(), url.clone())
+        .header("Accept", "application/fhir+json")
+        .header("Prefer", "return=representation");
+    if body.is_some() {
+        request_builder = request_builder.header("Content-Type", "application/fhir+json");
+    }
+    let (request_builder, request_payload) = encode_request(request_builder, compression, body);
+    let sent = Instant::now();
+    let response = request_builder
+        .send()
+        .instrument(trace_span!("request", %method, %url))
+        .await
+        .map_err(|err| {
+            operation_state.clone().completed().failed(
+                ServerOperationFailureKind::from_request_error(&err),
+                eyre!("HTTP request failed: '{}'", err),
+            )
+        })?;
+
+    // Always pull response body, to drain stream and release connection.
+    let response_status = response.status();
+    let response = read_response_body(response, compression, connect_timer, request_payload, sent)
+        .await
+        .unwrap_or_else(ServerOperationResponse::unreadable);
+    payload.add(&response.payload);
+    phases.add(&response.phases);
+    let response_body = response.body;
+
+    if !response_status.is_success() {
+        let error = eyre!(
+            "The {} to '{}' failed, with status '{}' and body: '{}'",
+            method,
+            &url,
+            response_status,
+            response_body
+        );
+        return Err(operation_state.clone().completed().failed_response(
+            response_status,
+            &response_body,
+            error,
+        ));
+    }
+
+    let response_json = serde_json::from_str(&response_body).map_err(|err| {
+        operation_state.clone().completed().failed(
+            ServerOperationFailureKind::VerificationFailure,
+            eyre!("Unable to parse response from '{}': '{}'", &url, err),
+        )
+    })?;
+    Ok((response_json, sent, response.received))
+}
+
+/// Verifies and benchmarks the index lag operation for the specified number of concurrent users.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `identifiers`: generates the identifier values for the `Organization`s created by each iteration
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+///
+/// Returns a [ServerOperationMeasurement] with the results.
+#[tracing::instrument(level = "info", skip(app_state, server_handle, identifiers))]
+async fn benchmark_index_lag_for_users(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    identifiers: &Mutex<StdRng>,
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+) -> ServerOperationMeasurement {
+    let (measurement, ()) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || ServerOperationMeasurementRecorder::new(&app_state.config, SERVER_OP_NAME_INDEX_LAG),
+        |length, mut recorder| async move {
+            run_operations_index_lag(
+                app_state,
+                server_handle,
+                identifiers,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, ())
+        },
+    )
+    .await;
+    measurement
+}
+
+/// Runs iterations of the index lag operation for the specified number of concurrent users, until the
+/// specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `identifiers`: generates the identifier values for the `Organization`s created by each iteration
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+async fn run_operations_index_lag(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    identifiers: &Mutex<StdRng>,
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
+    /*
+     * Build an iterator: One element (the identifier for the iteration's Organization) for each iteration to
+     * run. The iterator is lazy, so whether or not to start each iteration is only decided once a virtual
+     * user is ready to run it.
+     */
+    let circuit_breaker = recorder.circuit_breaker();
+    let started = Instant::now();
+    let iterations = (0..)
+        .take_while(|iteration| {
+            !circuit_breaker.is_tripped()
+                && length.should_start_iteration(*iteration, elapsed_since(started))
+        })
+        .map(|_| {
+            let mut identifiers = identifiers.lock().expect("Unable to lock identifiers.");
+            format!("{:016x}", identifiers.gen::<u64>())
+        });
+
+    /*
+     * Run those iterations with `concurrent_users` virtual users, recording the outcome of each iteration.
+     */
+    run_virtual_users(
+        server_handle,
+        concurrent_users,
+        compression,
+        app_state.config.think_time.as_ref(),
+        iterations,
+        |user, identifier| async move {
+            let operation_state = ServerOperationIterationState::new();
+            let operation =
+                run_operation_index_lag(server_handle, &user, identifier, operation_state.clone());
+            run_operation_with_timeout(app_state, operation_state, operation).await
+        },
+        |operation_result| recorder.record(operation_result),
+    )
+    .await;
+}
+
+/// Unit tests for the index lag operation.
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+
+    /// Verifies that [super::bundle_contains] only finds the resource with the specified ID.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn bundle_contains() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "entry": [
+                { "resource": { "resourceType": "Organization", "id": "older" } },
+                { "resource": { "resourceType": "Organization", "id": "123" } },
+            ],
+        });
+        assert!(super::bundle_contains(&bundle, "123"));
+        assert!(!super::bundle_contains(&bundle, "12"));
+        assert!(!super::bundle_contains(
+            &json!({ "resourceType": "Bundle", "total": 0 }),
+            "123"
+        ));
+    }
+
+    /// Verifies that [super::search_lag] is measured from the create response to when the search that found
+    /// the resource was sent.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn search_lag() {
+        let create_completed = Instant::now();
+        assert_eq!(
+            Duration::from_millis(30),
+            super::search_lag(
+                create_completed,
+                create_completed + Duration::from_millis(30)
+            )
+        );
+
+        // A resource that was found by the first search, sent right away, has no lag.
+        assert_eq!(
+            Duration::from_millis(0),
+            super::search_lag(create_completed, create_completed)
+        );
+    }
+}
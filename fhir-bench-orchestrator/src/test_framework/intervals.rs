@@ -0,0 +1,257 @@
+//! Breaks each measurement down into a time series of fixed-length windows.
+
+use super::{
+    ServerOperationInterval, ServerOperationIntervals, MICROS_PER_MILLI, MICROS_PER_SECOND,
+};
+use chrono::Duration;
+use hdrhistogram::serialization::interval_log::IntervalLogWriterBuilder;
+use hdrhistogram::serialization::V2DeflateSerializer;
+use hdrhistogram::Histogram;
+use std::convert::TryFrom;
+use std::time::Instant;
+
+/// Accumulates the outcomes of a measurement's iterations into windows, based on when each iteration
+/// completed, until they're ready to be summarized as [ServerOperationIntervals].
+pub struct ServerOperationIntervalsRecorder {
+    /// The length of each window.
+    window: Duration,
+
+    /// When the measurement started, in monotonic time.
+    started: Instant,
+
+    /// The total time that the window clock has been paused for (see
+    /// [ServerOperationIntervalsRecorder::pause]), not including any pause that's still ongoing.
+    paused: std::time::Duration,
+
+    /// When the window clock was paused, in monotonic time, if it's currently paused.
+    paused_since: Option<Instant>,
+
+    /// The [IntervalWindow]s recorded so far, in order.
+    windows: Vec<IntervalWindow>,
+}
+
+/// Accumulates the outcomes of the iterations that completed within a single window.
+struct IntervalWindow {
+    /// The raw latencies of each successful iteration, in microseconds.
+    histogram: Histogram<u64>,
+
+    /// The number of iterations that completed successfully.
+    iterations_succeeded: u32,
+
+    /// The number of iterations that failed to produce the expected result.
+    iterations_failed: u32,
+}
+
+impl ServerOperationIntervalsRecorder {
+    /// Constructs a new [ServerOperationIntervalsRecorder].
+    ///
+    /// Parameters:
+    /// * `window`: the length of each window
+    /// * `started`: when the measurement started, in monotonic time
+    pub fn new(window: Duration, started: Instant) -> ServerOperationIntervalsRecorder {
+        ServerOperationIntervalsRecorder {
+            window,
+            started,
+            paused: std::time::Duration::from_secs(0),
+            paused_since: None,
+            windows: vec![],
+        }
+    }
+
+    /// Pauses the window clock, e.g. while the server is being expunged between groups of iterations, so
+    /// that the time spent isn't reported as windows with no throughput. No iterations should complete
+    /// while it's paused.
+    ///
+    /// Parameters:
+    /// * `at`: when the clock was paused, in monotonic time
+    pub fn pause(&mut self, at: Instant) {
+        if self.paused_since.is_none() {
+            self.paused_since = Some(at);
+        }
+    }
+
+    /// Resumes the window clock, after a [ServerOperationIntervalsRecorder::pause].
+    ///
+    /// Parameters:
+    /// * `at`: when the clock was resumed, in monotonic time
+    pub fn resume(&mut self, at: Instant) {
+        if let Some(paused_since) = self.paused_since.take() {
+            self.paused += at.saturating_duration_since(paused_since);
+        }
+    }
+
+    /// Records a successful iteration.
+    ///
+    /// Parameters:
+    /// * `completed`: when the iteration completed, in monotonic time
+    /// * `latency_micros`: how long the iteration took, in microseconds
+    pub fn record_success(&mut self, completed: Instant, latency_micros: u64) {
+        let window = self.window_for(completed);
+        window
+            .histogram
+            .record(latency_micros)
+            .expect("Histogram recording failed.");
+        window.iterations_succeeded += 1;
+    }
+
+    /// Records a failed iteration.
+    ///
+    /// Parameters:
+    /// * `completed`: when the iteration completed, in monotonic time
+    pub fn record_failure(&mut self, completed: Instant) {
+        self.window_for(completed).iterations_failed += 1;
+    }
+
+    /// Returns the [IntervalWindow] for an iteration that completed at the specified time (which is offset
+    /// by any time that the window clock was paused for), adding it (and any skipped windows before it) if
+    /// needed.
+    fn window_for(&mut self, completed: Instant) -> &mut IntervalWindow {
+        let elapsed = completed
+            .saturating_duration_since(self.started)
+            .saturating_sub(self.paused);
+        let window_micros = self.window.num_microseconds().unwrap_or(i64::MAX) as u128;
+        let index = usize::try_from(elapsed.as_micros() / window_micros).unwrap();
+        if self.windows.len() <= index {
+            self.windows.resize_with(index + 1, || IntervalWindow {
+                histogram: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+                iterations_succeeded: 0,
+                iterations_failed: 0,
+            });
+        }
+
+        &mut self.windows[index]
+    }
+
+    /// Summarizes the recorded windows as [ServerOperationIntervals].
+    ///
+    /// Parameters:
+    /// * `duration`: how long the measurement ran for, including any time that the window clock was paused
+    ///   for, which is used to size the final (partial) window
+    pub fn into_intervals(self, duration: Duration) -> ServerOperationIntervals {
+        let duration =
+            duration - Duration::from_std(self.paused).expect("Unable to convert Duration.");
+        let mut latency_interval_log = Vec::new();
+        let mut serializer = V2DeflateSerializer::new();
+        let mut log_writer = IntervalLogWriterBuilder::new()
+            .with_max_value_divisor(MICROS_PER_MILLI)
+            .begin_log_with(&mut latency_interval_log, &mut serializer)
+            .expect("Unable to write interval log.");
+
+        let mut intervals = Vec::with_capacity(self.windows.len());
+        for (index, window) in self.windows.into_iter().enumerate() {
+            let offset = self.window * i32::try_from(index).unwrap();
+            let length = std::cmp::min(self.window, duration - offset);
+            let length = if length > Duration::zero() {
+                length
+            } else {
+                self.window
+            };
+
+            log_writer
+                .write_histogram(
+                    &window.histogram,
+                    offset.to_std().expect("Unable to convert Duration."),
+                    length.to_std().expect("Unable to convert Duration."),
+                    None,
+                )
+                .expect("Unable to write interval log.");
+
+            let length_micros = length.num_microseconds().unwrap_or(i64::MAX) as f64;
+            let millis = |micros: u64| micros as f64 / MICROS_PER_MILLI;
+            intervals.push(ServerOperationInterval {
+                offset,
+                throughput_per_second: f64::from(window.iterations_succeeded) / length_micros
+                    * MICROS_PER_SECOND,
+                iterations_failed: window.iterations_failed,
+                latency_millis_p50: millis(window.histogram.value_at_quantile(0.5)),
+                latency_millis_p90: millis(window.histogram.value_at_quantile(0.9)),
+                latency_millis_p99: millis(window.histogram.value_at_quantile(0.99)),
+                latency_millis_p100: millis(window.histogram.max()),
+            });
+        }
+
+        ServerOperationIntervals {
+            window: self.window,
+            intervals,
+            latency_interval_log: String::from_utf8(latency_interval_log)
+                .expect("Unable to convert interval log."),
+        }
+    }
+}
+
+/// Unit tests for the [ServerOperationIntervalsRecorder].
+#[cfg(test)]
+mod tests {
+    use super::ServerOperationIntervalsRecorder;
+    use chrono::Duration;
+    use std::time::Instant;
+
+    /// Verifies that [ServerOperationIntervalsRecorder] splits iterations into windows as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn record_intervals() {
+        let started = Instant::now();
+        let mut recorder = ServerOperationIntervalsRecorder::new(Duration::seconds(1), started);
+        for _ in 0..10 {
+            recorder.record_success(started + std::time::Duration::from_millis(100), 2_000);
+        }
+        recorder.record_failure(started + std::time::Duration::from_millis(2_100));
+        recorder.record_success(started + std::time::Duration::from_millis(2_200), 40_000);
+
+        let intervals = recorder.into_intervals(Duration::milliseconds(2_500));
+        assert_eq!(3, intervals.intervals.len());
+
+        assert_eq!(Duration::zero(), intervals.intervals[0].offset);
+        assert!((intervals.intervals[0].throughput_per_second - 10.0).abs() < f64::EPSILON);
+        assert_eq!(0, intervals.intervals[0].iterations_failed);
+        assert!((intervals.intervals[0].latency_millis_p99 - 2.0).abs() < 0.01);
+
+        assert_eq!(0, intervals.intervals[1].iterations_failed);
+        assert!(intervals.intervals[1].throughput_per_second.abs() < f64::EPSILON);
+
+        // The last window is only half as long, so its throughput should be scaled up accordingly.
+        assert_eq!(Duration::seconds(2), intervals.intervals[2].offset);
+        assert!((intervals.intervals[2].throughput_per_second - 2.0).abs() < f64::EPSILON);
+        assert_eq!(1, intervals.intervals[2].iterations_failed);
+        assert!((intervals.intervals[2].latency_millis_p100 - 40.0).abs() < 0.1);
+
+        assert_eq!(
+            3,
+            intervals
+                .latency_interval_log
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .count()
+        );
+    }
+
+    /// Verifies that [ServerOperationIntervalsRecorder] leaves the time that it was paused for out of its
+    /// windows.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn record_intervals_with_pause() {
+        let started = Instant::now();
+        let at = |millis| started + std::time::Duration::from_millis(millis);
+        let mut recorder = ServerOperationIntervalsRecorder::new(Duration::seconds(1), started);
+        for _ in 0..10 {
+            recorder.record_success(at(500), 2_000);
+        }
+
+        // Pause for five seconds, e.g. while the server is expunged, which should produce no windows.
+        recorder.pause(at(1_000));
+        recorder.resume(at(6_000));
+        for _ in 0..4 {
+            recorder.record_success(at(6_100), 2_000);
+        }
+        recorder.record_success(at(7_200), 2_000);
+
+        let intervals = recorder.into_intervals(Duration::milliseconds(7_500));
+        assert_eq!(3, intervals.intervals.len());
+        assert!((intervals.intervals[0].throughput_per_second - 10.0).abs() < f64::EPSILON);
+        assert!((intervals.intervals[1].throughput_per_second - 4.0).abs() < f64::EPSILON);
+
+        // The last window is only half as long, once the pause is excluded.
+        assert_eq!(Duration::seconds(2), intervals.intervals[2].offset);
+        assert!((intervals.intervals[2].throughput_per_second - 2.0).abs() < f64::EPSILON);
+    }
+}
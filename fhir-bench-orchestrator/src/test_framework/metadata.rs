@@ -1,36 +1,42 @@
 //! Contains the code to run `/metadata` server operations.
 
 use super::{
-    ServerOperationIterationFailed, ServerOperationIterationStarting,
-    ServerOperationIterationState, ServerOperationIterationSucceeded, ServerOperationMetrics,
+    elapsed_since, measure_operation, run_measurements, run_operation_with_timeout,
+    ServerOperationFailureKind, ServerOperationIterationFailed, ServerOperationIterationStarting,
+    ServerOperationIterationState, ServerOperationIterationSucceeded,
+    ServerOperationMeasurementLength, ServerOperationMeasurementRecorder,
 };
+use crate::config::ContentEncoding;
 use crate::servers::ServerHandle;
+use crate::test_framework::payload::{encode_request, read_response_body, ServerOperationResponse};
+use crate::test_framework::users::{run_virtual_users, VirtualUser};
 use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
 use crate::AppState;
-use chrono::prelude::*;
 use eyre::{eyre, Result};
-use futures::prelude::*;
-use hdrhistogram::Histogram;
-use std::convert::TryFrom;
-use tracing::{trace_span, warn, Instrument};
+use std::time::Instant;
+use tracing::{trace_span, Instrument};
 use url::Url;
 
-static SERVER_OP_NAME_METADATA: &str = "metadata";
+pub(super) static SERVER_OP_NAME_METADATA: &str = "metadata";
 
 /// Verifies and benchmarks the FHIR `/metadata` operations.
 pub async fn benchmark_operation_metadata(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
 ) -> ServerOperationLog {
-    let mut server_op_log = ServerOperationLog::new(SERVER_OP_NAME_METADATA.into());
-    for concurrent_users in app_state.config.concurrency_levels.clone() {
-        let measurement =
-            benchmark_operation_metadata_for_users(app_state, server_handle, concurrent_users)
-                .await;
-        server_op_log.measurements.push(measurement);
-    }
-
-    server_op_log
+    run_measurements(
+        app_state,
+        SERVER_OP_NAME_METADATA,
+        |concurrent_users, compression| {
+            benchmark_operation_metadata_for_users(
+                app_state,
+                server_handle,
+                concurrent_users,
+                compression,
+            )
+        },
+    )
+    .await
 }
 
 /// Creates the URL to access a server's `/metadata` endpoint.
@@ -46,12 +52,14 @@ pub fn create_metadata_url(server_handle: &dyn ServerHandle) -> Url {
 ///
 /// Parameters:
 /// * `server_handle`: the [ServerHandle] for the server to test
+/// * `user`: the [VirtualUser] to run the operation as
 /// * `operation_state`: the initial state machine for this operation iteration
 ///
 /// Returns the final [ServerOperationIterationState] containing information about the operation's
 /// success or failure.
 async fn run_operation_metadata(
     server_handle: &dyn ServerHandle,
+    user: &VirtualUser,
     operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
 ) -> std::result::Result<
     ServerOperationIterationState<ServerOperationIterationSucceeded>,
@@ -59,32 +67,46 @@ async fn run_operation_metadata(
 > {
     let url = create_metadata_url(server_handle);
 
-    let client = match server_handle.client() {
+    let client = match user.client() {
         Ok(client) => client,
         Err(err) => {
             return Err(operation_state
                 .completed()
-                .failed(eyre!(format!("Unable to create client: '{}'", err))));
+                .failed(ServerOperationFailureKind::ConnectionError, err));
         }
     };
 
-    let request_builder = server_handle.request_builder(client, http::Method::GET, url.clone());
+    let (request_builder, request_payload) = encode_request(
+        server_handle.request_builder(client, http::Method::GET, url.clone()),
+        user.compression(),
+        None,
+    );
+    let sent = Instant::now();
     let response = request_builder
         .send()
         .instrument(trace_span!("GET request", %url))
         .await;
 
-    let operation_state = operation_state.completed();
-
     match response {
         Ok(response) => {
             let response_status = response.status();
 
             // Always pull response body, to drain stream and release connection.
-            let response_body = match response.text().await {
-                Ok(response_body) => response_body,
-                Err(err) => format!("Unable to retrieve response body due to error: '{}'", err),
-            };
+            let ServerOperationResponse {
+                body: response_body,
+                payload,
+                phases,
+                received,
+            } = read_response_body(
+                response,
+                user.compression(),
+                user.connect_timer(),
+                request_payload,
+                sent,
+            )
+            .await
+            .unwrap_or_else(ServerOperationResponse::unreadable);
+            let operation_state = operation_state.completed_at(received);
 
             if !response_status.is_success() {
                 let error = eyre!(
@@ -93,14 +115,34 @@ async fn run_operation_metadata(
                     response_status,
                     response_body
                 );
-                let state = operation_state.failed(error);
+                let state = operation_state.failed_response(response_status, &response_body, error);
                 return Err(state);
             }
 
-            // TODO more checks needed
-            Ok(operation_state.succeeded())
+            // Verify that the server actually returned its CapabilityStatement. TODO more checks needed
+            let is_capability_statement = serde_json::from_str::<serde_json::Value>(&response_body)
+                .map(|response_json| response_json["resourceType"] == "CapabilityStatement")
+                .unwrap_or(false);
+            if !is_capability_statement {
+                let error = eyre!(
+                    "The GET /metadata to '{}' did not return a CapabilityStatement: '{}'",
+                    &url,
+                    response_body
+                );
+                return Err(
+                    operation_state.failed(ServerOperationFailureKind::VerificationFailure, error)
+                );
+            }
+
+            Ok(operation_state
+                .succeeded()
+                .with_payload(payload)
+                .with_phases(phases))
         }
-        Err(err) => Err(operation_state.failed(eyre!(format!("HTTP request failed: '{}'", err)))),
+        Err(err) => Err(operation_state.completed().failed(
+            ServerOperationFailureKind::from_request_error(&err),
+            eyre!(format!("HTTP request failed: '{}'", err)),
+        )),
     }
 }
 
@@ -117,23 +159,11 @@ pub async fn check_metadata_operation(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
 ) -> Result<()> {
+    let user = VirtualUser::new(server_handle, None);
     let operation_state = ServerOperationIterationState::new();
-    let operation = crate::test_framework::metadata::run_operation_metadata(
-        server_handle,
-        operation_state.clone(),
-    );
-    let operation = tokio::time::timeout(
-        app_state
-            .config
-            .operation_timeout
-            .to_std()
-            .expect("unable to convert Duration"),
-        operation,
-    );
-
-    // Having the timeout gives us a wrapped Result<Result ...>>. Un-nest them.
-    let result = operation.await?;
-    result
+    let operation = run_operation_metadata(server_handle, &user, operation_state.clone());
+    run_operation_with_timeout(app_state, operation_state, operation)
+        .await
         .map(|_| ())
         .map_err(|err| eyre!("Metadata check failed: '{:?}'", err))
 }
@@ -144,6 +174,7 @@ pub async fn check_metadata_operation(
 /// * `app_state`: the application's [AppState]
 /// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
 /// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
 ///
 /// Returns a [ServerOperationMeasurement] with the results.
 #[tracing::instrument(level = "info", skip(app_state, server_handle))]
@@ -151,72 +182,74 @@ async fn benchmark_operation_metadata_for_users(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
     concurrent_users: u32,
+    compression: Option<ContentEncoding>,
 ) -> ServerOperationMeasurement {
-    /*
-     * Build an iterator: One element for each iteration to run, run the operation for each iteration, and
-     * count the iterations that failed.
-     */
-    let operations = (0..app_state.config.iterations).map(|_| async {
-        let operation_state = ServerOperationIterationState::new();
-        let operation = run_operation_metadata(server_handle, operation_state.clone());
-        let operation = tokio::time::timeout(
-            app_state
-                .config
-                .operation_timeout
-                .to_std()
-                .expect("unable to convert Duration"),
-            operation,
-        );
-
-        // Having the timeout gives us a wrapped Result<Result ...>>. Un-nest them.
-        let result = operation.await;
-        let result = result.map_err(|err| {
-            operation_state
-                .completed()
-                .failed(eyre!("Operation timed out: '{}'", err))
-        });
-        result.and_then(|wrapped_result| wrapped_result)
-    });
+    let (measurement, ()) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || ServerOperationMeasurementRecorder::new(&app_state.config, SERVER_OP_NAME_METADATA),
+        |length, mut recorder| async move {
+            run_operations_metadata(
+                app_state,
+                server_handle,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, ())
+        },
+    )
+    .await;
+    measurement
+}
 
+/// Runs iterations of the FHIR `/metadata` operation for the specified number of concurrent users, until the
+/// specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+async fn run_operations_metadata(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
     /*
-     * Convert that iterator to a parallel stream, and use use `buffer_unordered(...)` to set it to run it only up to
-     * `concurrent_users`, at once.
+     * Build an iterator: One element for each iteration to run. The iterator is lazy, so whether or not to
+     * start each iteration is only decided once a virtual user is ready to run it.
      */
-    let mut operations = futures::stream::iter(operations)
-        .buffer_unordered(usize::try_from(concurrent_users).unwrap());
+    let circuit_breaker = recorder.circuit_breaker();
+    let started = Instant::now();
+    let iterations = (0..).take_while(|iteration| {
+        !circuit_breaker.is_tripped()
+            && length.should_start_iteration(*iteration, elapsed_since(started))
+    });
 
     /*
-     * Kick off the execution of the stream, summing up all of the failures that are encountered.
+     * Run those iterations with `concurrent_users` virtual users, recording the outcome of each iteration.
      */
-    let mut histogram = Histogram::<u64>::new(3).expect("Unable to construct histogram.");
-    let started = Utc::now();
-    let mut iterations_failed: u32 = 0;
-    while let Some(operation_result) = operations.next().await {
-        match operation_result {
-            Ok(operation_success) => {
-                let duration = operation_success.duration();
-                let duration_millis = duration.num_milliseconds();
-                histogram
-                    .record(duration_millis as u64)
-                    .expect("Histogram recording failed.");
-            }
-            Err(err) => {
-                warn!("Operation '{}' failed: '{:?}", SERVER_OP_NAME_METADATA, err);
-                iterations_failed += 1;
-            }
-        }
-    }
-    let completed = Utc::now();
-
-    let iterations_succeeded = app_state.config.iterations - iterations_failed;
-    let execution_duration = completed - started;
-    ServerOperationMeasurement {
+    run_virtual_users(
+        server_handle,
         concurrent_users,
-        started,
-        completed,
-        execution_duration,
-        iterations_failed,
-        iterations_skipped: 0,
-        metrics: ServerOperationMetrics::new(execution_duration, iterations_succeeded, histogram),
-    }
+        compression,
+        app_state.config.think_time.as_ref(),
+        iterations,
+        |user, _| async move {
+            let operation_state = ServerOperationIterationState::new();
+            let operation = run_operation_metadata(server_handle, &user, operation_state.clone());
+            run_operation_with_timeout(app_state, operation_state, operation).await
+        },
+        |operation_result| recorder.record(operation_result),
+    )
+    .await;
 }
@@ -0,0 +1,299 @@
+//! Contains the code to run the mixed-workload operation (see [AppConfig.scenario_mix]).
+
+use super::scenario::{
+    load_sample_data, open_chart_scenario, run_operation_scenario_with_timeout, Scenario,
+    ScenarioInput,
+};
+use super::{
+    elapsed_since, measure_operation, run_measurements, ServerOperationMeasurementLength,
+    ServerOperationMeasurementRecorder,
+};
+use crate::config::{ContentEncoding, ScenarioMixEntry};
+use crate::servers::ServerHandle;
+use crate::test_framework::users::run_virtual_users;
+use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
+use crate::AppState;
+use eyre::{eyre, Result};
+use rand::distributions::{Distribution, WeightedIndex};
+use std::convert::TryFrom;
+use std::time::Instant;
+use tracing::{info_span, Instrument};
+
+pub(super) static SERVER_OP_NAME_MIXED_WORKLOAD: &str = "mixed_workload";
+
+/// The resolved [AppConfig.scenario_mix]: each [Scenario] in the mix, along with the distribution that
+/// each iteration's [Scenario] is picked from.
+pub(super) struct ScenarioMix {
+    /// Each [Scenario] in the mix, in the order that they were configured.
+    scenarios: Vec<Scenario>,
+
+    /// The distribution of the indices of the `scenarios`, per their configured weights.
+    weights: WeightedIndex<u32>,
+}
+
+/// Verifies and benchmarks the mixed-workload operation, after loading the sample data into the server for
+/// its scenarios to run against.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+///
+/// Returns the [ServerOperationLog] for the mixed-workload operation.
+pub async fn benchmark_mixed_workload(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> ServerOperationLog {
+    let (mix, inputs) = match setup_mixed_workload(app_state, server_handle).await {
+        Ok(setup) => setup,
+        Err(err) => {
+            let mut server_op_log = ServerOperationLog::new(SERVER_OP_NAME_MIXED_WORKLOAD.into());
+            server_op_log.errors.push(format!("{:?}", err));
+            return server_op_log;
+        }
+    };
+
+    run_measurements(
+        app_state,
+        SERVER_OP_NAME_MIXED_WORKLOAD,
+        |concurrent_users, compression| {
+            benchmark_mixed_workload_for_users(
+                app_state,
+                server_handle,
+                &mix,
+                &inputs,
+                concurrent_users,
+                compression,
+            )
+        },
+    )
+    .await
+}
+
+/// Resolves the [AppConfig.scenario_mix] and then loads the sample data into the server for its scenarios to
+/// run against.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+///
+/// Returns the [ScenarioMix] and the [ScenarioInput]s for its iterations.
+async fn setup_mixed_workload(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> Result<(ScenarioMix, Vec<ScenarioInput>)> {
+    let mix = resolve_mix(&app_state.config.scenario_mix, &app_state.scenarios)?;
+    let inputs = load_sample_data(app_state, server_handle)
+        .instrument(info_span!(
+            "load_sample_data",
+            scenario = SERVER_OP_NAME_MIXED_WORKLOAD
+        ))
+        .await?;
+
+    Ok((mix, inputs))
+}
+
+/// Finds the [Scenario] for each of the specified [ScenarioMixEntry]s.
+///
+/// Parameters:
+/// * `scenario_mix`: the [AppConfig.scenario_mix] to resolve
+/// * `scenarios`: the [Scenario]s loaded from the `scenarios/` directory, which are available in addition to
+///   the built-in ones
+///
+/// Returns the resolved [ScenarioMix], or an error if any of its scenarios couldn't be found, or if its
+/// weights can't be sampled from.
+pub(super) fn resolve_mix(
+    scenario_mix: &[ScenarioMixEntry],
+    scenarios: &[Scenario],
+) -> Result<ScenarioMix> {
+    let built_in_scenarios = [open_chart_scenario()];
+    let mixed_scenarios = scenario_mix
+        .iter()
+        .map(|entry| {
+            built_in_scenarios
+                .iter()
+                .chain(scenarios.iter())
+                .find(|scenario| scenario.name == entry.scenario)
+                .cloned()
+                .ok_or_else(|| eyre!("Unknown scenario '{}' in scenario mix.", entry.scenario))
+        })
+        .collect::<Result<Vec<Scenario>>>()?;
+    let weights = WeightedIndex::new(scenario_mix.iter().map(|entry| entry.weight))
+        .map_err(|err| eyre!("Invalid scenario mix weights: '{}'.", err))?;
+
+    Ok(ScenarioMix {
+        scenarios: mixed_scenarios,
+        weights,
+    })
+}
+
+/// Verifies and benchmarks the mixed-workload operation for the specified number of concurrent users.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `mix`: the [ScenarioMix] to run
+/// * `inputs`: the [ScenarioInput] for each iteration, which will be cycled through
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+///
+/// Returns a [ServerOperationMeasurement] with the results.
+#[tracing::instrument(level = "info", skip(app_state, server_handle, mix, inputs))]
+async fn benchmark_mixed_workload_for_users(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    mix: &ScenarioMix,
+    inputs: &[ScenarioInput],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+) -> ServerOperationMeasurement {
+    let mixed_operations: Vec<&str> = mix
+        .scenarios
+        .iter()
+        .map(|scenario| scenario.name.as_str())
+        .collect();
+    let (measurement, ()) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || {
+            ServerOperationMeasurementRecorder::new_mixed(
+                &app_state.config,
+                SERVER_OP_NAME_MIXED_WORKLOAD,
+                &mixed_operations,
+            )
+        },
+        |length, mut recorder| async move {
+            run_operations_mixed(
+                app_state,
+                server_handle,
+                mix,
+                inputs,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, ())
+        },
+    )
+    .await;
+    measurement
+}
+
+/// Runs iterations of the mixed-workload operation for the specified number of concurrent users, until the
+/// specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `mix`: the [ScenarioMix] to run
+/// * `inputs`: the [ScenarioInput] for each iteration, which will be cycled through
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+#[allow(clippy::too_many_arguments)]
+async fn run_operations_mixed(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    mix: &ScenarioMix,
+    inputs: &[ScenarioInput],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
+    /*
+     * Build an iterator: One element (the index of the mix's scenario to run, and its [ScenarioInput]) for
+     * each iteration to run. The iterator is lazy, so whether or not to start each iteration is only decided
+     * once a virtual user is ready to run it.
+     */
+    let mut rng = app_state.workload_plan.rng(SERVER_OP_NAME_MIXED_WORKLOAD);
+    let circuit_breaker = recorder.circuit_breaker();
+    let started = Instant::now();
+    let iterations = (0..)
+        .take_while(|iteration| {
+            !circuit_breaker.is_tripped()
+                && length.should_start_iteration(*iteration, elapsed_since(started))
+        })
+        .map(|iteration| {
+            (
+                mix.weights.sample(&mut rng),
+                inputs[usize::try_from(iteration).unwrap() % inputs.len()].clone(),
+            )
+        });
+
+    /*
+     * Run those iterations with `concurrent_users` virtual users, recording the outcome of each iteration.
+     */
+    run_virtual_users(
+        server_handle,
+        concurrent_users,
+        compression,
+        app_state.config.think_time.as_ref(),
+        iterations,
+        |user, (mixed_operation, input)| async move {
+            let scenario = &mix.scenarios[mixed_operation];
+            let result = run_operation_scenario_with_timeout(
+                app_state,
+                server_handle,
+                user,
+                scenario,
+                input,
+            )
+            .await;
+            (mixed_operation, result)
+        },
+        |(mixed_operation, operation_result)| {
+            recorder.record_mixed(mixed_operation, operation_result)
+        },
+    )
+    .await;
+}
+
+/// Unit tests for the mixed-workload operation.
+#[cfg(test)]
+mod tests {
+    use crate::config::ScenarioMixEntry;
+    use crate::test_framework::scenario::Scenario;
+
+    /// Verifies that [ScenarioMixEntry]s are parsed and resolved by [super::resolve_mix] as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn resolve_mix() {
+        let scenario_mix: Vec<ScenarioMixEntry> = "scenario_open_chart=70, read_patient=30"
+            .split(',')
+            .map(|entry| entry.parse().unwrap())
+            .collect();
+        assert_eq!(
+            ScenarioMixEntry {
+                scenario: "read_patient".into(),
+                weight: 30
+            },
+            scenario_mix[1]
+        );
+        assert!("read_patient".parse::<ScenarioMixEntry>().is_err());
+        assert!("read_patient=0".parse::<ScenarioMixEntry>().is_err());
+
+        let scenarios = vec![Scenario {
+            name: "read_patient".into(),
+            steps: vec![],
+        }];
+        let mix = super::resolve_mix(&scenario_mix, &scenarios).unwrap();
+        assert_eq!("scenario_open_chart", mix.scenarios[0].name);
+        assert_eq!("read_patient", mix.scenarios[1].name);
+
+        assert!(super::resolve_mix(&scenario_mix, &[]).is_err());
+        assert!(super::resolve_mix(&[], &scenarios).is_err());
+
+        // Only scenarios can be mixed, not the other built-in operations.
+        let metadata_mix = vec!["metadata=1".parse().unwrap()];
+        assert!(super::resolve_mix(&metadata_mix, &scenarios).is_err());
+
+        // The mix is checked up front, unless there isn't one.
+        assert!(crate::test_framework::check_scenario_mix(&scenario_mix, &scenarios).is_ok());
+        assert!(crate::test_framework::check_scenario_mix(&metadata_mix, &scenarios).is_err());
+        assert!(crate::test_framework::check_scenario_mix(&[], &[]).is_ok());
+    }
+}
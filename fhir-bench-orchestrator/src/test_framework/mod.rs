@@ -1,17 +1,49 @@
 //! Contains the `run_operations(...)` method and result types for the benchmark test framework.
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, ContentEncoding, ScenarioMixEntry};
 use crate::servers::{ServerHandle, ServerName, ServerPlugin, ServerPluginWrapper};
-use crate::util::{serde_duration_iso8601, serde_histogram};
+use crate::test_framework::circuit_breaker::{CircuitBreakerTrip, ServerOperationCircuitBreaker};
+use crate::test_framework::intervals::ServerOperationIntervalsRecorder;
+use crate::test_framework::payload::{ServerOperationPayload, ServerOperationPayloadRecorder};
+use crate::test_framework::phases::{ServerOperationPhases, ServerOperationPhasesRecorder};
+use crate::test_framework::scenario::Scenario;
+use crate::test_framework::workload_plan::WorkloadPlan;
+use crate::util::{serde_duration_iso8601, serde_duration_millis, serde_histogram};
 use crate::AppState;
 use chrono::prelude::*;
 use chrono::Duration;
-use eyre::Result;
+use eyre::{eyre, Result};
 use hdrhistogram::Histogram;
 use serde::{Deserialize, Serialize};
-
+use std::convert::TryFrom;
+use std::future::Future;
+use std::time::Instant;
+use tracing::{info, info_span, warn, Instrument};
+
+mod circuit_breaker;
+mod contention;
+mod index_lag;
+mod intervals;
 pub mod metadata;
+mod mix;
+mod payload;
+mod phases;
 mod post_org;
+pub mod replay;
+mod saturation;
+pub mod scenario;
+mod trials;
+mod users;
+pub mod workload_plan;
+
+/// The maximum number of [ServerOperationFailureExample]s to keep for each [ServerOperationMeasurement].
+const MAX_FAILURE_EXAMPLES: usize = 10;
+
+/// The number of microseconds in a millisecond.
+const MICROS_PER_MILLI: f64 = 1_000.0;
+
+/// The number of microseconds in a second.
+const MICROS_PER_SECOND: f64 = 1_000_000.0;
 
 /// Stores the complete set of results from a run of the framework.
 #[derive(Clone, Deserialize, Serialize)]
@@ -22,9 +54,16 @@ pub struct FrameworkResults {
     /// When the benchmark framework run started, in wall clock time.
     pub completed: Option<DateTime<Utc>>,
 
+    /// If the benchmark framework run was interrupted before it could complete, a note saying so. Its
+    /// results will only include the operations that had completed by then.
+    pub interrupted: Option<String>,
+
     /// The configuration that the benchmark framework was run with.
     pub config: AppConfig,
 
+    /// The [WorkloadPlan] that every server was benchmarked with.
+    pub workload_plan: WorkloadPlan,
+
     /// Details on the system used to run the benchmarks.
     pub benchmark_metadata: FrameworkMetadata,
 
@@ -37,12 +76,19 @@ impl FrameworkResults {
     ///
     /// Params:
     /// * `config`: the application's configuration
+    /// * `workload_plan`: the [WorkloadPlan] that every server will be benchmarked with
     /// * `server_plugins`: the set of [ServerPlugin]s representing the supported FHIR server implementations
-    pub fn new(config: &AppConfig, server_plugins: &[ServerPluginWrapper]) -> FrameworkResults {
+    pub fn new(
+        config: &AppConfig,
+        workload_plan: &WorkloadPlan,
+        server_plugins: &[ServerPluginWrapper],
+    ) -> FrameworkResults {
         FrameworkResults {
             started: Utc::now(),
             completed: None,
+            interrupted: None,
             config: config.clone(),
+            workload_plan: workload_plan.clone(),
             benchmark_metadata: FrameworkMetadata::default(),
             servers: server_plugins
                 .iter()
@@ -183,13 +229,26 @@ pub struct ServerOperationLog {
     /// The name of the operation that was benchmarked.
     pub operation: ServerOperationName,
 
+    /// The effective settings that the operation was benchmarked with, after applying any
+    /// [AppConfig.operation_overrides] for it.
+    pub settings: Option<ServerOperationSettings>,
+
     /// The error message of any problems that halted the benchmark attempt early. Generally, if this is not
     /// empty, then the number of skipped iterations in some/all of the measurements will be non-zero and some expected
     /// `measurements` entries may be missing.
     pub errors: Vec<String>,
 
-    /// The benchmark runs/measurements made at various levels of concurrency.
+    /// The benchmark runs/measurements made at various levels of concurrency (and for each of the
+    /// [AppConfig.trials]).
     pub measurements: Vec<ServerOperationMeasurement>,
+
+    /// Summarizes the `measurements` at each level of concurrency, across all of the [AppConfig.trials]. This
+    /// will be empty unless more than one trial was run.
+    pub summaries: Vec<ServerOperationSummary>,
+
+    /// The headline results of the saturation search for this operation, if [AppConfig.saturation] mode
+    /// was enabled.
+    pub saturation: Option<ServerOperationSaturation>,
 }
 
 impl ServerOperationLog {
@@ -197,18 +256,118 @@ impl ServerOperationLog {
     pub fn new(operation: ServerOperationName) -> ServerOperationLog {
         ServerOperationLog {
             operation,
+            settings: None,
             errors: vec![],
             measurements: vec![],
+            summaries: vec![],
+            saturation: None,
         }
     }
 }
 
+/// The effective [AppConfig] settings that a [ServerOperationLog]'s operation was benchmarked with, which
+/// may differ from the rest of the run's due to [AppConfig.operation_overrides].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ServerOperationSettings {
+    /// The effective [AppConfig.iterations].
+    pub iterations: u32,
+
+    /// The effective [AppConfig.operation_timeout].
+    #[serde(with = "serde_duration_millis")]
+    pub operation_timeout: Duration,
+
+    /// The effective [AppConfig.concurrency_levels].
+    pub concurrency_levels: Vec<u32>,
+}
+
+impl ServerOperationSettings {
+    /// Constructs a new [ServerOperationSettings] from the specified effective [AppConfig].
+    ///
+    /// Parameters:
+    /// * `config`: the effective [AppConfig] for the operation, per [AppConfig::for_operation]
+    pub fn new(config: &AppConfig) -> ServerOperationSettings {
+        ServerOperationSettings {
+            iterations: config.iterations,
+            operation_timeout: config.operation_timeout,
+            concurrency_levels: config.concurrency_levels.clone(),
+        }
+    }
+}
+
+/// Summarizes the [ServerOperationMeasurement]s for each of the [AppConfig.trials] at one level of
+/// concurrency (and [AppConfig.compression]).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerOperationSummary {
+    /// The number of concurrent users' worth of load that the summarized measurements attempted to generate.
+    pub concurrent_users: u32,
+
+    /// The [ContentEncoding] that the summarized measurements requested, if any.
+    pub compression: Option<ContentEncoding>,
+
+    /// The number of trials (i.e. measurements) summarized.
+    pub trials: u32,
+
+    pub throughput_per_second: ConfidenceInterval,
+    pub latency_millis_p50: ConfidenceInterval,
+    pub latency_millis_p90: ConfidenceInterval,
+    pub latency_millis_p99: ConfidenceInterval,
+    pub latency_corrected_millis_p99: ConfidenceInterval,
+}
+
+/// The mean of a metric across several trials, along with its 95% confidence interval.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Details the highest load that a [ServerOperationLog]'s operation was able to sustain, as found by a
+/// saturation search. The measurements for each step of the search are recorded in
+/// [ServerOperationLog.measurements], as usual.
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationSaturation {
+    /// The highest number of concurrent users that the operation met its SLO at, or `None` if it never did.
+    pub max_concurrent_users: Option<u32>,
+
+    /// The throughput that the operation achieved at `max_concurrent_users`, or `None` if it never met its
+    /// SLO.
+    pub max_throughput_per_second: Option<f64>,
+
+    /// Why the saturation search stopped.
+    pub stop_reason: ServerOperationSaturationStop,
+}
+
+/// Enumerates the reasons that a saturation search might stop ramping up load.
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub enum ServerOperationSaturationStop {
+    /// The (corrected) p99 latency, in milliseconds, at the last concurrency level tried exceeded the SLO.
+    LatencyExceeded(f64),
+
+    /// The error rate at the last concurrency level tried exceeded the configured maximum.
+    ErrorRateExceeded(f64),
+
+    /// The measurement at the last concurrency level tried had skipped iterations, and so could not be
+    /// trusted.
+    IterationsSkipped(u32),
+
+    /// The configured maximum concurrency level was reached while still meeting the SLO.
+    MaxConcurrencyReached(),
+}
+
 /// Models the measurement attempts made for a [ServerOperationLog] at a particular level of concurrency.
 #[derive(Deserialize, Clone, Serialize)]
 pub struct ServerOperationMeasurement {
     /// The number of concurrent users' worth of load that the benchmark attempted to generate.
     pub concurrent_users: u32,
 
+    /// The [ContentEncoding] that this measurement's requests asked for (and compressed their bodies with),
+    /// per [AppConfig.compression], if any.
+    pub compression: Option<ContentEncoding>,
+
+    /// Which of the [AppConfig.trials] this measurement was part of, starting from `0`.
+    pub trial: u32,
+
     /// When this measurement attempt started, in wall-clock time.
     pub started: DateTime<Utc>,
 
@@ -221,14 +380,269 @@ pub struct ServerOperationMeasurement {
     #[serde(with = "serde_duration_iso8601")]
     pub execution_duration: Duration,
 
+    /// The number of iterations that were actually run (including any that failed), which will vary from
+    /// measurement to measurement if [AppConfig.measurement_duration] is set.
+    pub iterations: u32,
+
     /// The number of iterations that failed to produce the expected result.
     pub iterations_failed: u32,
 
     /// The number of iterations that were skipped due to problems that halte the benchmark attempt early.
     pub iterations_skipped: u32,
 
+    /// The [ServerOperationSkipReason] detailing why the measurement was halted early, if it was.
+    pub skip_reason: Option<ServerOperationSkipReason>,
+
+    /// Breaks down the `iterations_failed` by [ServerOperationFailureKind], with some examples.
+    pub failures: ServerOperationFailures,
+
+    /// The [ServerOperationWarmup] that was run before this measurement, if any.
+    pub warmup: Option<ServerOperationWarmup>,
+
     /// The [ServerOperationMetrics] for the measurement attempt.
     pub metrics: ServerOperationMetrics,
+
+    /// The [ServerOperationStepMetrics] for each step of the operation, if it's a multi-step scenario. The
+    /// `metrics` above cover each iteration end-to-end, across all of its steps.
+    pub steps: Vec<ServerOperationStepMetrics>,
+
+    /// The [ServerOperationMixMetrics] for each operation, if this is a mixed-workload measurement (see
+    /// [AppConfig.scenario_mix]). The `metrics` above cover all of the operations combined.
+    pub operations: Vec<ServerOperationMixMetrics>,
+
+    /// The [ServerOperationContention] observed, if this is an update contention measurement.
+    pub contention: Option<ServerOperationContention>,
+}
+
+/// Enumerates the reasons that a [ServerOperationMeasurement] may be halted early, skipping the rest of its
+/// iterations.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ServerOperationSkipReason {
+    /// The [AppConfig.circuit_breaker] tripped, at the specified failure rate.
+    CircuitBreakerTripped(f64),
+
+    /// The server could not be expunged between groups of iterations, with the specified error.
+    ExpungeFailed(String),
+}
+
+/// Details the optimistic-locking (`If-Match`) behavior observed during an update contention measurement,
+/// where every virtual user updates the same small set of resources at once.
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationContention {
+    /// The number of resources that were contended over.
+    pub resources: u32,
+
+    /// The number of updates that the server accepted.
+    pub updates_succeeded: u32,
+
+    /// The number of updates that the server rejected with `412 Precondition Failed` (or `409 Conflict`),
+    /// because the resource had been updated since it was read.
+    pub precondition_failures: u32,
+
+    /// The number of times that a rejected update was retried (from a fresh read).
+    pub retries: u32,
+
+    /// The number of updates whose outcome is unknown, as no response was received for them (e.g. they
+    /// timed out), so the server may or may not have applied them.
+    pub updates_unknown: u32,
+
+    /// The number of accepted updates that aren't reflected in the final versions of the resources, which
+    /// should always be zero. This is `None` if the resources couldn't be read back to check. Any of the
+    /// `updates_unknown` that the server applied will mask as many lost updates, so this is a lower bound.
+    pub lost_updates: Option<u32>,
+}
+
+/// Details the results of one of the operations in a mixed-workload measurement, from the same measurement
+/// window as (and concurrently with) the rest of the mix's operations.
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationMixMetrics {
+    /// The name of the operation.
+    pub operation: String,
+
+    /// The number of iterations of this operation that were run (including any that failed).
+    pub iterations: u32,
+
+    /// The number of iterations of this operation that failed to produce the expected result.
+    pub iterations_failed: u32,
+
+    /// Breaks down the `iterations_failed` by [ServerOperationFailureKind], with some examples.
+    pub failures: ServerOperationFailures,
+
+    /// The [ServerOperationMetrics] for this operation's iterations.
+    pub metrics: ServerOperationMetrics,
+
+    /// The [ServerOperationStepMetrics] for each step of this operation, if it's a multi-step scenario.
+    pub steps: Vec<ServerOperationStepMetrics>,
+}
+
+/// Details the latency of a single step of a multi-step scenario operation (or of a single phase of an
+/// operation's requests, such as its time to first byte), across all of the iterations that succeeded. As
+/// with [ServerOperationMetrics], the latencies are recorded in the histogram in microseconds, but the
+/// percentiles here are reported in (fractional) milliseconds.
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationStepMetrics {
+    /// The name of the step (or phase).
+    pub step: String,
+
+    pub latency_millis_mean: f64,
+    pub latency_millis_p50: f64,
+    pub latency_millis_p90: f64,
+    pub latency_millis_p99: f64,
+    pub latency_millis_p999: f64,
+    pub latency_millis_p100: f64,
+    #[serde(with = "serde_histogram")]
+    pub latency_histogram: Histogram<u64>,
+}
+
+impl ServerOperationStepMetrics {
+    /// Constructs a new [ServerOperationStepMetrics] from the specified step's latency histogram.
+    ///
+    /// Parameters:
+    /// * `step`: the name of the step
+    /// * `histogram`: the latencies of the step, in microseconds
+    pub fn new(step: String, histogram: Histogram<u64>) -> ServerOperationStepMetrics {
+        let millis = |micros: u64| micros as f64 / MICROS_PER_MILLI;
+
+        ServerOperationStepMetrics {
+            step,
+            latency_millis_mean: histogram.mean() / MICROS_PER_MILLI,
+            latency_millis_p50: millis(histogram.value_at_quantile(0.5)),
+            latency_millis_p90: millis(histogram.value_at_quantile(0.9)),
+            latency_millis_p99: millis(histogram.value_at_quantile(0.99)),
+            latency_millis_p999: millis(histogram.value_at_quantile(0.999)),
+            latency_millis_p100: millis(histogram.max()),
+            latency_histogram: histogram,
+        }
+    }
+}
+
+/// Summarizes the warmup iterations that were run before a [ServerOperationMeasurement], none of which are
+/// included in its counts or [ServerOperationMetrics].
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationWarmup {
+    /// When the warmup started, in wall-clock time.
+    pub started: DateTime<Utc>,
+
+    /// When the warmup completed, in wall-clock time.
+    pub completed: DateTime<Utc>,
+
+    /// How long the warmup spent running iterations.
+    #[serde(with = "serde_duration_iso8601")]
+    pub execution_duration: Duration,
+
+    /// The number of warmup iterations that were run (including any that failed).
+    pub iterations: u32,
+
+    /// The number of warmup iterations that failed to produce the expected result.
+    pub iterations_failed: u32,
+}
+
+/// Enumerates the categories that a failed iteration may fall into.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ServerOperationFailureKind {
+    /// The iteration did not complete within the [AppConfig.operation_timeout].
+    Timeout,
+
+    /// The request could not be sent, or its connection to the server failed.
+    ConnectionError,
+
+    /// The server responded with an HTTP `4xx` status.
+    HttpClientError,
+
+    /// The server responded with an HTTP `5xx` status (or some other unexpected non-success status).
+    HttpServerError,
+
+    /// The server responded successfully, but the response was not what was expected.
+    VerificationFailure,
+}
+
+impl ServerOperationFailureKind {
+    /// Returns the [ServerOperationFailureKind] for an unsuccessful HTTP response's status.
+    ///
+    /// Parameters:
+    /// * `status`: the [http::StatusCode] of the unsuccessful response
+    fn from_status(status: http::StatusCode) -> ServerOperationFailureKind {
+        if status.is_client_error() {
+            ServerOperationFailureKind::HttpClientError
+        } else {
+            ServerOperationFailureKind::HttpServerError
+        }
+    }
+
+    /// Returns the [ServerOperationFailureKind] for a failed HTTP request.
+    ///
+    /// Parameters:
+    /// * `error`: the [reqwest::Error] that the request failed with
+    fn from_request_error(error: &reqwest::Error) -> ServerOperationFailureKind {
+        if error.is_timeout() {
+            ServerOperationFailureKind::Timeout
+        } else {
+            ServerOperationFailureKind::ConnectionError
+        }
+    }
+}
+
+/// Counts the failed iterations of a [ServerOperationMeasurement] by [ServerOperationFailureKind], and keeps
+/// the first few of them as examples.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ServerOperationFailures {
+    /// The number of iterations that failed with [ServerOperationFailureKind::Timeout].
+    pub timeout: u32,
+
+    /// The number of iterations that failed with [ServerOperationFailureKind::ConnectionError].
+    pub connection_error: u32,
+
+    /// The number of iterations that failed with [ServerOperationFailureKind::HttpClientError].
+    pub http_client_error: u32,
+
+    /// The number of iterations that failed with [ServerOperationFailureKind::HttpServerError].
+    pub http_server_error: u32,
+
+    /// The number of iterations that failed with [ServerOperationFailureKind::VerificationFailure].
+    pub verification_failure: u32,
+
+    /// The first [MAX_FAILURE_EXAMPLES] failures that were encountered.
+    pub examples: Vec<ServerOperationFailureExample>,
+}
+
+impl ServerOperationFailures {
+    /// Counts a failure of the specified [ServerOperationFailureKind], keeping it as an example if there's
+    /// still room for more of those.
+    ///
+    /// Parameters:
+    /// * `kind`: the [ServerOperationFailureKind] of the failure
+    /// * `example`: a function that produces the [ServerOperationFailureExample] for the failure
+    fn record<F>(&mut self, kind: ServerOperationFailureKind, example: F)
+    where
+        F: FnOnce() -> ServerOperationFailureExample,
+    {
+        let count = match kind {
+            ServerOperationFailureKind::Timeout => &mut self.timeout,
+            ServerOperationFailureKind::ConnectionError => &mut self.connection_error,
+            ServerOperationFailureKind::HttpClientError => &mut self.http_client_error,
+            ServerOperationFailureKind::HttpServerError => &mut self.http_server_error,
+            ServerOperationFailureKind::VerificationFailure => &mut self.verification_failure,
+        };
+        *count += 1;
+
+        if self.examples.len() < MAX_FAILURE_EXAMPLES {
+            self.examples.push(example());
+        }
+    }
+}
+
+/// An example of a failed iteration, as kept in [ServerOperationFailures].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerOperationFailureExample {
+    /// The [ServerOperationFailureKind] of the failure.
+    pub kind: ServerOperationFailureKind,
+
+    /// The error message detailing the failure.
+    pub message: String,
+
+    /// The `diagnostics` of each issue in the FHIR `OperationOutcome` that the server responded with, if
+    /// any.
+    pub diagnostics: Vec<String>,
 }
 
 /// Represents the unique name of a FHIR server operation that this framework tests.
@@ -256,18 +670,43 @@ impl std::fmt::Display for ServerOperationName {
 }
 
 /// Details the performance of a single server operation, across all iterations (including any failures).
+///
+/// Latencies are recorded in the histograms in microseconds, but the percentiles here are all reported in
+/// (fractional) milliseconds.
+///
+/// The `latency_corrected_*` values are calculated from a histogram that has been corrected for coordinated
+/// omission, per [AppConfig.expected_interval]. If no expected interval was configured, they will match the
+/// raw `latency_*` values.
+///
+/// The `intervals` break those same iterations down into a time series, per [AppConfig.interval_window].
+///
+/// The `payload` details the size of the request and response bodies of the iterations that succeeded, and
+/// the `phases` break their latencies down into the `connect`, `ttfb` (time to first byte), and `body`
+/// phases.
 #[derive(Deserialize, Clone, Serialize)]
 pub struct ServerOperationMetrics {
     pub throughput_per_second: f64,
     pub latency_millis_mean: f64,
-    pub latency_millis_p50: u64,
-    pub latency_millis_p90: u64,
-    pub latency_millis_p99: u64,
-    pub latency_millis_p999: u64,
-    pub latency_millis_p100: u64,
+    pub latency_millis_p50: f64,
+    pub latency_millis_p90: f64,
+    pub latency_millis_p99: f64,
+    pub latency_millis_p999: f64,
+    pub latency_millis_p100: f64,
+    pub latency_corrected_millis_mean: f64,
+    pub latency_corrected_millis_p50: f64,
+    pub latency_corrected_millis_p90: f64,
+    pub latency_corrected_millis_p99: f64,
+    pub latency_corrected_millis_p999: f64,
+    pub latency_corrected_millis_p100: f64,
     #[serde(with = "serde_histogram")]
     pub latency_histogram: Histogram<u64>,
     pub latency_histogram_hgrm_gzip: String,
+    #[serde(with = "serde_histogram")]
+    pub latency_corrected_histogram: Histogram<u64>,
+    pub latency_corrected_histogram_hgrm_gzip: String,
+    pub intervals: ServerOperationIntervals,
+    pub payload: ServerOperationPayloadMetrics,
+    pub phases: Vec<ServerOperationStepMetrics>,
 }
 
 impl ServerOperationMetrics {
@@ -275,26 +714,670 @@ impl ServerOperationMetrics {
         duration: Duration,
         iterations_succeeded: u32,
         histogram: Histogram<u64>,
+        histogram_corrected: Histogram<u64>,
+        intervals: ServerOperationIntervals,
+        payload: ServerOperationPayloadMetrics,
+        phases: Vec<ServerOperationStepMetrics>,
     ) -> ServerOperationMetrics {
-        let duration_millis: f64 = duration.num_milliseconds() as f64;
-        let throughput_per_millis: f64 = Into::<f64>::into(iterations_succeeded) / duration_millis;
-        let throughput_per_second: f64 = throughput_per_millis * 1000f64;
+        let duration_micros: f64 = duration.num_microseconds().unwrap_or(i64::MAX) as f64;
+        let throughput_per_micros: f64 = Into::<f64>::into(iterations_succeeded) / duration_micros;
+        let throughput_per_second: f64 = throughput_per_micros * MICROS_PER_SECOND;
         let latency_histogram_hgrm_gzip =
-            crate::util::histogram_hgrm_export::export_to_hgrm_gzip(&histogram)
+            crate::util::histogram_hgrm_export::export_to_hgrm_gzip(&histogram, MICROS_PER_MILLI)
                 .expect("Unable to export histogram.");
+        let latency_corrected_histogram_hgrm_gzip =
+            crate::util::histogram_hgrm_export::export_to_hgrm_gzip(
+                &histogram_corrected,
+                MICROS_PER_MILLI,
+            )
+            .expect("Unable to export histogram.");
+
+        // The histograms record microseconds, so convert those values to fractional milliseconds.
+        let millis = |micros: u64| micros as f64 / MICROS_PER_MILLI;
 
         ServerOperationMetrics {
             throughput_per_second,
-            latency_millis_mean: histogram.mean(),
-            latency_millis_p50: histogram.value_at_quantile(0.5),
-            latency_millis_p90: histogram.value_at_quantile(0.9),
-            latency_millis_p99: histogram.value_at_quantile(0.99),
-            latency_millis_p999: histogram.value_at_quantile(0.999),
-            latency_millis_p100: histogram.max(),
+            latency_millis_mean: histogram.mean() / MICROS_PER_MILLI,
+            latency_millis_p50: millis(histogram.value_at_quantile(0.5)),
+            latency_millis_p90: millis(histogram.value_at_quantile(0.9)),
+            latency_millis_p99: millis(histogram.value_at_quantile(0.99)),
+            latency_millis_p999: millis(histogram.value_at_quantile(0.999)),
+            latency_millis_p100: millis(histogram.max()),
+            latency_corrected_millis_mean: histogram_corrected.mean() / MICROS_PER_MILLI,
+            latency_corrected_millis_p50: millis(histogram_corrected.value_at_quantile(0.5)),
+            latency_corrected_millis_p90: millis(histogram_corrected.value_at_quantile(0.9)),
+            latency_corrected_millis_p99: millis(histogram_corrected.value_at_quantile(0.99)),
+            latency_corrected_millis_p999: millis(histogram_corrected.value_at_quantile(0.999)),
+            latency_corrected_millis_p100: millis(histogram_corrected.max()),
             latency_histogram: histogram,
             latency_histogram_hgrm_gzip,
+            latency_corrected_histogram: histogram_corrected,
+            latency_corrected_histogram_hgrm_gzip,
+            intervals,
+            payload,
+            phases,
+        }
+    }
+}
+
+/// Details the size of the request and response bodies for a [ServerOperationMetrics], in bytes.
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationPayloadMetrics {
+    /// The size of the request bodies sent by each iteration, before compression (if any).
+    pub request_bytes: ServerOperationPayloadSizes,
+
+    /// The size of the compressed request bodies sent by each iteration, as they were sent over the wire, or
+    /// `None` if no request bodies were compressed.
+    pub request_compressed_bytes: Option<ServerOperationPayloadSizes>,
+
+    /// The size of the response bodies received by each iteration, after decompression (if any).
+    pub response_bytes: ServerOperationPayloadSizes,
+
+    /// The size of the compressed response bodies received by each iteration, as they were sent over the
+    /// wire, or `None` if the server never compressed its responses.
+    pub response_compressed_bytes: Option<ServerOperationPayloadSizes>,
+
+    /// The number of responses received by the iterations.
+    pub responses: u64,
+
+    /// The number of responses that the server encoded as the [ServerOperationMeasurement.compression]
+    /// requested (or, if none was requested, didn't encode at all).
+    pub responses_encoding_honoured: u64,
+}
+
+/// Summarizes a histogram of payload sizes, in bytes.
+#[derive(Deserialize, Clone, Serialize)]
+pub struct ServerOperationPayloadSizes {
+    pub bytes_mean: f64,
+    pub bytes_p50: u64,
+    pub bytes_p90: u64,
+    pub bytes_p99: u64,
+    pub bytes_p100: u64,
+    #[serde(with = "serde_histogram")]
+    pub bytes_histogram: Histogram<u64>,
+}
+
+impl ServerOperationPayloadSizes {
+    /// Constructs a new [ServerOperationPayloadSizes] from the specified histogram.
+    ///
+    /// Parameters:
+    /// * `histogram`: the payload sizes, in bytes
+    pub fn new(histogram: Histogram<u64>) -> ServerOperationPayloadSizes {
+        ServerOperationPayloadSizes {
+            bytes_mean: histogram.mean(),
+            bytes_p50: histogram.value_at_quantile(0.5),
+            bytes_p90: histogram.value_at_quantile(0.9),
+            bytes_p99: histogram.value_at_quantile(0.99),
+            bytes_p100: histogram.max(),
+            bytes_histogram: histogram,
+        }
+    }
+}
+
+/// The time series of interval metrics for a [ServerOperationMetrics], with one [ServerOperationInterval] per
+/// [AppConfig.interval_window].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerOperationIntervals {
+    /// The length of each interval.
+    #[serde(with = "serde_duration_millis")]
+    pub window: Duration,
+
+    /// The [ServerOperationInterval]s, in order.
+    pub intervals: Vec<ServerOperationInterval>,
+
+    /// The latency histogram for each interval, as an HdrHistogram interval log (with values reported in
+    /// milliseconds).
+    pub latency_interval_log: String,
+}
+
+/// Details the performance of a single server operation across the iterations that completed within one
+/// window of its measurement.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerOperationInterval {
+    /// How far into the measurement this interval started.
+    #[serde(with = "serde_duration_millis")]
+    pub offset: Duration,
+
+    pub throughput_per_second: f64,
+    pub iterations_failed: u32,
+    pub latency_millis_p50: f64,
+    pub latency_millis_p90: f64,
+    pub latency_millis_p99: f64,
+    pub latency_millis_p100: f64,
+}
+
+/// Specifies how long a [ServerOperationMeasurement] should keep starting new iterations for.
+#[derive(Clone, Copy, Debug)]
+enum ServerOperationMeasurementLength {
+    /// The measurement should run exactly this many iterations.
+    Iterations(u32),
+
+    /// The measurement should start iterations until the specified [Duration] has elapsed, subject to the
+    /// specified minimum and maximum iteration counts.
+    Duration {
+        duration: Duration,
+        min_iterations: u32,
+        max_iterations: Option<u32>,
+    },
+}
+
+impl ServerOperationMeasurementLength {
+    /// Returns the [ServerOperationMeasurementLength] specified by the application's [AppConfig].
+    fn new(config: &AppConfig) -> ServerOperationMeasurementLength {
+        match config.measurement_duration {
+            Some(duration) => ServerOperationMeasurementLength::Duration {
+                duration,
+                min_iterations: config.min_iterations.unwrap_or(0),
+                max_iterations: config.max_iterations,
+            },
+            None => ServerOperationMeasurementLength::Iterations(config.iterations),
+        }
+    }
+
+    /// Returns the [ServerOperationMeasurementLength] to use for warmups, as specified by the application's
+    /// [AppConfig], or `None` if no warmup should be run.
+    fn warmup(config: &AppConfig) -> Option<ServerOperationMeasurementLength> {
+        match (config.warmup_iterations, config.warmup_duration) {
+            (None, None) => None,
+            (Some(warmup_iterations), None) => Some(ServerOperationMeasurementLength::Iterations(
+                warmup_iterations,
+            )),
+            (warmup_iterations, Some(warmup_duration)) => {
+                Some(ServerOperationMeasurementLength::Duration {
+                    duration: warmup_duration,
+                    min_iterations: warmup_iterations.unwrap_or(0),
+                    max_iterations: None,
+                })
+            }
+        }
+    }
+
+    /// Returns `true` if another iteration should be started, or `false` if the measurement is done.
+    ///
+    /// Parameters:
+    /// * `iterations_started`: the number of iterations that have already been started
+    /// * `elapsed`: how long the measurement has been running iterations for, so far
+    fn should_start_iteration(&self, iterations_started: u32, elapsed: Duration) -> bool {
+        match *self {
+            ServerOperationMeasurementLength::Iterations(iterations) => {
+                iterations_started < iterations
+            }
+            ServerOperationMeasurementLength::Duration {
+                duration,
+                min_iterations,
+                max_iterations,
+            } => {
+                if max_iterations.is_some_and(|max| iterations_started >= max) {
+                    false
+                } else {
+                    iterations_started < min_iterations || elapsed < duration
+                }
+            }
+        }
+    }
+
+    /// Returns the maximum number of iterations that might still be started, if there is such a limit.
+    ///
+    /// Parameters:
+    /// * `iterations_started`: the number of iterations that have already been started
+    fn iterations_remaining(&self, iterations_started: u32) -> Option<u32> {
+        match *self {
+            ServerOperationMeasurementLength::Iterations(iterations) => {
+                Some(iterations.saturating_sub(iterations_started))
+            }
+            ServerOperationMeasurementLength::Duration { max_iterations, .. } => {
+                max_iterations.map(|max| max.saturating_sub(iterations_started))
+            }
+        }
+    }
+
+    /// Returns the number of iterations that the measurement would have run, had it not been halted early.
+    /// Used to count the iterations skipped by measurements that are halted early. For duration-based
+    /// measurements, this is extrapolated from the rate at which iterations were run before the halt (or, if
+    /// none were, is the maximum number of iterations, if set), but is always at least the minimum.
+    ///
+    /// Parameters:
+    /// * `iterations_run`: the number of iterations that were run before the measurement was halted
+    /// * `elapsed`: how long the measurement ran iterations for before it was halted
+    fn iterations_expected(&self, iterations_run: u32, elapsed: Duration) -> u32 {
+        match *self {
+            ServerOperationMeasurementLength::Iterations(iterations) => iterations,
+            ServerOperationMeasurementLength::Duration {
+                duration,
+                min_iterations,
+                max_iterations,
+            } => {
+                let extrapolated = match (duration.num_microseconds(), elapsed.num_microseconds()) {
+                    (Some(duration), Some(elapsed)) if iterations_run > 0 && elapsed > 0 => {
+                        // Round up, so that any partial iteration is counted.
+                        let (duration, elapsed) = (i128::from(duration), i128::from(elapsed));
+                        let extrapolated =
+                            (i128::from(iterations_run) * duration + elapsed - 1) / elapsed;
+                        Some(std::cmp::max(
+                            iterations_run,
+                            u32::try_from(extrapolated).unwrap_or(u32::MAX),
+                        ))
+                    }
+                    _ => max_iterations,
+                };
+                let expected = match (extrapolated, max_iterations) {
+                    (Some(extrapolated), Some(max)) => std::cmp::min(extrapolated, max),
+                    (Some(extrapolated), None) => extrapolated,
+                    (None, _) => 0,
+                };
+                std::cmp::max(expected, min_iterations)
+            }
+        }
+    }
+}
+
+/// Accumulates the outcomes of each iteration run for a [ServerOperationMeasurement], until they're ready to
+/// be summarized into [ServerOperationMetrics].
+struct ServerOperationMeasurementRecorder {
+    /// The name of the operation being measured, which is used when logging failures.
+    operation_name: String,
+
+    /// The [AppConfig.expected_interval] to correct for coordinated omission with, in microseconds (or `0`
+    /// if no correction should be applied).
+    expected_interval_micros: u64,
+
+    /// The raw latencies of each successful iteration, in microseconds.
+    histogram: Histogram<u64>,
+
+    /// The latencies of each successful iteration, in microseconds, as corrected for coordinated omission.
+    histogram_corrected: Histogram<u64>,
+
+    /// The number of iterations that completed successfully.
+    iterations_succeeded: u32,
+
+    /// The number of iterations that failed to produce the expected result.
+    iterations_failed: u32,
+
+    /// The [ServerOperationFailures] breaking down the iterations that failed.
+    failures: ServerOperationFailures,
+
+    /// Breaks the iterations down into a time series, per [AppConfig.interval_window].
+    intervals: ServerOperationIntervalsRecorder,
+
+    /// The latencies of each step of each successful iteration, in microseconds, for multi-step scenario
+    /// operations. Steps are kept in the order that they were first seen.
+    step_histograms: Vec<(String, Histogram<u64>)>,
+
+    /// A separate [ServerOperationMeasurementRecorder] for each operation, for mixed-workload measurements.
+    operations: Vec<ServerOperationMeasurementRecorder>,
+
+    /// The request and response body sizes of each successful iteration.
+    payloads: ServerOperationPayloadRecorder,
+
+    /// The latency phases of each successful iteration.
+    phases: ServerOperationPhasesRecorder,
+
+    /// The [ServerOperationCircuitBreaker] for the measurement, if [AppConfig.circuit_breaker] is enabled.
+    circuit_breaker: Option<ServerOperationCircuitBreaker>,
+
+    /// Why the measurement was halted early, if it was for a reason other than its circuit breaker tripping.
+    halted: Option<ServerOperationSkipReason>,
+}
+
+impl ServerOperationMeasurementRecorder {
+    /// Constructs a new [ServerOperationMeasurementRecorder] for the specified operation.
+    ///
+    /// Parameters:
+    /// * `config`: the application's [AppConfig]
+    /// * `operation_name`: the name of the operation being measured
+    fn new(config: &AppConfig, operation_name: &str) -> ServerOperationMeasurementRecorder {
+        ServerOperationMeasurementRecorder {
+            operation_name: operation_name.to_string(),
+            expected_interval_micros: config
+                .expected_interval
+                .and_then(|expected_interval| expected_interval.num_microseconds())
+                .map(|expected_interval| expected_interval as u64)
+                .unwrap_or(0),
+            histogram: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+            histogram_corrected: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+            iterations_succeeded: 0,
+            iterations_failed: 0,
+            failures: ServerOperationFailures::default(),
+            intervals: ServerOperationIntervalsRecorder::new(
+                config.interval_window,
+                Instant::now(),
+            ),
+            step_histograms: vec![],
+            operations: vec![],
+            payloads: ServerOperationPayloadRecorder::new(),
+            phases: ServerOperationPhasesRecorder::new(),
+            circuit_breaker: config
+                .circuit_breaker
+                .as_ref()
+                .map(ServerOperationCircuitBreaker::new),
+            halted: None,
+        }
+    }
+
+    /// Constructs a new [ServerOperationMeasurementRecorder] for a mixed-workload operation, which will also
+    /// record each of the mix's operations separately.
+    ///
+    /// Parameters:
+    /// * `config`: the application's [AppConfig]
+    /// * `operation_name`: the name of the mixed-workload operation being measured
+    /// * `mixed_operations`: the names of the operations in the mix, in the order that they'll be referred
+    ///   to by [ServerOperationMeasurementRecorder::record_mixed]
+    fn new_mixed(
+        config: &AppConfig,
+        operation_name: &str,
+        mixed_operations: &[&str],
+    ) -> ServerOperationMeasurementRecorder {
+        let mut recorder = ServerOperationMeasurementRecorder::new(config, operation_name);
+        recorder.operations = mixed_operations
+            .iter()
+            .map(|mixed_operation| {
+                // Only the overall mix needs a circuit breaker, as that's what decides when it's halted.
+                let mut operation_recorder =
+                    ServerOperationMeasurementRecorder::new(config, mixed_operation);
+                operation_recorder.circuit_breaker = None;
+                operation_recorder
+            })
+            .collect();
+        recorder
+    }
+
+    /// Records the outcome of a single operation iteration, logging out any failures.
+    ///
+    /// Parameters:
+    /// * `operation_result`: the final [ServerOperationIterationState] of the iteration
+    fn record(
+        &mut self,
+        operation_result: std::result::Result<
+            ServerOperationIterationState<ServerOperationIterationSucceeded>,
+            ServerOperationIterationState<ServerOperationIterationFailed>,
+        >,
+    ) {
+        match operation_result {
+            Ok(operation_success) => {
+                let duration = operation_success.duration();
+                let success = operation_success._inner;
+                self.record_success(
+                    success.completed.completed,
+                    duration,
+                    success.steps,
+                    &success.payload,
+                    &success.phases,
+                );
+            }
+            Err(err) => {
+                warn!("Operation '{}' failed: '{:?}", self.operation_name, err);
+                let failure = err._inner;
+                self.record_failure(failure.completed.completed, failure.kind, || {
+                    ServerOperationFailureExample {
+                        kind: failure.kind,
+                        message: format!("{}", failure.error),
+                        diagnostics: failure.diagnostics,
+                    }
+                });
+            }
+        }
+    }
+
+    /// Records the outcome of a single mixed-workload iteration, both for the overall measurement and for
+    /// the mix's operation that it ran.
+    ///
+    /// Parameters:
+    /// * `mixed_operation`: the index of the mix's operation that the iteration ran, as passed to
+    ///   [ServerOperationMeasurementRecorder::new_mixed]
+    /// * `operation_result`: the final [ServerOperationIterationState] of the iteration
+    fn record_mixed(
+        &mut self,
+        mixed_operation: usize,
+        operation_result: std::result::Result<
+            ServerOperationIterationState<ServerOperationIterationSucceeded>,
+            ServerOperationIterationState<ServerOperationIterationFailed>,
+        >,
+    ) {
+        // The steps are only recorded for the mix's operation, as different operations' steps may share names.
+        let recorder = &mut self.operations[mixed_operation];
+        let mut operation_result = operation_result;
+        match &mut operation_result {
+            Ok(operation_success) => recorder.record_success(
+                operation_success._inner.completed.completed,
+                operation_success.duration(),
+                std::mem::take(&mut operation_success._inner.steps),
+                &operation_success._inner.payload,
+                &operation_success._inner.phases,
+            ),
+            Err(err) => {
+                let failure = &err._inner;
+                recorder.record_failure(failure.completed.completed, failure.kind, || {
+                    ServerOperationFailureExample {
+                        kind: failure.kind,
+                        message: format!("{}", failure.error),
+                        diagnostics: failure.diagnostics.clone(),
+                    }
+                });
+            }
+        }
+
+        self.record(operation_result);
+    }
+
+    /// Records a successful operation iteration.
+    ///
+    /// Parameters:
+    /// * `completed`: when the iteration completed, in monotonic time
+    /// * `duration`: how long the iteration took
+    /// * `steps`: the name and duration of each of the iteration's steps, for multi-step scenario operations
+    /// * `payload`: the [ServerOperationPayload] of the iteration
+    /// * `phases`: the [ServerOperationPhases] of the iteration
+    fn record_success(
+        &mut self,
+        completed: Instant,
+        duration: std::time::Duration,
+        steps: Vec<(String, std::time::Duration)>,
+        payload: &ServerOperationPayload,
+        phases: &ServerOperationPhases,
+    ) {
+        let duration_micros = duration.as_micros() as u64;
+        self.histogram
+            .record(duration_micros)
+            .expect("Histogram recording failed.");
+        self.histogram_corrected
+            .record_correct(duration_micros, self.expected_interval_micros)
+            .expect("Histogram recording failed.");
+        self.iterations_succeeded += 1;
+        self.intervals.record_success(completed, duration_micros);
+        for (step, step_duration) in steps {
+            self.record_step(step, step_duration);
+        }
+        self.payloads.record(payload);
+        self.phases.record(phases);
+        if let Some(circuit_breaker) = &mut self.circuit_breaker {
+            circuit_breaker.record(&self.operation_name, false);
+        }
+    }
+
+    /// Records a failed operation iteration.
+    ///
+    /// Parameters:
+    /// * `completed`: when the iteration completed, in monotonic time
+    /// * `kind`: the [ServerOperationFailureKind] that the failure falls into
+    /// * `example_fn`: builds a [ServerOperationFailureExample] for the failure, if one is still needed
+    fn record_failure<F>(
+        &mut self,
+        completed: Instant,
+        kind: ServerOperationFailureKind,
+        example_fn: F,
+    ) where
+        F: FnOnce() -> ServerOperationFailureExample,
+    {
+        self.iterations_failed += 1;
+        self.intervals.record_failure(completed);
+        self.failures.record(kind, example_fn);
+        if let Some(circuit_breaker) = &mut self.circuit_breaker {
+            circuit_breaker.record(&self.operation_name, true);
+        }
+    }
+
+    /// Pauses the clock that the measurement's intervals are windowed by (see
+    /// [ServerOperationIntervalsRecorder::pause]), e.g. while the server is expunged between iterations.
+    fn pause_intervals(&mut self) {
+        let now = Instant::now();
+        self.intervals.pause(now);
+        for recorder in &mut self.operations {
+            recorder.intervals.pause(now);
         }
     }
+
+    /// Resumes the clock that the measurement's intervals are windowed by, after
+    /// [ServerOperationMeasurementRecorder::pause_intervals].
+    fn resume_intervals(&mut self) {
+        let now = Instant::now();
+        self.intervals.resume(now);
+        for recorder in &mut self.operations {
+            recorder.intervals.resume(now);
+        }
+    }
+
+    /// Returns the [CircuitBreakerTrip] flag for this measurement, which the code starting its iterations
+    /// should check before each one. It will never be set if [AppConfig.circuit_breaker] isn't enabled.
+    fn circuit_breaker(&self) -> CircuitBreakerTrip {
+        self.circuit_breaker
+            .as_ref()
+            .map(|circuit_breaker| circuit_breaker.trip())
+            .unwrap_or_default()
+    }
+
+    /// Records that the measurement was halted early, so that none of its remaining iterations will be run.
+    ///
+    /// Parameters:
+    /// * `reason`: the [ServerOperationSkipReason] why the measurement was halted
+    fn halt(&mut self, reason: ServerOperationSkipReason) {
+        self.halted = Some(reason);
+    }
+
+    /// Returns the number of iterations that were skipped and the [ServerOperationSkipReason] why, if the
+    /// measurement was halted or its circuit breaker tripped, or `(0, None)` if neither happened.
+    ///
+    /// Parameters:
+    /// * `length`: the [ServerOperationMeasurementLength] that the measurement was run with
+    /// * `execution_duration`: how long the measurement ran for
+    fn skipped(
+        &self,
+        length: ServerOperationMeasurementLength,
+        execution_duration: Duration,
+    ) -> (u32, Option<ServerOperationSkipReason>) {
+        let skip_reason = self.halted.clone().or_else(|| {
+            self.circuit_breaker
+                .as_ref()
+                .and_then(|circuit_breaker| circuit_breaker.tripped_failure_rate())
+                .map(ServerOperationSkipReason::CircuitBreakerTripped)
+        });
+        match skip_reason {
+            Some(skip_reason) => (
+                length
+                    .iterations_expected(self.iterations(), execution_duration)
+                    .saturating_sub(self.iterations()),
+                Some(skip_reason),
+            ),
+            None => (0, None),
+        }
+    }
+
+    /// Returns the number of iterations that have been recorded, so far.
+    fn iterations(&self) -> u32 {
+        self.iterations_succeeded + self.iterations_failed
+    }
+
+    /// Returns the number of iterations that have failed, so far.
+    fn iterations_failed(&self) -> u32 {
+        self.iterations_failed
+    }
+
+    /// Records the latency of a single step of a successful multi-step scenario operation iteration.
+    ///
+    /// Parameters:
+    /// * `step`: the name of the step
+    /// * `step_duration`: how long the step took
+    fn record_step(&mut self, step: String, step_duration: std::time::Duration) {
+        let index = match self.step_histograms.iter().position(|(s, _)| *s == step) {
+            Some(index) => index,
+            None => {
+                let histogram = Histogram::<u64>::new(3).expect("Unable to construct histogram.");
+                self.step_histograms.push((step, histogram));
+                self.step_histograms.len() - 1
+            }
+        };
+        self.step_histograms[index]
+            .1
+            .record(step_duration.as_micros() as u64)
+            .expect("Histogram recording failed.");
+    }
+
+    /// Returns the [ServerOperationFailures] recorded so far, leaving an empty one in its place.
+    fn take_failures(&mut self) -> ServerOperationFailures {
+        std::mem::take(&mut self.failures)
+    }
+
+    /// Returns the [ServerOperationStepMetrics] for the steps recorded so far (if any), clearing them out.
+    fn take_steps(&mut self) -> Vec<ServerOperationStepMetrics> {
+        std::mem::take(&mut self.step_histograms)
+            .into_iter()
+            .map(|(step, histogram)| ServerOperationStepMetrics::new(step, histogram))
+            .collect()
+    }
+
+    /// Returns the [ServerOperationMixMetrics] for each of the mix's operations (if any), clearing them out.
+    ///
+    /// Parameters:
+    /// * `duration`: how long the measurement ran for
+    fn take_operations(&mut self, duration: Duration) -> Vec<ServerOperationMixMetrics> {
+        std::mem::take(&mut self.operations)
+            .into_iter()
+            .map(|mut recorder| ServerOperationMixMetrics {
+                operation: recorder.operation_name.clone(),
+                iterations: recorder.iterations(),
+                iterations_failed: recorder.iterations_failed(),
+                failures: recorder.take_failures(),
+                steps: recorder.take_steps(),
+                metrics: recorder.into_metrics(duration),
+            })
+            .collect()
+    }
+
+    /// Summarizes the recorded iterations as a [ServerOperationWarmup].
+    ///
+    /// Parameters:
+    /// * `started`: when the warmup started, in wall-clock time
+    /// * `completed`: when the warmup completed, in wall-clock time
+    /// * `execution_duration`: how long the warmup ran for
+    fn into_warmup(
+        self,
+        started: DateTime<Utc>,
+        completed: DateTime<Utc>,
+        execution_duration: Duration,
+    ) -> ServerOperationWarmup {
+        ServerOperationWarmup {
+            started,
+            completed,
+            execution_duration,
+            iterations: self.iterations(),
+            iterations_failed: self.iterations_failed,
+        }
+    }
+
+    /// Summarizes the recorded iterations into [ServerOperationMetrics].
+    ///
+    /// Parameters:
+    /// * `duration`: how long the measurement ran for
+    fn into_metrics(self, duration: Duration) -> ServerOperationMetrics {
+        ServerOperationMetrics::new(
+            duration,
+            self.iterations_succeeded,
+            self.histogram,
+            self.histogram_corrected,
+            self.intervals.into_intervals(duration),
+            self.payloads.into_metrics(),
+            self.phases.into_metrics(),
+        )
+    }
 }
 
 /// A state machine for tracking the progress and results of a single iteration for a server
@@ -307,8 +1390,8 @@ struct ServerOperationIterationState<S> {
 /// This [ServerOperationIterationState] state node models an operation that is starting.
 #[derive(Clone, Debug)]
 struct ServerOperationIterationStarting {
-    /// When this operation iteration started, in wall-clock time.
-    started: DateTime<Utc>,
+    /// When this operation iteration started, in monotonic time.
+    started: Instant,
 }
 
 /// This [ServerOperationIterationState] state node models an operation that has completed, but
@@ -322,8 +1405,8 @@ struct ServerOperationIterationCompleted {
     /// The state from the operation's start.
     start: ServerOperationIterationStarting,
 
-    /// When this operation iteration completed, in wall-clock time.
-    completed: DateTime<Utc>,
+    /// When this operation iteration completed, in monotonic time.
+    completed: Instant,
 }
 
 /// This [ServerOperationIterationState] state node models an operation that has completed
@@ -332,6 +1415,15 @@ struct ServerOperationIterationCompleted {
 struct ServerOperationIterationSucceeded {
     /// The state from the operation's completion.
     completed: ServerOperationIterationCompleted,
+
+    /// The name and duration of each step of the operation, for multi-step scenario operations.
+    steps: Vec<(String, std::time::Duration)>,
+
+    /// The sizes of the operation's request and response bodies.
+    payload: ServerOperationPayload,
+
+    /// How long each of the operation's latency phases took.
+    phases: ServerOperationPhases,
 }
 
 /// This [ServerOperationIterationState] state node models an operation that failed to complete
@@ -341,8 +1433,14 @@ struct ServerOperationIterationFailed {
     /// The state from the operation's completion.
     completed: ServerOperationIterationCompleted,
 
+    /// The [ServerOperationFailureKind] that the failure falls into.
+    kind: ServerOperationFailureKind,
+
     /// The [anyhow::Error] detailing how/why the operation iteraion failed.
     error: eyre::Error,
+
+    /// The `diagnostics` from any FHIR `OperationOutcome` that the server responded with.
+    diagnostics: Vec<String>,
 }
 
 impl ServerOperationIterationState<ServerOperationIterationStarting> {
@@ -351,18 +1449,45 @@ impl ServerOperationIterationState<ServerOperationIterationStarting> {
     pub fn new() -> ServerOperationIterationState<ServerOperationIterationStarting> {
         ServerOperationIterationState {
             _inner: ServerOperationIterationStarting {
-                started: Utc::now(),
+                started: Instant::now(),
             },
         }
     }
 
+    /// Creates a new [ServerOperationIterationState] state machine instance for an operation iteration
+    /// that was scheduled to start at the specified time, so that any delay in actually starting it counts
+    /// towards its latency (rather than being hidden by coordinated omission).
+    ///
+    /// Parameters:
+    /// * `scheduled`: when the operation iteration was scheduled to start, in monotonic time
+    pub fn scheduled(
+        scheduled: Instant,
+    ) -> ServerOperationIterationState<ServerOperationIterationStarting> {
+        ServerOperationIterationState {
+            _inner: ServerOperationIterationStarting { started: scheduled },
+        }
+    }
+
     /// Transitions this [ServerOperationIterationState] state machine instance after the operation
     /// iteration completes, but before its success or failure has been determined.
     pub fn completed(self) -> ServerOperationIterationState<ServerOperationIterationCompleted> {
+        self.completed_at(Instant::now())
+    }
+
+    /// Transitions this [ServerOperationIterationState] state machine instance after the operation
+    /// iteration completed at the specified time, e.g. when its final response was received (rather than
+    /// after it was decoded), but before its success or failure has been determined.
+    ///
+    /// Parameters:
+    /// * `completed`: when the operation iteration completed, in monotonic time
+    pub fn completed_at(
+        self,
+        completed: Instant,
+    ) -> ServerOperationIterationState<ServerOperationIterationCompleted> {
         ServerOperationIterationState {
             _inner: ServerOperationIterationCompleted {
                 start: self._inner,
-                completed: Utc::now(),
+                completed,
             },
         }
     }
@@ -372,9 +1497,24 @@ impl ServerOperationIterationState<ServerOperationIterationCompleted> {
     /// Transitions this [ServerOperationIterationState] state machine instance after the operation
     /// iteration has been deemed a success.
     pub fn succeeded(self) -> ServerOperationIterationState<ServerOperationIterationSucceeded> {
+        self.succeeded_with_steps(vec![])
+    }
+
+    /// Transitions this [ServerOperationIterationState] state machine instance after a multi-step scenario
+    /// operation iteration has been deemed a success.
+    ///
+    /// Parameters:
+    /// * `steps`: the name and duration of each step of the operation
+    pub fn succeeded_with_steps(
+        self,
+        steps: Vec<(String, std::time::Duration)>,
+    ) -> ServerOperationIterationState<ServerOperationIterationSucceeded> {
         ServerOperationIterationState {
             _inner: ServerOperationIterationSucceeded {
                 completed: self._inner,
+                steps,
+                payload: ServerOperationPayload::default(),
+                phases: ServerOperationPhases::default(),
             },
         }
     }
@@ -383,45 +1523,453 @@ impl ServerOperationIterationState<ServerOperationIterationCompleted> {
     /// iteration has been deemed a failure.
     ///
     /// Parameters:
+    /// * `kind`: the [ServerOperationFailureKind] that the failure falls into
     /// * `error`: the [anyhow::Error] detailing how/why the operation iteraion failed
     pub fn failed(
         self,
+        kind: ServerOperationFailureKind,
         error: eyre::Error,
     ) -> ServerOperationIterationState<ServerOperationIterationFailed> {
         ServerOperationIterationState {
             _inner: ServerOperationIterationFailed {
                 completed: self._inner,
+                kind,
                 error,
+                diagnostics: vec![],
             },
         }
     }
+
+    /// Transitions this [ServerOperationIterationState] state machine instance after the server responded
+    /// with an unsuccessful HTTP status, keeping any `OperationOutcome` diagnostics from the response.
+    ///
+    /// Parameters:
+    /// * `status`: the unsuccessful [http::StatusCode] that the server responded with
+    /// * `response_body`: the body of the server's response
+    /// * `error`: the [anyhow::Error] detailing how/why the operation iteraion failed
+    pub fn failed_response(
+        self,
+        status: http::StatusCode,
+        response_body: &str,
+        error: eyre::Error,
+    ) -> ServerOperationIterationState<ServerOperationIterationFailed> {
+        let mut state = self.failed(ServerOperationFailureKind::from_status(status), error);
+        state._inner.diagnostics = parse_operation_outcome_diagnostics(response_body);
+        state
+    }
+}
+
+/// Parses out the `diagnostics` of each issue in a FHIR `OperationOutcome` resource.
+///
+/// Parameters:
+/// * `response_body`: the (possible) JSON `OperationOutcome` to parse
+///
+/// Returns the `diagnostics` found, which will be empty if the body was not an `OperationOutcome`.
+fn parse_operation_outcome_diagnostics(response_body: &str) -> Vec<String> {
+    let outcome: serde_json::Value = match serde_json::from_str(response_body) {
+        Ok(outcome) => outcome,
+        Err(_) => return vec![],
+    };
+    if outcome["resourceType"] != "OperationOutcome" {
+        return vec![];
+    }
+
+    outcome["issue"]
+        .as_array()
+        .map(|issues| {
+            issues
+                .iter()
+                .filter_map(|issue| issue["diagnostics"].as_str())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 impl ServerOperationIterationState<ServerOperationIterationSucceeded> {
-    /// Returns the [Duration] that the operation iteration ran for.
-    pub fn duration(&self) -> Duration {
+    /// Records the sizes of the successful operation iteration's request and response bodies.
+    ///
+    /// Parameters:
+    /// * `payload`: the [ServerOperationPayload] of the operation iteration
+    pub fn with_payload(
+        mut self,
+        payload: ServerOperationPayload,
+    ) -> ServerOperationIterationState<ServerOperationIterationSucceeded> {
+        self._inner.payload = payload;
+        self
+    }
+
+    /// Records how long each of the successful operation iteration's latency phases took.
+    ///
+    /// Parameters:
+    /// * `phases`: the [ServerOperationPhases] of the operation iteration
+    pub fn with_phases(
+        mut self,
+        phases: ServerOperationPhases,
+    ) -> ServerOperationIterationState<ServerOperationIterationSucceeded> {
+        self._inner.phases = phases;
+        self
+    }
+
+    /// Returns the [std::time::Duration] that the operation iteration ran for.
+    pub fn duration(&self) -> std::time::Duration {
         self._inner.completed.completed - self._inner.completed.start.started
     }
 }
 
+/// Returns how much monotonic time has elapsed since the specified [Instant], as a [Duration].
+fn elapsed_since(started: Instant) -> Duration {
+    Duration::from_std(started.elapsed()).expect("Unable to convert Duration.")
+}
+
+/// Verifies and benchmarks an operation for the specified number of concurrent users: warms the server up
+/// first, if configured to, and then runs the measurement itself.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `new_recorder`: constructs the [ServerOperationMeasurementRecorder] for the warmup and for the
+///   measurement
+/// * `run_operations`: runs the operation's iterations until the specified
+///   [ServerOperationMeasurementLength] is reached, recording each in the specified
+///   [ServerOperationMeasurementRecorder], and then returns that recorder along with any
+///   operation-specific results
+///
+/// Returns a [ServerOperationMeasurement] with the results, along with the measurement's (not the warmup's)
+/// operation-specific results.
+async fn measure_operation<T, R, F, Fut>(
+    app_state: &AppState,
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    new_recorder: R,
+    mut run_operations: F,
+) -> (ServerOperationMeasurement, T)
+where
+    R: Fn() -> ServerOperationMeasurementRecorder,
+    F: FnMut(ServerOperationMeasurementLength, ServerOperationMeasurementRecorder) -> Fut,
+    Fut: Future<Output = (ServerOperationMeasurementRecorder, T)>,
+{
+    // Warm the server up first, if configured to.
+    let warmup = match ServerOperationMeasurementLength::warmup(&app_state.config) {
+        Some(warmup_length) => {
+            let warmup_started = Utc::now();
+            let warmup_started_instant = Instant::now();
+            let (warmup_recorder, _) = run_operations(warmup_length, new_recorder())
+                .instrument(info_span!("warmup"))
+                .await;
+            Some(warmup_recorder.into_warmup(
+                warmup_started,
+                Utc::now(),
+                elapsed_since(warmup_started_instant),
+            ))
+        }
+        None => None,
+    };
+
+    let length = ServerOperationMeasurementLength::new(&app_state.config);
+    let started = Utc::now();
+    let started_instant = Instant::now();
+    let (mut recorder, results) = run_operations(length, new_recorder()).await;
+    let execution_duration = elapsed_since(started_instant);
+    let completed = Utc::now();
+    let (iterations_skipped, skip_reason) = recorder.skipped(length, execution_duration);
+
+    let measurement = ServerOperationMeasurement {
+        concurrent_users,
+        compression,
+        trial: 0,
+        started,
+        completed,
+        execution_duration,
+        iterations: recorder.iterations(),
+        iterations_failed: recorder.iterations_failed(),
+        iterations_skipped,
+        skip_reason,
+        failures: recorder.take_failures(),
+        warmup,
+        steps: recorder.take_steps(),
+        operations: recorder.take_operations(execution_duration),
+        contention: None,
+        metrics: recorder.into_metrics(execution_duration),
+    };
+    (measurement, results)
+}
+
+/// Runs a single operation iteration, failing it if it doesn't complete within the
+/// [AppConfig.operation_timeout].
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `operation_state`: the initial state machine for this operation iteration, which `operation` was
+///   started from
+/// * `operation`: the operation iteration to run
+///
+/// Returns the final [ServerOperationIterationState] containing information about the operation's
+/// success or failure.
+async fn run_operation_with_timeout<Fut>(
+    app_state: &AppState,
+    operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
+    operation: Fut,
+) -> std::result::Result<
+    ServerOperationIterationState<ServerOperationIterationSucceeded>,
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+>
+where
+    Fut: Future<
+        Output = std::result::Result<
+            ServerOperationIterationState<ServerOperationIterationSucceeded>,
+            ServerOperationIterationState<ServerOperationIterationFailed>,
+        >,
+    >,
+{
+    let operation = tokio::time::timeout(
+        app_state
+            .config
+            .operation_timeout
+            .to_std()
+            .expect("unable to convert Duration"),
+        operation,
+    );
+
+    // Having the timeout gives us a wrapped Result<Result ...>>. Un-nest them.
+    let result = operation.await;
+    let result = result.map_err(|err| {
+        operation_state.completed().failed(
+            ServerOperationFailureKind::Timeout,
+            eyre!("Operation timed out: '{}'", err),
+        )
+    });
+    result.and_then(|wrapped_result| wrapped_result)
+}
+
+/// Runs the measurements for an operation: once for each of the [AppConfig.concurrency_levels] and
+/// [AppConfig.compression] encodings in each of the [AppConfig.trials], or as a saturation search if
+/// [AppConfig.saturation] is enabled (which only uses the first of the [AppConfig.compression] encodings).
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `operation_name`: the name of the operation being benchmarked
+/// * `measure`: runs and returns a [ServerOperationMeasurement] for the operation at the specified number of
+///   concurrent users, requesting the specified [ContentEncoding] (if any)
+///
+/// Returns the [ServerOperationLog] for the operation.
+async fn run_measurements<F, Fut>(
+    app_state: &AppState,
+    operation_name: &str,
+    measure: F,
+) -> ServerOperationLog
+where
+    F: Fn(u32, Option<ContentEncoding>) -> Fut,
+    Fut: Future<Output = ServerOperationMeasurement>,
+{
+    let mut server_op_log = ServerOperationLog::new(operation_name.into());
+    let compressions: Vec<Option<ContentEncoding>> = if app_state.config.compression.is_empty() {
+        vec![None]
+    } else {
+        app_state
+            .config
+            .compression
+            .iter()
+            .copied()
+            .map(Some)
+            .collect()
+    };
+
+    match &app_state.config.saturation {
+        None => {
+            for trial in 0..app_state.config.trials {
+                for compression in &compressions {
+                    for concurrent_users in app_state.config.concurrency_levels.clone() {
+                        let mut measurement = measure(concurrent_users, *compression)
+                            .instrument(info_span!("trial", trial, ?compression))
+                            .await;
+
+                        // The operations don't know which trial they're part of, so that's filled in here.
+                        measurement.trial = trial;
+                        server_op_log.measurements.push(measurement);
+                    }
+                }
+            }
+            server_op_log.summaries = trials::summarize_trials(&server_op_log.measurements);
+        }
+        Some(saturation_config) => {
+            let compression = compressions[0];
+            let saturation = saturation::search_for_saturation(
+                saturation_config,
+                &|concurrent_users| measure(concurrent_users, compression),
+                &mut server_op_log.measurements,
+            )
+            .await;
+            server_op_log.saturation = Some(saturation);
+        }
+    }
+
+    server_op_log
+}
+
+/// Returns the names of all of the built-in operations (whether or not they're enabled), which the
+/// scenarios loaded from the `scenarios/` directory may not reuse.
+pub(crate) fn built_in_operation_names() -> Vec<&'static str> {
+    vec![
+        metadata::SERVER_OP_NAME_METADATA,
+        post_org::SERVER_OP_NAME_POST_ORG,
+        index_lag::SERVER_OP_NAME_INDEX_LAG,
+        contention::SERVER_OP_NAME_UPDATE_CONTENTION,
+        scenario::SERVER_OP_NAME_SCENARIO_OPEN_CHART,
+        mix::SERVER_OP_NAME_MIXED_WORKLOAD,
+        replay::SERVER_OP_NAME_REPLAY,
+    ]
+}
+
+/// Verifies that the [AppConfig.scenario_mix] can be run, if one was configured.
+///
+/// Parameters:
+/// * `scenario_mix`: the [AppConfig.scenario_mix] to check
+/// * `scenarios`: the [Scenario]s loaded from the `scenarios/` directory, which are available in addition to
+///   the built-in ones
+///
+/// Returns an error if any of the mix's scenarios couldn't be found, or if its weights can't be sampled from.
+pub(crate) fn check_scenario_mix(
+    scenario_mix: &[ScenarioMixEntry],
+    scenarios: &[Scenario],
+) -> Result<()> {
+    if scenario_mix.is_empty() {
+        return Ok(());
+    }
+    mix::resolve_mix(scenario_mix, scenarios).map(|_| ())
+}
+
 /// Runs the benchmark framework to test the supported operations for the specified FHIR server.
 ///
 /// Parameters:
 /// * `app_state`: the application's [AppState]
 /// * `server_handle`: the [ServerHandle] for the server implementation to be tested
-///
-/// Returns the [ServerOperationLog]s for each operation that was tested.
-#[allow(clippy::vec_init_then_push)]
+/// * `results`: the [ServerOperationLog] for each operation will be added to this as soon as it's been
+///   tested, so that the operations completed so far are kept, even if the run is interrupted. Any
+///   operations already in it (i.e. from a resumed checkpoint) will be skipped.
+/// * `checkpoint`: called with all of the `results` after each operation has been tested
 pub async fn run_operations(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
-) -> Result<Vec<ServerOperationLog>> {
-    let mut results = vec![];
+    results: &mut Vec<ServerOperationLog>,
+    checkpoint: &dyn Fn(&[ServerOperationLog]),
+) -> Result<()> {
+    let op_state = app_state.for_operation(metadata::SERVER_OP_NAME_METADATA);
+    run_operation(
+        results,
+        checkpoint,
+        metadata::SERVER_OP_NAME_METADATA,
+        &op_state,
+        metadata::benchmark_operation_metadata(&op_state, server_handle),
+    )
+    .await;
+    let op_state = app_state.for_operation(post_org::SERVER_OP_NAME_POST_ORG);
+    run_operation(
+        results,
+        checkpoint,
+        post_org::SERVER_OP_NAME_POST_ORG,
+        &op_state,
+        post_org::benchmark_post_org(&op_state, server_handle),
+    )
+    .await;
+    let op_state = app_state.for_operation(index_lag::SERVER_OP_NAME_INDEX_LAG);
+    run_operation(
+        results,
+        checkpoint,
+        index_lag::SERVER_OP_NAME_INDEX_LAG,
+        &op_state,
+        index_lag::benchmark_index_lag(&op_state, server_handle),
+    )
+    .await;
+    let op_state = app_state.for_operation(contention::SERVER_OP_NAME_UPDATE_CONTENTION);
+    run_operation(
+        results,
+        checkpoint,
+        contention::SERVER_OP_NAME_UPDATE_CONTENTION,
+        &op_state,
+        contention::benchmark_update_contention(&op_state, server_handle),
+    )
+    .await;
+    let op_state = app_state.for_operation(scenario::SERVER_OP_NAME_SCENARIO_OPEN_CHART);
+    run_operation(
+        results,
+        checkpoint,
+        scenario::SERVER_OP_NAME_SCENARIO_OPEN_CHART,
+        &op_state,
+        scenario::benchmark_open_chart(&op_state, server_handle),
+    )
+    .await;
+    for scenario in &app_state.scenarios {
+        let op_state = app_state.for_operation(&scenario.name);
+        run_operation(
+            results,
+            checkpoint,
+            &scenario.name,
+            &op_state,
+            scenario::benchmark_scenario(&op_state, server_handle, scenario),
+        )
+        .await;
+    }
+    if !app_state.config.scenario_mix.is_empty() {
+        let op_state = app_state.for_operation(mix::SERVER_OP_NAME_MIXED_WORKLOAD);
+        run_operation(
+            results,
+            checkpoint,
+            mix::SERVER_OP_NAME_MIXED_WORKLOAD,
+            &op_state,
+            mix::benchmark_mixed_workload(&op_state, server_handle),
+        )
+        .await;
+    }
+    if app_state.config.replay.is_some() {
+        let op_state = app_state.for_operation(replay::SERVER_OP_NAME_REPLAY);
+        run_operation(
+            results,
+            checkpoint,
+            replay::SERVER_OP_NAME_REPLAY,
+            &op_state,
+            replay::benchmark_replay(&op_state, server_handle),
+        )
+        .await;
+    }
 
-    results.push(metadata::benchmark_operation_metadata(app_state, server_handle).await);
-    results.push(post_org::benchmark_post_org(app_state, server_handle).await);
+    Ok(())
+}
 
-    Ok(results)
+/// Runs a single operation's benchmark, unless it's already in the results, adding its [ServerOperationLog]
+/// (along with the effective [ServerOperationSettings] it ran with) to them and then checkpointing them.
+///
+/// Parameters:
+/// * `results`: the [ServerOperationLog]s for each operation that's been tested so far
+/// * `checkpoint`: called with all of the `results` after the operation has been tested
+/// * `operation_name`: the name of the operation
+/// * `op_state`: the [AppState] for the operation, per [AppState::for_operation]
+/// * `benchmark`: benchmarks the operation, which will only be run if it isn't already in the results
+async fn run_operation<Fut>(
+    results: &mut Vec<ServerOperationLog>,
+    checkpoint: &dyn Fn(&[ServerOperationLog]),
+    operation_name: &str,
+    op_state: &AppState,
+    benchmark: Fut,
+) where
+    Fut: Future<Output = ServerOperationLog>,
+{
+    if results
+        .iter()
+        .any(|result| result.operation.0 == operation_name)
+    {
+        info!(
+            operation = operation_name,
+            "Skipping operation, which was already completed in the checkpoint."
+        );
+        return;
+    }
+
+    let mut server_op_log = benchmark.await;
+    server_op_log.settings = Some(ServerOperationSettings::new(&op_state.config));
+    results.push(server_op_log);
+    checkpoint(results);
 }
 
 /// Unit tests for the test case structures, etc.
@@ -430,12 +1978,25 @@ pub async fn run_operations(
 /// as otherwise serde serialization does not preserve field order.
 #[cfg(test)]
 mod tests {
+    use crate::test_framework::workload_plan::WorkloadPlan;
     use crate::test_framework::{
-        FrameworkOperationLog, FrameworkOperationResult, FrameworkResults, ServerOperationLog,
-        ServerOperationMeasurement, ServerOperationMetrics, ServerResult,
+        ConfidenceInterval, FrameworkOperationLog, FrameworkOperationResult, FrameworkResults,
+        ServerOperationFailureExample, ServerOperationFailureKind, ServerOperationFailures,
+        ServerOperationInterval, ServerOperationIntervals, ServerOperationIterationState,
+        ServerOperationLog, ServerOperationMeasurement, ServerOperationMeasurementLength,
+        ServerOperationMeasurementRecorder, ServerOperationMetrics, ServerOperationPayloadMetrics,
+        ServerOperationPayloadSizes, ServerOperationSaturation, ServerOperationSaturationStop,
+        ServerOperationSettings, ServerOperationSkipReason, ServerOperationSummary,
+        ServerOperationWarmup, ServerResult,
     };
     use crate::util::serde_duration_iso8601;
-    use crate::{config::AppConfig, test_framework::FrameworkMetadata};
+    use crate::{
+        config::{
+            AppConfig, CircuitBreakerConfig, OperationOverrides, SaturationConfig, ThinkTime,
+            ThinkTimeDistribution, Transport,
+        },
+        test_framework::FrameworkMetadata,
+    };
     use chrono::prelude::*;
     use chrono::Duration;
     use hdrhistogram::Histogram;
@@ -472,25 +2033,104 @@ mod tests {
         let expected = json!({
             "throughput_per_second": 42.0,
             "latency_millis_mean": 1.0,
-            "latency_millis_p50": 1,
-            "latency_millis_p90": 1,
-            "latency_millis_p99": 1,
-            "latency_millis_p999": 1,
-            "latency_millis_p100": 1,
+            "latency_millis_p50": 1.0,
+            "latency_millis_p90": 1.0,
+            "latency_millis_p99": 1.0,
+            "latency_millis_p999": 1.0,
+            "latency_millis_p100": 1.0,
+            "latency_corrected_millis_mean": 1.0,
+            "latency_corrected_millis_p50": 1.0,
+            "latency_corrected_millis_p90": 1.0,
+            "latency_corrected_millis_p99": 1.0,
+            "latency_corrected_millis_p999": 1.0,
+            "latency_corrected_millis_p100": 1.0,
             "latency_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
             "latency_histogram_hgrm_gzip": "foo",
+            "latency_corrected_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+            "latency_corrected_histogram_hgrm_gzip": "foo",
+            "intervals": {
+                "window": 1000,
+                "intervals": [{
+                    "offset": 0,
+                    "throughput_per_second": 42.0,
+                    "iterations_failed": 0,
+                    "latency_millis_p50": 1.0,
+                    "latency_millis_p90": 1.0,
+                    "latency_millis_p99": 1.0,
+                    "latency_millis_p100": 1.0,
+                }],
+                "latency_interval_log": "foo",
+            },
+            "payload": {
+                "request_bytes": {
+                    "bytes_mean": 0.0,
+                    "bytes_p50": 0,
+                    "bytes_p90": 0,
+                    "bytes_p99": 0,
+                    "bytes_p100": 0,
+                    "bytes_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                },
+                "request_compressed_bytes": null,
+                "response_bytes": {
+                    "bytes_mean": 0.0,
+                    "bytes_p50": 0,
+                    "bytes_p90": 0,
+                    "bytes_p99": 0,
+                    "bytes_p100": 0,
+                    "bytes_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                },
+                "response_compressed_bytes": null,
+                "responses": 0,
+                "responses_encoding_honoured": 0,
+            },
+            "phases": [],
         });
         let expected = serde_json::to_string(&expected).unwrap();
         let actual = ServerOperationMetrics {
             throughput_per_second: 42.0,
             latency_millis_mean: 1.0,
-            latency_millis_p50: 1,
-            latency_millis_p90: 1,
-            latency_millis_p99: 1,
-            latency_millis_p999: 1,
-            latency_millis_p100: 1,
+            latency_millis_p50: 1.0,
+            latency_millis_p90: 1.0,
+            latency_millis_p99: 1.0,
+            latency_millis_p999: 1.0,
+            latency_millis_p100: 1.0,
+            latency_corrected_millis_mean: 1.0,
+            latency_corrected_millis_p50: 1.0,
+            latency_corrected_millis_p90: 1.0,
+            latency_corrected_millis_p99: 1.0,
+            latency_corrected_millis_p999: 1.0,
+            latency_corrected_millis_p100: 1.0,
             latency_histogram: Histogram::<u64>::new(3).expect("Error creating histogram."),
             latency_histogram_hgrm_gzip: "foo".into(),
+            latency_corrected_histogram: Histogram::<u64>::new(3)
+                .expect("Error creating histogram."),
+            latency_corrected_histogram_hgrm_gzip: "foo".into(),
+            intervals: ServerOperationIntervals {
+                window: Duration::seconds(1),
+                intervals: vec![ServerOperationInterval {
+                    offset: Duration::zero(),
+                    throughput_per_second: 42.0,
+                    iterations_failed: 0,
+                    latency_millis_p50: 1.0,
+                    latency_millis_p90: 1.0,
+                    latency_millis_p99: 1.0,
+                    latency_millis_p100: 1.0,
+                }],
+                latency_interval_log: "foo".into(),
+            },
+            payload: ServerOperationPayloadMetrics {
+                request_bytes: ServerOperationPayloadSizes::new(
+                    Histogram::<u64>::new(3).expect("Error creating histogram."),
+                ),
+                request_compressed_bytes: None,
+                response_bytes: ServerOperationPayloadSizes::new(
+                    Histogram::<u64>::new(3).expect("Error creating histogram."),
+                ),
+                response_compressed_bytes: None,
+                responses: 0,
+                responses_encoding_honoured: 0,
+            },
+            phases: vec![],
         };
         let actual = serde_json::to_string(&actual).unwrap();
         assert_eq!(expected, actual);
@@ -502,14 +2142,20 @@ mod tests {
     async fn serialize_server_operation_log() {
         let expected = json!({
             "operation": "Operation A",
+            "settings": null,
             "errors": [],
             "measurements": [],
+            "summaries": [],
+            "saturation": null,
         });
         let expected = serde_json::to_string(&expected).unwrap();
         let actual = ServerOperationLog {
             operation: SERVER_OP_NAME_FAKE.into(),
+            settings: None,
             errors: vec![],
             measurements: vec![],
+            summaries: vec![],
+            saturation: None,
         };
         let actual = serde_json::to_string(&actual).unwrap();
         assert_eq!(expected, actual);
@@ -521,47 +2167,330 @@ mod tests {
     async fn serialize_server_operation_measurement() {
         let expected = json!({
             "concurrent_users": 10,
+            "compression": null,
+            "trial": 0,
             "started": "2020-01-01T15:00:00Z",
             "completed": "2020-01-01T16:00:00Z",
             "execution_duration": "PT1.234S",
+            "iterations": 2,
             "iterations_failed": 1,
             "iterations_skipped": 0,
+            "skip_reason": null,
+            "failures": {
+                "timeout": 0,
+                "connection_error": 0,
+                "http_client_error": 1,
+                "http_server_error": 0,
+                "verification_failure": 0,
+                "examples": [{
+                    "kind": "HttpClientError",
+                    "message": "Bad request.",
+                    "diagnostics": ["Invalid resource."],
+                }],
+            },
+            "warmup": {
+                "started": "2020-01-01T14:00:00Z",
+                "completed": "2020-01-01T15:00:00Z",
+                "execution_duration": "PT3600.0S",
+                "iterations": 100,
+                "iterations_failed": 0,
+            },
             "metrics": {
                 "throughput_per_second": 42.0,
                 "latency_millis_mean": 1.0,
-                "latency_millis_p50": 1,
-                "latency_millis_p90": 1,
-                "latency_millis_p99": 1,
-                "latency_millis_p999": 1,
-                "latency_millis_p100": 1,
+                "latency_millis_p50": 1.0,
+                "latency_millis_p90": 1.0,
+                "latency_millis_p99": 1.0,
+                "latency_millis_p999": 1.0,
+                "latency_millis_p100": 1.0,
+                "latency_corrected_millis_mean": 1.0,
+                "latency_corrected_millis_p50": 1.0,
+                "latency_corrected_millis_p90": 1.0,
+                "latency_corrected_millis_p99": 1.0,
+                "latency_corrected_millis_p999": 1.0,
+                "latency_corrected_millis_p100": 1.0,
                 "latency_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
                 "latency_histogram_hgrm_gzip": "foo",
-            }
+                "latency_corrected_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                "latency_corrected_histogram_hgrm_gzip": "foo",
+                "intervals": {
+                    "window": 1000,
+                    "intervals": [],
+                    "latency_interval_log": "",
+                },
+                "payload": {
+                    "request_bytes": {
+                        "bytes_mean": 0.0,
+                        "bytes_p50": 0,
+                        "bytes_p90": 0,
+                        "bytes_p99": 0,
+                        "bytes_p100": 0,
+                        "bytes_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                    },
+                    "request_compressed_bytes": null,
+                    "response_bytes": {
+                        "bytes_mean": 0.0,
+                        "bytes_p50": 0,
+                        "bytes_p90": 0,
+                        "bytes_p99": 0,
+                        "bytes_p100": 0,
+                        "bytes_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                    },
+                    "response_compressed_bytes": null,
+                    "responses": 0,
+                    "responses_encoding_honoured": 0,
+                },
+                "phases": [],
+            },
+            "steps": [],
+            "operations": [],
+            "contention": null,
         });
         let expected = serde_json::to_string(&expected).unwrap();
         let actual = ServerOperationMeasurement {
             concurrent_users: 10,
+            compression: None,
+            trial: 0,
             started: Utc.ymd(2020, 1, 1).and_hms(15, 0, 0),
             completed: Utc.ymd(2020, 1, 1).and_hms(16, 0, 0),
             execution_duration: Duration::nanoseconds(serde_duration_iso8601::NANOS_PER_SEC + 234),
+            iterations: 2,
             iterations_failed: 1,
             iterations_skipped: 0,
+            skip_reason: None,
+            failures: ServerOperationFailures {
+                http_client_error: 1,
+                examples: vec![ServerOperationFailureExample {
+                    kind: ServerOperationFailureKind::HttpClientError,
+                    message: "Bad request.".into(),
+                    diagnostics: vec!["Invalid resource.".into()],
+                }],
+                ..ServerOperationFailures::default()
+            },
+            steps: vec![],
+            operations: vec![],
+            contention: None,
+            warmup: Some(ServerOperationWarmup {
+                started: Utc.ymd(2020, 1, 1).and_hms(14, 0, 0),
+                completed: Utc.ymd(2020, 1, 1).and_hms(15, 0, 0),
+                execution_duration: Duration::hours(1),
+                iterations: 100,
+                iterations_failed: 0,
+            }),
             metrics: ServerOperationMetrics {
                 throughput_per_second: 42.0,
                 latency_millis_mean: 1.0,
-                latency_millis_p50: 1,
-                latency_millis_p90: 1,
-                latency_millis_p99: 1,
-                latency_millis_p999: 1,
-                latency_millis_p100: 1,
+                latency_millis_p50: 1.0,
+                latency_millis_p90: 1.0,
+                latency_millis_p99: 1.0,
+                latency_millis_p999: 1.0,
+                latency_millis_p100: 1.0,
+                latency_corrected_millis_mean: 1.0,
+                latency_corrected_millis_p50: 1.0,
+                latency_corrected_millis_p90: 1.0,
+                latency_corrected_millis_p99: 1.0,
+                latency_corrected_millis_p999: 1.0,
+                latency_corrected_millis_p100: 1.0,
                 latency_histogram: Histogram::<u64>::new(3).expect("Error creating histogram."),
                 latency_histogram_hgrm_gzip: "foo".into(),
+                latency_corrected_histogram: Histogram::<u64>::new(3)
+                    .expect("Error creating histogram."),
+                latency_corrected_histogram_hgrm_gzip: "foo".into(),
+                intervals: ServerOperationIntervals {
+                    window: Duration::seconds(1),
+                    intervals: vec![],
+                    latency_interval_log: "".into(),
+                },
+                payload: ServerOperationPayloadMetrics {
+                    request_bytes: ServerOperationPayloadSizes::new(
+                        Histogram::<u64>::new(3).expect("Error creating histogram."),
+                    ),
+                    request_compressed_bytes: None,
+                    response_bytes: ServerOperationPayloadSizes::new(
+                        Histogram::<u64>::new(3).expect("Error creating histogram."),
+                    ),
+                    response_compressed_bytes: None,
+                    responses: 0,
+                    responses_encoding_honoured: 0,
+                },
+                phases: vec![],
             },
         };
         let actual = serde_json::to_string(&actual).unwrap();
         assert_eq!(expected, actual);
     }
 
+    /// Verifies that [ServerOperationMeasurementLength] decides when to stop measurements as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn server_operation_measurement_length() {
+        let length = ServerOperationMeasurementLength::Iterations(10);
+        assert!(length.should_start_iteration(9, Duration::hours(1)));
+        assert!(!length.should_start_iteration(10, Duration::seconds(0)));
+        assert_eq!(Some(4), length.iterations_remaining(6));
+
+        let length = ServerOperationMeasurementLength::Duration {
+            duration: Duration::seconds(10),
+            min_iterations: 5,
+            max_iterations: Some(100),
+        };
+        assert!(length.should_start_iteration(50, Duration::seconds(9)));
+        assert!(!length.should_start_iteration(50, Duration::seconds(10)));
+        assert!(length.should_start_iteration(4, Duration::seconds(20)));
+        assert!(!length.should_start_iteration(100, Duration::seconds(0)));
+        assert_eq!(Some(50), length.iterations_remaining(50));
+        assert_eq!(100, length.iterations_expected(0, Duration::seconds(0)));
+        assert_eq!(50, length.iterations_expected(10, Duration::seconds(2)));
+        assert_eq!(5, length.iterations_expected(1, Duration::seconds(10)));
+
+        let length = ServerOperationMeasurementLength::Duration {
+            duration: Duration::seconds(10),
+            min_iterations: 0,
+            max_iterations: None,
+        };
+        assert!(length.should_start_iteration(u32::MAX - 1, Duration::seconds(9)));
+        assert_eq!(None, length.iterations_remaining(50));
+    }
+
+    /// Verifies that [ServerOperationMeasurementRecorder::skipped] estimates how many iterations a
+    /// duration-based measurement skipped when its circuit breaker tripped.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn server_operation_measurement_recorder_skipped_duration() {
+        let mut config = AppConfig::new().expect("Unable to load config.");
+        config.circuit_breaker =
+            Some(CircuitBreakerConfig::new(4, 0.5).expect("Unable to configure."));
+        let mut recorder = ServerOperationMeasurementRecorder::new(&config, SERVER_OP_NAME_FAKE);
+        let length = ServerOperationMeasurementLength::Duration {
+            duration: Duration::seconds(10),
+            min_iterations: 0,
+            max_iterations: None,
+        };
+        assert_eq!(0, recorder.skipped(length, Duration::seconds(2)).0);
+
+        for _ in 0..4 {
+            recorder.record(Err(ServerOperationIterationState::new()
+                .completed()
+                .failed(
+                    ServerOperationFailureKind::Timeout,
+                    eyre::eyre!("Too slow."),
+                )));
+        }
+
+        // 4 iterations in the first 2 of 10 seconds means that about 16 more would have been run.
+        let (skipped, reason) = recorder.skipped(length, Duration::seconds(2));
+        assert_eq!(16, skipped);
+        assert!(matches!(
+            reason,
+            Some(ServerOperationSkipReason::CircuitBreakerTripped(_))
+        ));
+
+        // ... unless the measurement's maximum iterations would have stopped it sooner.
+        let length = ServerOperationMeasurementLength::Duration {
+            duration: Duration::seconds(10),
+            min_iterations: 0,
+            max_iterations: Some(10),
+        };
+        assert_eq!(6, recorder.skipped(length, Duration::seconds(2)).0);
+    }
+
+    /// Verifies that [ServerOperationMeasurementRecorder] classifies failures as expected, and keeps their
+    /// `OperationOutcome` diagnostics.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn server_operation_measurement_recorder_failures() {
+        let mut recorder = ServerOperationMeasurementRecorder::new(
+            &AppConfig::new().expect("Unable to load config."),
+            SERVER_OP_NAME_FAKE,
+        );
+        recorder.record(Err(ServerOperationIterationState::new()
+            .completed()
+            .failed(
+                ServerOperationFailureKind::Timeout,
+                eyre::eyre!("Too slow."),
+            )));
+        let outcome = json!({
+            "resourceType": "OperationOutcome",
+            "issue": [{
+                "severity": "error",
+                "code": "processing",
+                "diagnostics": "Invalid resource.",
+            }],
+        });
+        recorder.record(Err(ServerOperationIterationState::new()
+            .completed()
+            .failed_response(
+                http::StatusCode::BAD_REQUEST,
+                &outcome.to_string(),
+                eyre::eyre!("Bad request."),
+            )));
+        recorder.record(Err(ServerOperationIterationState::new()
+            .completed()
+            .failed_response(
+                http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Not JSON.",
+                eyre::eyre!("Server error."),
+            )));
+
+        assert_eq!(3, recorder.iterations_failed());
+        let failures = recorder.take_failures();
+        assert_eq!(1, failures.timeout);
+        assert_eq!(1, failures.http_client_error);
+        assert_eq!(1, failures.http_server_error);
+        assert_eq!(3, failures.examples.len());
+        assert_eq!(
+            ServerOperationFailureKind::HttpClientError,
+            failures.examples[1].kind
+        );
+        assert_eq!("Bad request.", failures.examples[1].message);
+        assert_eq!(vec!["Invalid resource."], failures.examples[1].diagnostics);
+        assert!(failures.examples[2].diagnostics.is_empty());
+    }
+
+    /// Verifies that [ServerOperationMeasurementRecorder] breaks mixed-workload iterations down by operation
+    /// as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn server_operation_measurement_recorder_mixed() {
+        let mut recorder = ServerOperationMeasurementRecorder::new_mixed(
+            &AppConfig::new().expect("Unable to load config."),
+            SERVER_OP_NAME_FAKE,
+            &["read", "create"],
+        );
+        for _ in 0..3 {
+            recorder.record_mixed(
+                0,
+                Ok(ServerOperationIterationState::new()
+                    .completed()
+                    .succeeded_with_steps(vec![(
+                        "read".into(),
+                        std::time::Duration::from_millis(1),
+                    )])),
+            );
+        }
+        recorder.record_mixed(
+            1,
+            Err(ServerOperationIterationState::new().completed().failed(
+                ServerOperationFailureKind::Timeout,
+                eyre::eyre!("Too slow."),
+            )),
+        );
+
+        assert_eq!(4, recorder.iterations());
+        assert_eq!(1, recorder.iterations_failed());
+        assert!(recorder.take_steps().is_empty());
+        let operations = recorder.take_operations(Duration::seconds(1));
+        assert_eq!(2, operations.len());
+        assert_eq!("read", operations[0].operation);
+        assert_eq!(3, operations[0].iterations);
+        assert_eq!(0, operations[0].iterations_failed);
+        assert_eq!(1, operations[0].steps.len());
+        assert!((operations[0].metrics.throughput_per_second - 3.0).abs() < f64::EPSILON);
+        assert_eq!("create", operations[1].operation);
+        assert_eq!(1, operations[1].iterations_failed);
+        assert_eq!(1, operations[1].failures.timeout);
+    }
+
     /// Verifies that `FrameworkResults` serializes as expected.
     #[tracing::instrument(level = "info")]
     #[test_env_log::test(tokio::test)]
@@ -569,11 +2498,50 @@ mod tests {
         let expected = json!({
             "started": "2020-01-01T12:00:00Z",
             "completed": "2020-01-01T19:00:00Z",
+            "interrupted": null,
             "config": {
                 "iterations": 1,
                 "operation_timeout": 1000,
                 "concurrency_levels": [1, 10],
+                "operation_overrides": [{
+                    "operation": "Operation A",
+                    "iterations": null,
+                    "operation_timeout": 5000,
+                    "concurrency_levels": null,
+                }],
                 "population_size": 1,
+                "expected_interval": 10,
+                "measurement_duration": null,
+                "min_iterations": null,
+                "max_iterations": null,
+                "warmup_iterations": 100,
+                "warmup_duration": null,
+                "interval_window": 1000,
+                "trials": 1,
+                "think_time": {
+                    "mean": 500,
+                    "distribution": "Exponential",
+                },
+                "scenario_mix": [],
+                "workload_seed": 42,
+                "replay": null,
+                "compression": [],
+                "transport": { "Http1KeepAlive": { "pool_size": null } },
+                "circuit_breaker": null,
+                "saturation": {
+                    "slo_p99": 100,
+                    "max_error_rate": 0.01,
+                    "step_factor": 2.0,
+                    "max_concurrency": 10,
+                    "resolution": 1,
+                },
+                "servers_file": null,
+            },
+            "workload_plan": {
+                "seed": 42,
+                "sample_data_fingerprint": "0badf00d",
+                "organizations": ["org-b", "org-a"],
+                "patients": ["patient-a.json"],
             },
             "benchmark_metadata": {
                 "cargo_profile": "release",
@@ -596,26 +2564,100 @@ mod tests {
                 "operations": [
                     {
                         "operation": "Operation A",
+                        "settings": {
+                            "iterations": 1,
+                            "operation_timeout": 5000,
+                            "concurrency_levels": [1, 10],
+                        },
                         "errors": [],
                         "measurements": [{
                             "concurrent_users": 10,
+                            "compression": null,
+                            "trial": 0,
                             "started": "2020-01-01T15:00:00Z",
                             "completed": "2020-01-01T16:00:00Z",
                             "execution_duration": "PT1.234S",
+                            "iterations": 2,
                             "iterations_failed": 1,
                             "iterations_skipped": 0,
+                            "skip_reason": null,
+                            "failures": {
+                                "timeout": 0,
+                                "connection_error": 0,
+                                "http_client_error": 0,
+                                "http_server_error": 0,
+                                "verification_failure": 0,
+                                "examples": [],
+                            },
+                            "warmup": null,
                             "metrics": {
                                 "throughput_per_second": 42.0,
                                 "latency_millis_mean": 1.0,
-                                "latency_millis_p50": 1,
-                                "latency_millis_p90": 1,
-                                "latency_millis_p99": 1,
-                                "latency_millis_p999": 1,
-                                "latency_millis_p100": 1,
+                                "latency_millis_p50": 1.0,
+                                "latency_millis_p90": 1.0,
+                                "latency_millis_p99": 1.0,
+                                "latency_millis_p999": 1.0,
+                                "latency_millis_p100": 1.0,
+                                "latency_corrected_millis_mean": 1.0,
+                                "latency_corrected_millis_p50": 1.0,
+                                "latency_corrected_millis_p90": 1.0,
+                                "latency_corrected_millis_p99": 1.0,
+                                "latency_corrected_millis_p999": 1.0,
+                                "latency_corrected_millis_p100": 1.0,
                                 "latency_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
                                 "latency_histogram_hgrm_gzip": "foo",
+                                "latency_corrected_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                                "latency_corrected_histogram_hgrm_gzip": "foo",
+                                "intervals": {
+                                    "window": 1000,
+                                    "intervals": [],
+                                    "latency_interval_log": "",
+                                },
+                                "payload": {
+                                    "request_bytes": {
+                                        "bytes_mean": 0.0,
+                                        "bytes_p50": 0,
+                                        "bytes_p90": 0,
+                                        "bytes_p99": 0,
+                                        "bytes_p100": 0,
+                                        "bytes_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                                    },
+                                    "request_compressed_bytes": null,
+                                    "response_bytes": {
+                                        "bytes_mean": 0.0,
+                                        "bytes_p50": 0,
+                                        "bytes_p90": 0,
+                                        "bytes_p99": 0,
+                                        "bytes_p100": 0,
+                                        "bytes_histogram": "HISTFAAAABx4nJNpmSzMwMDAyAABzFAaxmey/wBlAQA8yQJ9",
+                                    },
+                                    "response_compressed_bytes": null,
+                                    "responses": 0,
+                                    "responses_encoding_honoured": 0,
+                                },
+                                "phases": [],
+                            },
+                            "steps": [],
+                            "operations": [],
+                            "contention": null,
+                        }],
+                        "summaries": [{
+                            "concurrent_users": 10,
+                            "compression": null,
+                            "trials": 2,
+                            "throughput_per_second": { "mean": 42.0, "lower": 40.0, "upper": 44.0 },
+                            "latency_millis_p50": { "mean": 42.0, "lower": 40.0, "upper": 44.0 },
+                            "latency_millis_p90": { "mean": 42.0, "lower": 40.0, "upper": 44.0 },
+                            "latency_millis_p99": { "mean": 42.0, "lower": 40.0, "upper": 44.0 },
+                            "latency_corrected_millis_p99": { "mean": 42.0, "lower": 40.0, "upper": 44.0 },
+                        }],
+                        "saturation": {
+                            "max_concurrent_users": 10,
+                            "max_throughput_per_second": 42.0,
+                            "stop_reason": {
+                                "MaxConcurrencyReached": []
                             }
-                        }]
+                        }
                     }
                 ],
                 "shutdown": {
@@ -628,14 +2670,60 @@ mod tests {
             }
         ]});
         let expected = serde_json::to_string(&expected).unwrap();
+        let confidence_interval = ConfidenceInterval {
+            mean: 42.0,
+            lower: 40.0,
+            upper: 44.0,
+        };
         let actual = FrameworkResults {
             started: Utc.ymd(2020, 1, 1).and_hms(12, 0, 0),
             completed: Some(Utc.ymd(2020, 1, 1).and_hms(19, 0, 0)),
+            interrupted: None,
             config: AppConfig {
                 iterations: 1,
                 operation_timeout: Duration::milliseconds(1000),
                 concurrency_levels: vec![1, 10],
+                operation_overrides: vec![OperationOverrides {
+                    operation: SERVER_OP_NAME_FAKE.into(),
+                    iterations: None,
+                    operation_timeout: Some(Duration::milliseconds(5000)),
+                    concurrency_levels: None,
+                }],
                 population_size: 1,
+                expected_interval: Some(Duration::milliseconds(10)),
+                measurement_duration: None,
+                min_iterations: None,
+                max_iterations: None,
+                warmup_iterations: Some(100),
+                warmup_duration: None,
+                interval_window: Duration::seconds(1),
+                trials: 1,
+                think_time: Some(ThinkTime {
+                    mean: Duration::milliseconds(500),
+                    distribution: ThinkTimeDistribution::Exponential,
+                }),
+                scenario_mix: vec![],
+                workload_seed: 42,
+                replay: None,
+                compression: vec![],
+                transport: Transport::default(),
+                circuit_breaker: None,
+                saturation: Some(SaturationConfig {
+                    slo_p99: Duration::milliseconds(100),
+                    max_error_rate: 0.01,
+                    step_factor: 2.0,
+                    max_concurrency: 10,
+                    resolution: 1,
+                }),
+                servers_file: None,
+                checkpoint: None,
+                resume: false,
+            },
+            workload_plan: WorkloadPlan {
+                seed: 42,
+                sample_data_fingerprint: "0badf00d".into(),
+                organizations: vec!["org-b".into(), "org-a".into()],
+                patients: vec!["patient-a.json".into()],
             },
             benchmark_metadata: FrameworkMetadata {
                 cargo_profile: "release".into(),
@@ -655,29 +2743,85 @@ mod tests {
                 }),
                 operations: Some(vec![ServerOperationLog {
                     operation: SERVER_OP_NAME_FAKE.into(),
+                    settings: Some(ServerOperationSettings {
+                        iterations: 1,
+                        operation_timeout: Duration::milliseconds(5000),
+                        concurrency_levels: vec![1, 10],
+                    }),
                     errors: vec![],
                     measurements: vec![ServerOperationMeasurement {
                         concurrent_users: 10,
+                        compression: None,
+                        trial: 0,
                         started: Utc.ymd(2020, 1, 1).and_hms(15, 0, 0),
                         completed: Utc.ymd(2020, 1, 1).and_hms(16, 0, 0),
                         execution_duration: Duration::nanoseconds(
                             serde_duration_iso8601::NANOS_PER_SEC + 234,
                         ),
+                        iterations: 2,
                         iterations_failed: 1,
                         iterations_skipped: 0,
+                        skip_reason: None,
+                        failures: ServerOperationFailures::default(),
+                        warmup: None,
+                        steps: vec![],
+                        operations: vec![],
+                        contention: None,
                         metrics: ServerOperationMetrics {
                             throughput_per_second: 42.0,
                             latency_millis_mean: 1.0,
-                            latency_millis_p50: 1,
-                            latency_millis_p90: 1,
-                            latency_millis_p99: 1,
-                            latency_millis_p999: 1,
-                            latency_millis_p100: 1,
+                            latency_millis_p50: 1.0,
+                            latency_millis_p90: 1.0,
+                            latency_millis_p99: 1.0,
+                            latency_millis_p999: 1.0,
+                            latency_millis_p100: 1.0,
+                            latency_corrected_millis_mean: 1.0,
+                            latency_corrected_millis_p50: 1.0,
+                            latency_corrected_millis_p90: 1.0,
+                            latency_corrected_millis_p99: 1.0,
+                            latency_corrected_millis_p999: 1.0,
+                            latency_corrected_millis_p100: 1.0,
                             latency_histogram: Histogram::<u64>::new(3)
                                 .expect("Error creating histogram."),
                             latency_histogram_hgrm_gzip: "foo".into(),
+                            latency_corrected_histogram: Histogram::<u64>::new(3)
+                                .expect("Error creating histogram."),
+                            latency_corrected_histogram_hgrm_gzip: "foo".into(),
+                            intervals: ServerOperationIntervals {
+                                window: Duration::seconds(1),
+                                intervals: vec![],
+                                latency_interval_log: "".into(),
+                            },
+                            payload: ServerOperationPayloadMetrics {
+                                request_bytes: ServerOperationPayloadSizes::new(
+                                    Histogram::<u64>::new(3).expect("Error creating histogram."),
+                                ),
+                                request_compressed_bytes: None,
+                                response_bytes: ServerOperationPayloadSizes::new(
+                                    Histogram::<u64>::new(3).expect("Error creating histogram."),
+                                ),
+                                response_compressed_bytes: None,
+                                responses: 0,
+                                responses_encoding_honoured: 0,
+                            },
+                            phases: vec![],
                         },
                     }],
+                    summaries: vec![ServerOperationSummary {
+                        concurrent_users: 10,
+                        compression: None,
+                        trials: 2,
+                        throughput_per_second: confidence_interval.clone(),
+                        latency_millis_p50: confidence_interval.clone(),
+                        latency_millis_p90: confidence_interval.clone(),
+                        latency_millis_p99: confidence_interval.clone(),
+                        latency_corrected_millis_p99: confidence_interval,
+                    }],
+                    saturation: Some(ServerOperationSaturation {
+                        max_concurrent_users: Some(10),
+                        max_throughput_per_second: Some(42.0),
+                        stop_reason: ServerOperationSaturationStop::MaxConcurrencyReached(),
+                    }),
                 }]),
                 shutdown: Some(FrameworkOperationLog {
                     started: Utc.ymd(2020, 1, 1).and_hms(17, 0, 0),
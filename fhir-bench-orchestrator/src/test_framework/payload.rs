@@ -0,0 +1,542 @@
+//! Contains the code to measure and compress the request and response bodies of server operations.
+
+use super::phases::ServerOperationPhases;
+use super::{ServerOperationPayloadMetrics, ServerOperationPayloadSizes};
+use crate::config::ContentEncoding;
+use crate::servers::ConnectTimer;
+use eyre::Result;
+use hdrhistogram::Histogram;
+use std::io::{Read, Write};
+use std::time::Instant;
+
+/// The size of the buffers used to compress and decompress Brotli bodies, in bytes.
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// The Brotli quality level (`0` to `11`) to compress request bodies with: a middling level, comparable to
+/// the gzip default, as is typical for dynamically-generated content.
+const BROTLI_QUALITY: u32 = 5;
+
+/// The base-2 logarithm of the Brotli window size to compress request bodies with (the recommended default).
+const BROTLI_WINDOW_SIZE: u32 = 22;
+
+/// The sizes of the request and response bodies of a single operation iteration, summed across all of the
+/// requests that the iteration made.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(super) struct ServerOperationPayload {
+    /// The number of bytes in the request bodies that were sent, before compression (if any).
+    pub request_bytes: u64,
+
+    /// The number of bytes in the request bodies that were sent compressed, as they were sent over the wire,
+    /// or `None` if no requests were compressed.
+    pub request_compressed_bytes: Option<u64>,
+
+    /// The number of bytes in the response bodies that were received, after decompression (if any).
+    pub response_bytes: u64,
+
+    /// The number of bytes in the response bodies that were received compressed, as they were sent over the
+    /// wire, or `None` if no responses were compressed.
+    pub response_compressed_bytes: Option<u64>,
+
+    /// The number of responses that were received.
+    pub responses: u64,
+
+    /// The number of responses that the server encoded as requested (which, if no encoding was requested,
+    /// means that they weren't encoded at all).
+    pub responses_encoding_honoured: u64,
+}
+
+impl ServerOperationPayload {
+    /// Adds the sizes from another request/response to these ones.
+    ///
+    /// Parameters:
+    /// * `other`: the [ServerOperationPayload] to add in
+    pub fn add(&mut self, other: &ServerOperationPayload) {
+        let add_optional = |bytes: Option<u64>, other_bytes: Option<u64>| match (bytes, other_bytes)
+        {
+            (None, None) => None,
+            (bytes, other_bytes) => Some(bytes.unwrap_or(0) + other_bytes.unwrap_or(0)),
+        };
+        self.request_bytes += other.request_bytes;
+        self.request_compressed_bytes = add_optional(
+            self.request_compressed_bytes,
+            other.request_compressed_bytes,
+        );
+        self.response_bytes += other.response_bytes;
+        self.response_compressed_bytes = add_optional(
+            self.response_compressed_bytes,
+            other.response_compressed_bytes,
+        );
+        self.responses += other.responses;
+        self.responses_encoding_honoured += other.responses_encoding_honoured;
+    }
+}
+
+/// A response whose body has been read by [read_response_body].
+pub(super) struct ServerOperationResponse {
+    /// The decoded response body, or a description of the error that prevented it from being read.
+    pub body: String,
+
+    /// The [ServerOperationPayload] for the request and response.
+    pub payload: ServerOperationPayload,
+
+    /// The [ServerOperationPhases] for the request and response.
+    pub phases: ServerOperationPhases,
+
+    /// When the response body had been received in full, before it was decoded, in monotonic time. Operation
+    /// iterations should be timed up to here, so that the client's decompression isn't counted as latency.
+    pub received: Instant,
+}
+
+impl ServerOperationResponse {
+    /// Constructs a [ServerOperationResponse] for a response whose body couldn't be read.
+    ///
+    /// Parameters:
+    /// * `error`: the error that prevented the response body from being read
+    pub fn unreadable(error: eyre::Error) -> ServerOperationResponse {
+        ServerOperationResponse {
+            body: format!("Unable to retrieve response body due to error: '{}'", error),
+            payload: ServerOperationPayload::default(),
+            phases: ServerOperationPhases::default(),
+            received: Instant::now(),
+        }
+    }
+}
+
+/// Applies the specified [ContentEncoding] (if any) to a request: asking for the response to be encoded
+/// with it, and also compressing the request body with it (where supported).
+///
+/// Parameters:
+/// * `request_builder`: the [reqwest::RequestBuilder] for the request
+/// * `compression`: the [ContentEncoding] to use, or `None` to not request any encoding
+/// * `body`: the request body to send, if any
+///
+/// Returns the updated [reqwest::RequestBuilder], along with the [ServerOperationPayload] for its request
+/// body, which should be passed to [read_response_body].
+pub(super) fn encode_request(
+    request_builder: reqwest::RequestBuilder,
+    compression: Option<ContentEncoding>,
+    body: Option<String>,
+) -> (reqwest::RequestBuilder, ServerOperationPayload) {
+    let mut request_builder = request_builder;
+    if let Some(compression) = compression {
+        request_builder =
+            request_builder.header(http::header::ACCEPT_ENCODING, compression.header_value());
+    }
+
+    let mut payload = ServerOperationPayload::default();
+    if let Some(body) = body {
+        payload.request_bytes = body.len() as u64;
+        let compressed_body = compression.and_then(|compression| compress(compression, &body));
+        request_builder = match (compression, compressed_body) {
+            (Some(compression), Some(compressed_body)) => {
+                payload.request_compressed_bytes = Some(compressed_body.len() as u64);
+                request_builder
+                    .header(http::header::CONTENT_ENCODING, compression.header_value())
+                    .body(compressed_body)
+            }
+            _ => request_builder.body(body),
+        };
+    }
+
+    (request_builder, payload)
+}
+
+/// Compresses the specified request body with the specified [ContentEncoding], if it's supported for
+/// request bodies.
+///
+/// Parameters:
+/// * `compression`: the [ContentEncoding] to compress with
+/// * `body`: the request body to compress
+///
+/// Returns the compressed request body, or `None` if the [ContentEncoding] doesn't compress anything.
+fn compress(compression: ContentEncoding, body: &str) -> Option<Vec<u8>> {
+    let compressed_body = match compression {
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(body.as_bytes())
+                .and_then(|_| encoder.finish())
+        }
+        ContentEncoding::Deflate => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(body.as_bytes())
+                .and_then(|_| encoder.finish())
+        }
+        ContentEncoding::Brotli => {
+            let mut encoder = brotli::CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW_SIZE,
+            );
+            encoder
+                .write_all(body.as_bytes())
+                .and_then(|_| encoder.flush())
+                .map(|_| encoder.into_inner())
+        }
+        ContentEncoding::Identity => return None,
+    };
+    Some(compressed_body.expect("Unable to compress request body."))
+}
+
+/// Reads the full body of the specified [reqwest::Response], decompressing it if the server compressed it
+/// (with a [ContentEncoding] that's supported). A response with any other `Content-Encoding` is counted as
+/// not having honoured the requested encoding, and its body is returned as-is.
+///
+/// Parameters:
+/// * `response`: the [reqwest::Response] to read the body of
+/// * `compression`: the [ContentEncoding] that was requested, if any
+/// * `connect_timer`: the [ConnectTimer] of the client that sent the request, which records how long it
+///   spent establishing a new connection for it, if it did
+/// * `request_payload`: the [ServerOperationPayload] for the request that `response` is for, as returned by
+///   [encode_request]
+/// * `sent`: when the request that `response` is for was sent, in monotonic time (this should be called
+///   as soon as the response is received, so that its headers' arrival can be timed)
+///
+/// Returns the [ServerOperationResponse].
+pub(super) async fn read_response_body(
+    response: reqwest::Response,
+    compression: Option<ContentEncoding>,
+    connect_timer: &ConnectTimer,
+    request_payload: ServerOperationPayload,
+    sent: Instant,
+) -> Result<ServerOperationResponse> {
+    let headers_received = Instant::now();
+    let connect = connect_timer.take();
+    let encoding: Option<ContentEncoding> =
+        match response.headers().get(http::header::CONTENT_ENCODING) {
+            Some(encoding) => encoding
+                .to_str()
+                .ok()
+                .and_then(|encoding| encoding.parse().ok()),
+            None => Some(ContentEncoding::Identity),
+        };
+    let body = response.bytes().await?;
+    let body_received = Instant::now();
+
+    let mut response_body = String::new();
+    match encoding {
+        Some(ContentEncoding::Identity) | None => {
+            response_body = String::from_utf8_lossy(&body).into_owned()
+        }
+        Some(ContentEncoding::Gzip) => {
+            flate2::read::GzDecoder::new(&body[..]).read_to_string(&mut response_body)?;
+        }
+        Some(ContentEncoding::Deflate) => {
+            flate2::read::ZlibDecoder::new(&body[..]).read_to_string(&mut response_body)?;
+        }
+        Some(ContentEncoding::Brotli) => {
+            brotli::Decompressor::new(&body[..], BROTLI_BUFFER_SIZE)
+                .read_to_string(&mut response_body)?;
+        }
+    }
+
+    let mut payload = request_payload;
+    payload.add(&ServerOperationPayload {
+        response_bytes: response_body.len() as u64,
+        response_compressed_bytes: match encoding {
+            Some(ContentEncoding::Identity) | None => None,
+            Some(_) => Some(body.len() as u64),
+        },
+        responses: 1,
+        responses_encoding_honoured: if encoding
+            == Some(compression.unwrap_or(ContentEncoding::Identity))
+        {
+            1
+        } else {
+            0
+        },
+        ..ServerOperationPayload::default()
+    });
+    let phases = ServerOperationPhases {
+        connect,
+        ttfb: (headers_received - sent).saturating_sub(connect.unwrap_or_default()),
+        body: body_received - headers_received,
+    };
+    Ok(ServerOperationResponse {
+        body: response_body,
+        payload,
+        phases,
+        received: body_received,
+    })
+}
+
+/// Accumulates the [ServerOperationPayload]s of each successful iteration run for a measurement, until
+/// they're ready to be summarized into [ServerOperationPayloadMetrics].
+pub(super) struct ServerOperationPayloadRecorder {
+    /// The (uncompressed) request body sizes of each successful iteration, in bytes.
+    request_bytes: Histogram<u64>,
+
+    /// The compressed request body sizes of each successful iteration that sent compressed requests, in
+    /// bytes.
+    request_compressed_bytes: Histogram<u64>,
+
+    /// The (decompressed) response body sizes of each successful iteration, in bytes.
+    response_bytes: Histogram<u64>,
+
+    /// The compressed response body sizes of each successful iteration that received compressed responses,
+    /// in bytes.
+    response_compressed_bytes: Histogram<u64>,
+
+    /// The number of responses received by the successful iterations.
+    responses: u64,
+
+    /// The number of responses received by the successful iterations that were encoded as requested.
+    responses_encoding_honoured: u64,
+}
+
+impl ServerOperationPayloadRecorder {
+    /// Constructs a new, empty [ServerOperationPayloadRecorder].
+    pub fn new() -> ServerOperationPayloadRecorder {
+        ServerOperationPayloadRecorder {
+            request_bytes: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+            request_compressed_bytes: Histogram::<u64>::new(3)
+                .expect("Unable to construct histogram."),
+            response_bytes: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+            response_compressed_bytes: Histogram::<u64>::new(3)
+                .expect("Unable to construct histogram."),
+            responses: 0,
+            responses_encoding_honoured: 0,
+        }
+    }
+
+    /// Records the [ServerOperationPayload] of a single successful operation iteration.
+    ///
+    /// Parameters:
+    /// * `payload`: the [ServerOperationPayload] to record
+    pub fn record(&mut self, payload: &ServerOperationPayload) {
+        self.request_bytes
+            .record(payload.request_bytes)
+            .expect("Histogram recording failed.");
+        if let Some(request_compressed_bytes) = payload.request_compressed_bytes {
+            self.request_compressed_bytes
+                .record(request_compressed_bytes)
+                .expect("Histogram recording failed.");
+        }
+        self.response_bytes
+            .record(payload.response_bytes)
+            .expect("Histogram recording failed.");
+        if let Some(response_compressed_bytes) = payload.response_compressed_bytes {
+            self.response_compressed_bytes
+                .record(response_compressed_bytes)
+                .expect("Histogram recording failed.");
+        }
+        self.responses += payload.responses;
+        self.responses_encoding_honoured += payload.responses_encoding_honoured;
+    }
+
+    /// Summarizes the recorded payloads into [ServerOperationPayloadMetrics].
+    pub fn into_metrics(self) -> ServerOperationPayloadMetrics {
+        let sizes_if_any = |histogram: Histogram<u64>| {
+            if histogram.is_empty() {
+                None
+            } else {
+                Some(ServerOperationPayloadSizes::new(histogram))
+            }
+        };
+
+        ServerOperationPayloadMetrics {
+            request_bytes: ServerOperationPayloadSizes::new(self.request_bytes),
+            request_compressed_bytes: sizes_if_any(self.request_compressed_bytes),
+            response_bytes: ServerOperationPayloadSizes::new(self.response_bytes),
+            response_compressed_bytes: sizes_if_any(self.response_compressed_bytes),
+            responses: self.responses,
+            responses_encoding_honoured: self.responses_encoding_honoured,
+        }
+    }
+}
+
+/// Unit tests for the payload size measurements.
+#[cfg(test)]
+mod tests {
+    use super::ServerOperationPayload;
+    use crate::config::ContentEncoding;
+    use crate::servers::ConnectTimer;
+    use std::io::{Read, Write};
+    use std::time::Instant;
+
+    /// Verifies that [super::encode_request] compresses request bodies as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn encode_request() {
+        let body = r#"{"resourceType":"Organization"}"#;
+        let request_builder = || reqwest::Client::new().post("http://localhost/Organization");
+
+        let (request_builder_identity, payload) =
+            super::encode_request(request_builder(), None, Some(body.into()));
+        let request = request_builder_identity.build().unwrap();
+        assert!(request.headers().get("Accept-Encoding").is_none());
+        assert_eq!(Some(body.as_bytes()), request.body().unwrap().as_bytes());
+        assert_eq!(body.len() as u64, payload.request_bytes);
+        assert_eq!(None, payload.request_compressed_bytes);
+
+        let (request_builder_gzip, payload) = super::encode_request(
+            request_builder(),
+            Some(ContentEncoding::Gzip),
+            Some(body.into()),
+        );
+        let request = request_builder_gzip.build().unwrap();
+        assert_eq!("gzip", request.headers()["Accept-Encoding"]);
+        assert_eq!("gzip", request.headers()["Content-Encoding"]);
+        let compressed = request.body().unwrap().as_bytes().unwrap();
+        assert_eq!(
+            Some(compressed.len() as u64),
+            payload.request_compressed_bytes
+        );
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(compressed)
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(body, decompressed);
+
+        let (request_builder_brotli, payload) = super::encode_request(
+            request_builder(),
+            Some(ContentEncoding::Brotli),
+            Some(body.into()),
+        );
+        let request = request_builder_brotli.build().unwrap();
+        assert_eq!("br", request.headers()["Accept-Encoding"]);
+        assert_eq!("br", request.headers()["Content-Encoding"]);
+        let compressed = request.body().unwrap().as_bytes().unwrap();
+        assert_eq!(
+            Some(compressed.len() as u64),
+            payload.request_compressed_bytes
+        );
+        let mut decompressed = String::new();
+        brotli::Decompressor::new(compressed, 4096)
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(body, decompressed);
+    }
+
+    /// Verifies that [super::read_response_body] measures plain, gzip-compressed, Brotli-compressed, and
+    /// unsupported-encoding responses (and their phases) as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn read_response_body() {
+        let body = r#"{"resourceType":"Patient"}"#;
+        let request_payload = ServerOperationPayload {
+            request_bytes: 42,
+            ..ServerOperationPayload::default()
+        };
+
+        let response = reqwest::Response::from(http::Response::new(body));
+        let sent = Instant::now();
+        let response = super::read_response_body(
+            response,
+            None,
+            &ConnectTimer::default(),
+            request_payload.clone(),
+            sent,
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, response.body);
+        assert_eq!(
+            ServerOperationPayload {
+                request_bytes: 42,
+                request_compressed_bytes: None,
+                response_bytes: body.len() as u64,
+                response_compressed_bytes: None,
+                responses: 1,
+                responses_encoding_honoured: 1,
+            },
+            response.payload
+        );
+        assert_eq!(None, response.phases.connect);
+        assert!(response.phases.ttfb + response.phases.body <= response.received - sent);
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let compressed_len = compressed.len() as u64;
+        let gzip_response = || {
+            let response = http::Response::builder()
+                .header("Content-Encoding", "gzip")
+                .body(compressed.clone())
+                .unwrap();
+            reqwest::Response::from(response)
+        };
+        let response = super::read_response_body(
+            gzip_response(),
+            Some(ContentEncoding::Gzip),
+            &ConnectTimer::default(),
+            ServerOperationPayload::default(),
+            Instant::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, response.body);
+        assert_eq!(
+            Some(compressed_len),
+            response.payload.response_compressed_bytes
+        );
+        assert_eq!(1, response.payload.responses_encoding_honoured);
+
+        let payload = super::read_response_body(
+            gzip_response(),
+            Some(ContentEncoding::Deflate),
+            &ConnectTimer::default(),
+            ServerOperationPayload::default(),
+            Instant::now(),
+        )
+        .await
+        .unwrap()
+        .payload;
+        assert_eq!(0, payload.responses_encoding_honoured);
+
+        // An encoding that isn't supported is read as-is, and counted as not honoured.
+        let unknown_response = http::Response::builder()
+            .header("Content-Encoding", "zstd")
+            .body(body)
+            .unwrap();
+        let unknown = super::read_response_body(
+            reqwest::Response::from(unknown_response),
+            Some(ContentEncoding::Gzip),
+            &ConnectTimer::default(),
+            ServerOperationPayload::default(),
+            Instant::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, unknown.body);
+        assert_eq!(None, unknown.payload.response_compressed_bytes);
+        assert_eq!(1, unknown.payload.responses);
+        assert_eq!(0, unknown.payload.responses_encoding_honoured);
+
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(body.as_bytes()).unwrap();
+        encoder.flush().unwrap();
+        let brotli_compressed = encoder.into_inner();
+        let brotli_response = http::Response::builder()
+            .header("Content-Encoding", "br")
+            .body(brotli_compressed.clone())
+            .unwrap();
+        let brotli = super::read_response_body(
+            reqwest::Response::from(brotli_response),
+            Some(ContentEncoding::Brotli),
+            &ConnectTimer::default(),
+            ServerOperationPayload::default(),
+            Instant::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, brotli.body);
+        let brotli_payload = brotli.payload;
+        assert_eq!(
+            Some(brotli_compressed.len() as u64),
+            brotli_payload.response_compressed_bytes
+        );
+        assert_eq!(1, brotli_payload.responses_encoding_honoured);
+
+        let mut total = payload.clone();
+        total.add(&request_payload);
+        assert_eq!(42, total.request_bytes);
+        assert_eq!(body.len() as u64, total.response_bytes);
+        assert_eq!(Some(compressed_len), total.response_compressed_bytes);
+        assert_eq!(1, total.responses);
+    }
+}
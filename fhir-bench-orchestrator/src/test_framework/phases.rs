@@ -0,0 +1,142 @@
+//! Contains the code to break the latency of server operations down into phases: connecting to the server,
+//! waiting for the first byte of its response (TTFB), and then receiving the rest of the response body.
+//! This shows whether a server is slow to start responding, or slow to serialize large responses.
+
+use super::ServerOperationStepMetrics;
+use hdrhistogram::Histogram;
+use std::time::Duration;
+
+/// The name of the phase for establishing new connections to the server.
+static PHASE_CONNECT: &str = "connect";
+
+/// The name of the phase from sending a request until its response headers are received.
+static PHASE_TTFB: &str = "ttfb";
+
+/// The name of the phase from receiving a response's headers until its body is complete.
+static PHASE_BODY: &str = "body";
+
+/// How long each phase of a single operation iteration took, summed across all of the requests that the
+/// iteration made.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(super) struct ServerOperationPhases {
+    /// How long it took to establish new connections to the server for the requests, or `None` if they all
+    /// reused pooled connections.
+    pub connect: Option<Duration>,
+
+    /// How long it took from sending each request until its response headers were received, excluding the
+    /// time spent establishing any new connection for it.
+    pub ttfb: Duration,
+
+    /// How long it took from receiving each response's headers until its body was complete.
+    pub body: Duration,
+}
+
+impl ServerOperationPhases {
+    /// Adds the phases from another request/response to these ones.
+    ///
+    /// Parameters:
+    /// * `other`: the [ServerOperationPhases] to add in
+    pub fn add(&mut self, other: &ServerOperationPhases) {
+        self.connect = match (self.connect, other.connect) {
+            (None, None) => None,
+            (connect, other_connect) => {
+                Some(connect.unwrap_or_default() + other_connect.unwrap_or_default())
+            }
+        };
+        self.ttfb += other.ttfb;
+        self.body += other.body;
+    }
+}
+
+/// Accumulates the [ServerOperationPhases] of each successful iteration run for a measurement, until
+/// they're ready to be summarized into [ServerOperationStepMetrics].
+pub(super) struct ServerOperationPhasesRecorder {
+    /// The connect latencies of each successful iteration that established new connections, in
+    /// microseconds.
+    connect: Histogram<u64>,
+
+    /// The TTFB latencies of each successful iteration, in microseconds.
+    ttfb: Histogram<u64>,
+
+    /// The body latencies of each successful iteration, in microseconds.
+    body: Histogram<u64>,
+}
+
+impl ServerOperationPhasesRecorder {
+    /// Constructs a new, empty [ServerOperationPhasesRecorder].
+    pub fn new() -> ServerOperationPhasesRecorder {
+        ServerOperationPhasesRecorder {
+            connect: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+            ttfb: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+            body: Histogram::<u64>::new(3).expect("Unable to construct histogram."),
+        }
+    }
+
+    /// Records the [ServerOperationPhases] of a single successful operation iteration.
+    ///
+    /// Parameters:
+    /// * `phases`: the [ServerOperationPhases] to record
+    pub fn record(&mut self, phases: &ServerOperationPhases) {
+        if let Some(connect) = phases.connect {
+            self.connect
+                .record(connect.as_micros() as u64)
+                .expect("Histogram recording failed.");
+        }
+        self.ttfb
+            .record(phases.ttfb.as_micros() as u64)
+            .expect("Histogram recording failed.");
+        self.body
+            .record(phases.body.as_micros() as u64)
+            .expect("Histogram recording failed.");
+    }
+
+    /// Summarizes the recorded phases into a [ServerOperationStepMetrics] for each phase.
+    pub fn into_metrics(self) -> Vec<ServerOperationStepMetrics> {
+        vec![
+            ServerOperationStepMetrics::new(PHASE_CONNECT.into(), self.connect),
+            ServerOperationStepMetrics::new(PHASE_TTFB.into(), self.ttfb),
+            ServerOperationStepMetrics::new(PHASE_BODY.into(), self.body),
+        ]
+    }
+}
+
+/// Unit tests for the latency phases.
+#[cfg(test)]
+mod tests {
+    use super::{ServerOperationPhases, ServerOperationPhasesRecorder};
+    use std::time::Duration;
+
+    /// Verifies that [ServerOperationPhases] are added up and summarized as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn record_phases() {
+        let mut phases = ServerOperationPhases {
+            connect: None,
+            ttfb: Duration::from_millis(10),
+            body: Duration::from_millis(2),
+        };
+        phases.add(&ServerOperationPhases {
+            connect: None,
+            ttfb: Duration::from_millis(20),
+            body: Duration::from_millis(3),
+        });
+        assert_eq!(None, phases.connect);
+        phases.add(&ServerOperationPhases {
+            connect: Some(Duration::from_millis(4)),
+            ..ServerOperationPhases::default()
+        });
+        assert_eq!(Some(Duration::from_millis(4)), phases.connect);
+        assert_eq!(Duration::from_millis(30), phases.ttfb);
+        assert_eq!(Duration::from_millis(5), phases.body);
+
+        let mut recorder = ServerOperationPhasesRecorder::new();
+        recorder.record(&phases);
+        let metrics = recorder.into_metrics();
+        assert_eq!(
+            vec!["connect", "ttfb", "body"],
+            metrics.iter().map(|m| m.step.as_str()).collect::<Vec<_>>()
+        );
+        assert!((metrics[0].latency_millis_p100 - 4.0).abs() < 0.1);
+        assert!((metrics[1].latency_millis_p100 - 30.0).abs() < 0.1);
+    }
+}
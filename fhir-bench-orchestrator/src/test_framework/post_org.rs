@@ -2,23 +2,28 @@
 //! operations.
 
 use super::{
-    ServerOperationIterationFailed, ServerOperationIterationStarting,
-    ServerOperationIterationState, ServerOperationIterationSucceeded, ServerOperationMetrics,
+    elapsed_since, measure_operation, run_measurements, run_operation_with_timeout,
+    ServerOperationFailureKind, ServerOperationIterationFailed, ServerOperationIterationStarting,
+    ServerOperationIterationState, ServerOperationIterationSucceeded,
+    ServerOperationMeasurementLength, ServerOperationMeasurementRecorder,
+    ServerOperationSkipReason,
 };
+use crate::config::ContentEncoding;
 use crate::servers::ServerPlugin;
+use crate::test_framework::payload::{encode_request, read_response_body, ServerOperationResponse};
+use crate::test_framework::users::{run_virtual_users, VirtualUser};
+use crate::test_framework::workload_plan;
 use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
 use crate::AppState;
 use crate::{sample_data::SampleResource, servers::ServerHandle};
-use chrono::prelude::*;
 use chrono::Duration;
 use eyre::eyre;
-use futures::prelude::*;
-use hdrhistogram::Histogram;
 use std::convert::TryFrom;
+use std::time::Instant;
 use tracing::{info_span, trace_span, warn, Instrument};
 use url::Url;
 
-static SERVER_OP_NAME_POST_ORG: &str = "POST /Organization";
+pub(super) static SERVER_OP_NAME_POST_ORG: &str = "POST /Organization";
 
 /// Attempts to verify and benchmark FHIR `POST /Organization` operations for the specified FHIR
 /// server.
@@ -33,15 +38,14 @@ pub async fn benchmark_post_org(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
 ) -> ServerOperationLog {
-    let mut server_op_log = ServerOperationLog::new(SERVER_OP_NAME_POST_ORG.into());
-
-    for concurrent_users in app_state.config.concurrency_levels.clone() {
-        let measurement =
-            benchmark_post_org_for_users(app_state, server_handle, concurrent_users).await;
-        server_op_log.measurements.push(measurement);
-    }
-
-    server_op_log
+    run_measurements(
+        app_state,
+        SERVER_OP_NAME_POST_ORG,
+        |concurrent_users, compression| {
+            benchmark_post_org_for_users(app_state, server_handle, concurrent_users, compression)
+        },
+    )
+    .await
 }
 
 /// Verifies and benchmarks FHIR `POST /Organization` operations for the specified number of concurrent users.
@@ -50,6 +54,7 @@ pub async fn benchmark_post_org(
 /// * `app_state`: the application's [AppState]
 /// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
 /// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
 ///
 /// Returns a [ServerOperationMeasurement] with the results.
 #[tracing::instrument(level = "info", skip(app_state, server_handle))]
@@ -57,103 +62,124 @@ async fn benchmark_post_org_for_users(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
     concurrent_users: u32,
+    compression: Option<ContentEncoding>,
 ) -> ServerOperationMeasurement {
-    // Setup the results tracking state.
-    let mut histogram = Histogram::<u64>::new(3).expect("Unable to construct histogram.");
-    let started = Utc::now();
+    let (measurement, ()) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || ServerOperationMeasurementRecorder::new(&app_state.config, SERVER_OP_NAME_POST_ORG),
+        |length, mut recorder| async move {
+            run_operations_post_org(
+                app_state,
+                server_handle,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, ())
+        },
+    )
+    .await;
+    measurement
+}
+
+/// Runs iterations of FHIR `POST /Organization` operations for the specified number of concurrent users,
+/// until the specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+///
+/// If the server can't be expunged between groups of iterations, the measurement is halted, skipping all of
+/// its remaining iterations.
+async fn run_operations_post_org(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
     let mut execution_duration: Duration = Duration::seconds(0);
     let mut iterations_attempted: u32 = 0;
-    let mut iterations_failed: u32 = 0;
 
     /* The iterations need to be split across groups, based on the resources (i.e. sample data) that each
-     * iteration will consume. */
-    let sample_orgs_count: u32 = u32::try_from(app_state.sample_data.iter_orgs().count()).unwrap();
+     * iteration will consume, which are used in the order given by the run's workload plan. */
+    let sample_orgs = workload_plan::order_by_plan(
+        &app_state.workload_plan.organizations,
+        app_state.sample_data.iter_orgs().collect(),
+        |org| &org.metadata.source_id,
+    );
+    let sample_orgs_count: u32 = u32::try_from(sample_orgs.len()).unwrap();
     assert!(sample_orgs_count > 0, "No sample orgs found.");
-    let groups = (app_state.config.iterations - 1) / sample_orgs_count + 1;
-    for group_index in 0..groups {
-        // How many iterations should be run for this group?
-        let iterations_remaining = app_state.config.iterations - iterations_attempted;
-        let group_iterations = std::cmp::min(sample_orgs_count, iterations_remaining);
-
-        // Wipe the server to start with a blank slate. Also allows for sample data to be re-used.
-        match server_handle.expunge_all_content(app_state).await {
-            Ok(_) => {}
-            Err(err) => {
-                warn!("FHIR server expunge: error: {}", err);
-                let completed = Utc::now();
-                let iterations_succeeded = app_state.config.iterations - iterations_failed;
-                let execution_duration = completed - started;
-                return ServerOperationMeasurement {
-                    concurrent_users,
-                    started,
-                    completed,
-                    execution_duration,
-                    iterations_failed,
-                    iterations_skipped: iterations_remaining,
-                    metrics: ServerOperationMetrics::new(
-                        execution_duration,
-                        iterations_succeeded,
-                        histogram,
-                    ),
-                };
-            }
+    let mut group_index: u32 = 0;
+    let circuit_breaker = recorder.circuit_breaker();
+    while !circuit_breaker.is_tripped()
+        && length.should_start_iteration(iterations_attempted, execution_duration)
+    {
+        // How many iterations might be run for this group?
+        let group_iterations = match length.iterations_remaining(iterations_attempted) {
+            Some(iterations_remaining) => std::cmp::min(sample_orgs_count, iterations_remaining),
+            None => sample_orgs_count,
         };
 
-        // Load the sample data that each iteration will consume an element of.
-        let sample_data = app_state
-            .sample_data
-            .iter_orgs()
-            .take(usize::try_from(group_iterations).unwrap());
-        let group_started = Utc::now();
+        /* Wipe the server to start with a blank slate. Also allows for sample data to be re-used. The
+         * measurement's interval windows are paused meanwhile, as no iterations can run. */
+        recorder.pause_intervals();
+        let expunged = server_handle.expunge_all_content(app_state).await;
+        recorder.resume_intervals();
+        if let Err(err) = expunged {
+            warn!("FHIR server expunge: error: {}", err);
+            recorder.halt(ServerOperationSkipReason::ExpungeFailed(format!("{}", err)));
+            return;
+        }
+
+        /* Load the sample data that each iteration will consume an element of. This is consumed lazily, so
+         * whether or not to start each iteration is only decided once there's room for it to run. */
+        let group_started = Instant::now();
+        let sample_data = sample_orgs
+            .iter()
+            .take(usize::try_from(group_iterations).unwrap())
+            .zip(iterations_attempted..)
+            .take_while(|(_, iteration)| {
+                !circuit_breaker.is_tripped()
+                    && length.should_start_iteration(
+                        *iteration,
+                        execution_duration + elapsed_since(group_started),
+                    )
+            })
+            .map(|(org, _)| org.clone());
 
         // Run the iterations for this group.
-        let group_results = benchmark_post_org_for_users_and_data(
+        let group_iterations_before = recorder.iterations();
+        benchmark_post_org_for_users_and_data(
             app_state,
             server_handle,
             concurrent_users,
+            compression,
             sample_data,
+            recorder,
         )
         .instrument(info_span!(
             "benchmark_post_org_for_users_and_data",
             concurrent_users,
+            ?compression,
             group_index,
-            group_count = groups,
             group_iterations
         ))
         .await;
 
-        let group_completed = Utc::now();
-        for operation_result in group_results {
-            match operation_result {
-                Ok(operation_success) => {
-                    let duration = operation_success.duration();
-                    let duration_millis = duration.num_milliseconds();
-                    histogram
-                        .record(duration_millis as u64)
-                        .expect("Histogram recording failed.");
-                }
-                Err(err) => {
-                    warn!("Operation '{}' failed: '{:?}", SERVER_OP_NAME_POST_ORG, err);
-                    iterations_failed += 1;
-                }
-            }
-        }
-        iterations_attempted += group_iterations;
-        execution_duration = execution_duration + (group_completed - group_started);
-    }
-
-    let completed = Utc::now();
-
-    let iterations_succeeded = app_state.config.iterations - iterations_failed;
-    let execution_duration = completed - started;
-    ServerOperationMeasurement {
-        concurrent_users,
-        started,
-        completed,
-        execution_duration,
-        iterations_failed,
-        iterations_skipped: 0,
-        metrics: ServerOperationMetrics::new(execution_duration, iterations_succeeded, histogram),
+        let group_duration = elapsed_since(group_started);
+        iterations_attempted += recorder.iterations() - group_iterations_before;
+        execution_duration = execution_duration + group_duration;
+        group_index += 1;
     }
 }
 
@@ -164,62 +190,36 @@ async fn benchmark_post_org_for_users(
 /// * `app_state`: the application's [AppState]
 /// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
 /// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
 /// * `sample_data`: the sample data to test against -- one iteration should be run for each element in it
-///
-/// Returns the number of iterations that failed.
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
 async fn benchmark_post_org_for_users_and_data(
     app_state: &AppState,
     server_handle: &dyn ServerHandle,
     concurrent_users: u32,
+    compression: Option<ContentEncoding>,
     sample_data: impl Iterator<Item = SampleResource>,
-) -> Vec<
-    std::result::Result<
-        ServerOperationIterationState<ServerOperationIterationSucceeded>,
-        ServerOperationIterationState<ServerOperationIterationFailed>,
-    >,
-> {
-    /*
-     * Build an iterator: One element for each iteration to run, run the operation for each iteration, and
-     * count the iterations that failed.
-     */
-    let operations = sample_data.into_iter().map(|org| async {
-        let operation_state = ServerOperationIterationState::new();
-        let operation = run_operation_post_org(server_handle, operation_state.clone(), org);
-        let operation = tokio::time::timeout(
-            app_state
-                .config
-                .operation_timeout
-                .to_std()
-                .expect("unable to convert Duration"),
-            operation,
-        );
-
-        // Having the timeout gives us a wrapped Result<Result ...>>. Un-nest them.
-        let result = operation.await;
-        let result = result.map_err(|err| {
-            operation_state
-                .completed()
-                .failed(eyre!("Operation timed out: '{}'", err))
-        });
-        result.and_then(|wrapped_result| wrapped_result)
-    });
-
-    /*
-     * Convert that iterator to a parallel stream, and use use `buffer_unordered(...)` to set it to run it only up to
-     * `concurrent_users`, at once.
-     */
-    let mut operations = futures::stream::iter(operations)
-        .buffer_unordered(usize::try_from(concurrent_users).unwrap());
-
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
     /*
-     * Kick off the execution of the stream, summing up all of the failures that are encountered.
+     * Run one iteration for each element of sample data, with `concurrent_users` virtual users, recording the
+     * outcome of each iteration as it completes.
      */
-    let mut results = vec![];
-    while let Some(n) = operations.next().await {
-        results.push(n);
-    }
-
-    results
+    run_virtual_users(
+        server_handle,
+        concurrent_users,
+        compression,
+        app_state.config.think_time.as_ref(),
+        sample_data,
+        |user, org| async move {
+            let operation_state = ServerOperationIterationState::new();
+            let operation =
+                run_operation_post_org(server_handle, &user, operation_state.clone(), org);
+            run_operation_with_timeout(app_state, operation_state, operation).await
+        },
+        |operation_result| recorder.record(operation_result),
+    )
+    .await;
 }
 
 /// Creates the URL to access a server's `/Organization` endpoint.
@@ -238,6 +238,7 @@ fn create_org_url(server_handle: &dyn ServerHandle) -> Url {
 ///
 /// Parameters:
 /// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `user`: the [VirtualUser] to run the operation as
 /// * `operation_state`: the initial state machine for this operation iteration
 /// * `org`: the sample `Organization` resource to test with
 ///
@@ -245,6 +246,7 @@ fn create_org_url(server_handle: &dyn ServerHandle) -> Url {
 /// success or failure.
 async fn run_operation_post_org(
     server_handle: &dyn ServerHandle,
+    user: &VirtualUser,
     operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
     org: SampleResource,
 ) -> std::result::Result<
@@ -268,41 +270,57 @@ async fn run_operation_post_org(
     let org_string = match serde_json::to_string(&org.resource_json) {
         Ok(org_string) => org_string,
         Err(err) => {
-            return Err(operation_state
-                .completed()
-                .failed(eyre!(format!("{}", err))));
+            return Err(operation_state.completed().failed(
+                ServerOperationFailureKind::VerificationFailure,
+                eyre!(err).wrap_err("Unable to serialize the sample Organization."),
+            ));
         }
     };
 
-    let client = match server_handle.client() {
+    let client = match user.client() {
         Ok(client) => client,
         Err(err) => {
             return Err(operation_state
                 .completed()
-                .failed(eyre!(format!("{}", err))));
+                .failed(ServerOperationFailureKind::ConnectionError, err));
         }
     };
 
-    let request_builder = server_handle
-        .request_builder(client, http::Method::POST, url.clone())
-        .header("Content-Type", "application/fhir+json")
-        .body(org_string);
+    let (request_builder, request_payload) = encode_request(
+        server_handle
+            .request_builder(client, http::Method::POST, url.clone())
+            .header("Content-Type", "application/fhir+json"),
+        user.compression(),
+        Some(org_string),
+    );
+    let sent = Instant::now();
     let response = request_builder
         .send()
         .instrument(trace_span!("POST request", %url))
         .await;
 
-    let operation_state = operation_state.completed();
-
     match response {
         Ok(response) => {
             let response_status = response.status();
-            if !response_status.is_success() {
-                let response_body = match response.text().await {
-                    Ok(response_body) => response_body,
-                    Err(err) => format!("Unable to retrieve response body due to error: '{}'", err),
-                };
 
+            // Always pull response body, to drain stream and release connection.
+            let ServerOperationResponse {
+                body: response_body,
+                payload,
+                phases,
+                received,
+            } = read_response_body(
+                response,
+                user.compression(),
+                user.connect_timer(),
+                request_payload,
+                sent,
+            )
+            .await
+            .unwrap_or_else(ServerOperationResponse::unreadable);
+            let operation_state = operation_state.completed_at(received);
+
+            if !response_status.is_success() {
                 let error = eyre!(
                     "The POST to '{}' failed for '{:?}', with status '{}' and body: '{}'",
                     &url,
@@ -310,13 +328,19 @@ async fn run_operation_post_org(
                     response_status,
                     response_body
                 );
-                let state = operation_state.failed(error);
+                let state = operation_state.failed_response(response_status, &response_body, error);
                 return Err(state);
             }
 
             // TODO more checks needed
-            Ok(operation_state.succeeded())
+            Ok(operation_state
+                .succeeded()
+                .with_payload(payload)
+                .with_phases(phases))
         }
-        Err(err) => Err(operation_state.failed(eyre!(format!("{}", err)))),
+        Err(err) => Err(operation_state.completed().failed(
+            ServerOperationFailureKind::from_request_error(&err),
+            eyre!(format!("{}", err)),
+        )),
     }
 }
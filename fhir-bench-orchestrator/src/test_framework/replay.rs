@@ -0,0 +1,1015 @@
+//! Contains the code to run the traffic replay operation (see [AppConfig.replay]), which replays a log of
+//! recorded FHIR requests against the server.
+
+use super::scenario::load_sample_data;
+use super::{
+    elapsed_since, measure_operation, run_measurements, run_operation_with_timeout,
+    ServerOperationFailureKind, ServerOperationIterationFailed, ServerOperationIterationStarting,
+    ServerOperationIterationState, ServerOperationIterationSucceeded,
+    ServerOperationMeasurementLength, ServerOperationMeasurementRecorder,
+};
+use crate::config::{ContentEncoding, ReplaySpeed};
+use crate::servers::ServerHandle;
+use crate::test_framework::payload::{encode_request, read_response_body, ServerOperationResponse};
+use crate::test_framework::users::{run_virtual_users, VirtualUser};
+use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
+use crate::AppState;
+use chrono::prelude::*;
+use eyre::{eyre, Context, Result};
+use lazy_static::lazy_static;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::{info_span, trace_span, Instrument};
+use url::Url;
+
+pub(super) static SERVER_OP_NAME_REPLAY: &str = "replay";
+
+/// The name of the step that records how late each request was sent, relative to its recorded time, when
+/// replaying at [ReplaySpeed::Recorded].
+static STEP_SCHEDULE_LAG: &str = "schedule_lag";
+
+/// The recorded request headers that are never replayed: those specific to the recorded connection, those
+/// that the replay sets itself (e.g. `Accept-Encoding`, per the measurement's [ContentEncoding]), and any
+/// credentials, which are replaced by the server's own.
+const HEADERS_NOT_REPLAYED: &[&str] = &[
+    "accept-encoding",
+    "authorization",
+    "connection",
+    "content-encoding",
+    "content-length",
+    "cookie",
+    "host",
+    "keep-alive",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// The FHIR R4 resource types, which (along with `metadata`, `_history`, and `$` operations) are what
+/// [relative_fhir_url] recognizes as the first segment of a URL's path relative to the server's base URL.
+const FHIR_RESOURCE_TYPES: &[&str] = &[
+    "Account",
+    "ActivityDefinition",
+    "AdverseEvent",
+    "AllergyIntolerance",
+    "Appointment",
+    "AppointmentResponse",
+    "AuditEvent",
+    "Basic",
+    "Binary",
+    "BiologicallyDerivedProduct",
+    "BodyStructure",
+    "Bundle",
+    "CapabilityStatement",
+    "CarePlan",
+    "CareTeam",
+    "CatalogEntry",
+    "ChargeItem",
+    "ChargeItemDefinition",
+    "Claim",
+    "ClaimResponse",
+    "ClinicalImpression",
+    "CodeSystem",
+    "Communication",
+    "CommunicationRequest",
+    "CompartmentDefinition",
+    "Composition",
+    "ConceptMap",
+    "Condition",
+    "Consent",
+    "Contract",
+    "Coverage",
+    "CoverageEligibilityRequest",
+    "CoverageEligibilityResponse",
+    "DetectedIssue",
+    "Device",
+    "DeviceDefinition",
+    "DeviceMetric",
+    "DeviceRequest",
+    "DeviceUseStatement",
+    "DiagnosticReport",
+    "DocumentManifest",
+    "DocumentReference",
+    "EffectEvidenceSynthesis",
+    "Encounter",
+    "Endpoint",
+    "EnrollmentRequest",
+    "EnrollmentResponse",
+    "EpisodeOfCare",
+    "EventDefinition",
+    "Evidence",
+    "EvidenceVariable",
+    "ExampleScenario",
+    "ExplanationOfBenefit",
+    "FamilyMemberHistory",
+    "Flag",
+    "Goal",
+    "GraphDefinition",
+    "Group",
+    "GuidanceResponse",
+    "HealthcareService",
+    "ImagingStudy",
+    "Immunization",
+    "ImmunizationEvaluation",
+    "ImmunizationRecommendation",
+    "ImplementationGuide",
+    "InsurancePlan",
+    "Invoice",
+    "Library",
+    "Linkage",
+    "List",
+    "Location",
+    "Measure",
+    "MeasureReport",
+    "Media",
+    "Medication",
+    "MedicationAdministration",
+    "MedicationDispense",
+    "MedicationKnowledge",
+    "MedicationRequest",
+    "MedicationStatement",
+    "MedicinalProduct",
+    "MedicinalProductAuthorization",
+    "MedicinalProductContraindication",
+    "MedicinalProductIndication",
+    "MedicinalProductIngredient",
+    "MedicinalProductInteraction",
+    "MedicinalProductManufactured",
+    "MedicinalProductPackaged",
+    "MedicinalProductPharmaceutical",
+    "MedicinalProductUndesirableEffect",
+    "MessageDefinition",
+    "MessageHeader",
+    "MolecularSequence",
+    "NamingSystem",
+    "NutritionOrder",
+    "Observation",
+    "ObservationDefinition",
+    "OperationDefinition",
+    "OperationOutcome",
+    "Organization",
+    "OrganizationAffiliation",
+    "Parameters",
+    "Patient",
+    "PaymentNotice",
+    "PaymentReconciliation",
+    "Person",
+    "PlanDefinition",
+    "Practitioner",
+    "PractitionerRole",
+    "Procedure",
+    "Provenance",
+    "Questionnaire",
+    "QuestionnaireResponse",
+    "RelatedPerson",
+    "RequestGroup",
+    "ResearchDefinition",
+    "ResearchElementDefinition",
+    "ResearchStudy",
+    "ResearchSubject",
+    "RiskAssessment",
+    "RiskEvidenceSynthesis",
+    "Schedule",
+    "SearchParameter",
+    "ServiceRequest",
+    "Slot",
+    "Specimen",
+    "SpecimenDefinition",
+    "StructureDefinition",
+    "StructureMap",
+    "Subscription",
+    "Substance",
+    "SubstanceNucleicAcid",
+    "SubstancePolymer",
+    "SubstanceProtein",
+    "SubstanceReferenceInformation",
+    "SubstanceSourceMaterial",
+    "SubstanceSpecification",
+    "SupplyDelivery",
+    "SupplyRequest",
+    "Task",
+    "TerminologyCapabilities",
+    "TestReport",
+    "TestScript",
+    "ValueSet",
+    "VerificationResult",
+    "VisionPrescription",
+];
+
+lazy_static! {
+    /// A placeholder FHIR server base URL, which each [RecordedRequest]'s URL is checked against when it's
+    /// parsed, so that it can be joined to any server's base URL.
+    static ref PLACEHOLDER_BASE_URL: Url =
+        Url::parse("http://localhost/fhir/").expect("Error parsing URL.");
+}
+
+/// A single recorded FHIR request, to be replayed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    /// The request's HTTP method, e.g. `GET`.
+    method: http::Method,
+
+    /// The request's URL, relative to the FHIR server's base URL, e.g. `Patient?name=Smith`.
+    url: String,
+
+    /// The request's headers, other than the [HEADERS_NOT_REPLAYED].
+    headers: HeaderMap,
+
+    /// The request's body, if any.
+    body: Option<String>,
+
+    /// When the request was sent, relative to the first request in the recording.
+    offset: Duration,
+
+    /// The HTTP status of the recorded response, if known.
+    status: Option<u16>,
+}
+
+impl RecordedRequest {
+    /// Constructs a new [RecordedRequest], verifying that it can be replayed.
+    ///
+    /// Parameters:
+    /// * `method`: the request's recorded HTTP method
+    /// * `url`: the request's recorded URL, which will be made relative per [relative_fhir_url]
+    /// * `headers`: the request's recorded headers, as name-value pairs, which will be filtered per
+    ///   [replayed_headers]
+    /// * `body`: the request's body, if any
+    /// * `offset`: when the request was sent, relative to the first request in the recording
+    /// * `status`: the HTTP status of the recorded response, if known
+    ///
+    /// Returns the new [RecordedRequest], or an error if its method, URL, or headers were invalid.
+    fn new<I>(
+        method: &str,
+        url: &str,
+        headers: I,
+        body: Option<String>,
+        offset: Duration,
+        status: Option<u16>,
+    ) -> Result<RecordedRequest>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let method = http::Method::from_bytes(method.as_bytes())
+            .with_context(|| format!("Invalid method '{}'.", method))?;
+        let url = relative_fhir_url(url);
+        PLACEHOLDER_BASE_URL
+            .join(&url)
+            .with_context(|| format!("Invalid URL '{}'.", url))?;
+        let mut header_map = HeaderMap::new();
+        for (name, value) in replayed_headers(headers) {
+            header_map.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .with_context(|| format!("Invalid header name '{}'.", name))?,
+                HeaderValue::from_str(&value)
+                    .with_context(|| format!("Invalid value for header '{}'.", name))?,
+            );
+        }
+
+        Ok(RecordedRequest {
+            method,
+            url,
+            headers: header_map,
+            body,
+            offset,
+            status,
+        })
+    }
+}
+
+/// A single line of an NDJSON recording, as parsed by [parse_ndjson].
+#[derive(Deserialize)]
+struct NdjsonRequest {
+    /// The request's HTTP method.
+    method: String,
+
+    /// The request's URL, which will be made relative per [relative_fhir_url].
+    url: String,
+
+    /// The request's headers.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+
+    /// The request's body, as either a string or (for convenience) the JSON itself.
+    #[serde(default)]
+    body: serde_json::Value,
+
+    /// When the request was sent, in milliseconds since the start of the recording.
+    #[serde(default)]
+    timestamp_ms: u64,
+
+    /// The HTTP status of the recorded response, if known.
+    #[serde(default)]
+    status: Option<u16>,
+}
+
+/// Verifies and benchmarks the traffic replay operation, after loading the sample data into the server for
+/// the recorded requests to run against.
+pub async fn benchmark_replay(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> ServerOperationLog {
+    let replay = app_state
+        .config
+        .replay
+        .as_ref()
+        .expect("Replay operation run without replay config.");
+
+    let inputs = load_sample_data(app_state, server_handle)
+        .instrument(info_span!(
+            "load_sample_data",
+            operation = SERVER_OP_NAME_REPLAY
+        ))
+        .await;
+    if let Err(err) = inputs {
+        let mut server_op_log = ServerOperationLog::new(SERVER_OP_NAME_REPLAY.into());
+        server_op_log.errors.push(format!("{:?}", err));
+        return server_op_log;
+    }
+
+    run_measurements(
+        app_state,
+        SERVER_OP_NAME_REPLAY,
+        |concurrent_users, compression| {
+            benchmark_replay_for_users(
+                app_state,
+                server_handle,
+                replay.speed,
+                &app_state.recorded_requests,
+                concurrent_users,
+                compression,
+            )
+        },
+    )
+    .await
+}
+
+/// Loads the recorded requests from the specified file: an HTTP Archive if its extension is `.har`, or
+/// NDJSON otherwise.
+///
+/// Parameters:
+/// * `path`: the recording file to load
+///
+/// Returns the [RecordedRequest]s, ordered by when they were sent, or an error if there weren't any, or if any
+/// of them couldn't be replayed.
+pub fn load_recorded_requests(path: &Path) -> Result<Vec<RecordedRequest>> {
+    let recording = std::fs::read_to_string(path)
+        .with_context(|| format!("Unable to read replay file '{:?}'.", path))?;
+    let is_har = path
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("har"))
+        .unwrap_or(false);
+    let mut recorded_requests = if is_har {
+        parse_har(&recording)
+    } else {
+        parse_ndjson(&recording)
+    }
+    .with_context(|| format!("Unable to parse replay file '{:?}'.", path))?;
+
+    if recorded_requests.is_empty() {
+        return Err(eyre!("No recorded requests were found in '{:?}'.", path));
+    }
+    recorded_requests.sort_by_key(|recorded_request| recorded_request.offset);
+    Ok(recorded_requests)
+}
+
+/// Parses an NDJSON recording, where each line is a JSON object with `method`, `url`, and (optionally)
+/// `headers` (an object), `body` (a string or JSON), `timestamp_ms` (relative to the start of the
+/// recording), and `status` (of the recorded response) fields.
+///
+/// Parameters:
+/// * `recording`: the NDJSON to parse
+fn parse_ndjson(recording: &str) -> Result<Vec<RecordedRequest>> {
+    let mut recorded_requests = vec![];
+    for (line_index, line) in recording.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: NdjsonRequest = serde_json::from_str(line)
+            .with_context(|| format!("Unable to parse line {}.", line_index + 1))?;
+        let body = match request.body {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(body) => Some(body),
+            body => Some(body.to_string()),
+        };
+        recorded_requests.push(
+            RecordedRequest::new(
+                &request.method,
+                &request.url,
+                request.headers,
+                body,
+                Duration::from_millis(request.timestamp_ms),
+                request.status,
+            )
+            .with_context(|| format!("Invalid request on line {}.", line_index + 1))?,
+        );
+    }
+
+    Ok(recorded_requests)
+}
+
+/// Parses an HTTP Archive (HAR) recording, using each of its `log.entries`' `request` and the `status` of its
+/// `response`.
+///
+/// Parameters:
+/// * `recording`: the HAR JSON to parse
+fn parse_har(recording: &str) -> Result<Vec<RecordedRequest>> {
+    let har: serde_json::Value = serde_json::from_str(recording)?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or_else(|| eyre!("The HAR did not contain any 'log.entries'."))?;
+
+    let mut started_times = vec![];
+    let mut recorded_requests = vec![];
+    for (entry_index, entry) in entries.iter().enumerate() {
+        let invalid = |field: &str| eyre!("HAR entry {} had no valid '{}'.", entry_index, field);
+        let request = &entry["request"];
+        let started = entry["startedDateTime"]
+            .as_str()
+            .and_then(|started| DateTime::parse_from_rfc3339(started).ok())
+            .ok_or_else(|| invalid("startedDateTime"))?;
+        let method = request["method"]
+            .as_str()
+            .ok_or_else(|| invalid("request.method"))?;
+        let url = request["url"]
+            .as_str()
+            .ok_or_else(|| invalid("request.url"))?;
+        let headers: Vec<(String, String)> = request["headers"]
+            .as_array()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|header| {
+                        Some((
+                            header["name"].as_str()?.to_string(),
+                            header["value"].as_str()?.to_string(),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        started_times.push(started);
+        recorded_requests.push(
+            RecordedRequest::new(
+                method,
+                url,
+                headers,
+                request["postData"]["text"].as_str().map(str::to_string),
+                Duration::default(),
+                entry["response"]["status"]
+                    .as_u64()
+                    .and_then(|status| u16::try_from(status).ok()),
+            )
+            .with_context(|| format!("Invalid request in HAR entry {}.", entry_index))?,
+        );
+    }
+
+    // Make the times relative to the first request.
+    if let Some(first_started) = started_times.iter().min().cloned() {
+        for (recorded_request, started) in recorded_requests.iter_mut().zip(started_times) {
+            recorded_request.offset = (started - first_started).to_std().unwrap_or_default();
+        }
+    }
+
+    Ok(recorded_requests)
+}
+
+/// Returns the specified recorded URL relative to the FHIR server's base URL, so it can be replayed against
+/// any server. Any scheme, host, and base path are dropped: everything before the first path segment that
+/// starts a FHIR REST URL (one of the [FHIR_RESOURCE_TYPES], `metadata`, `_history`, or a `$` operation),
+/// e.g. `https://example.com/fhir/r4/Patient?name=Smith` becomes `Patient?name=Smith`.
+///
+/// Parameters:
+/// * `url`: the recorded URL, which may be absolute or relative
+fn relative_fhir_url(url: &str) -> String {
+    let path_and_query = match Url::parse(url) {
+        Ok(url) => match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        },
+        Err(_) => url.to_string(),
+    };
+    let (path, query) = match path_and_query.find('?') {
+        Some(separator) => path_and_query.split_at(separator),
+        None => (path_and_query.as_str(), ""),
+    };
+
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let fhir_start = segments
+        .iter()
+        .position(|segment| {
+            FHIR_RESOURCE_TYPES.contains(segment)
+                || segment.starts_with('$')
+                || *segment == "metadata"
+                || *segment == "_history"
+        })
+        .unwrap_or(segments.len());
+    format!("{}{}", segments[fhir_start..].join("/"), query)
+}
+
+/// Returns the specified recorded headers, other than the [HEADERS_NOT_REPLAYED] (and any HTTP/2
+/// pseudo-headers, e.g. `:authority`).
+///
+/// Parameters:
+/// * `headers`: the recorded headers, as name-value pairs
+fn replayed_headers<I>(headers: I) -> Vec<(String, String)>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    headers
+        .into_iter()
+        .filter(|(name, _)| {
+            let name = name.to_ascii_lowercase();
+            !name.starts_with(':') && !HEADERS_NOT_REPLAYED.contains(&name.as_str())
+        })
+        .collect()
+}
+
+/// Verifies and benchmarks the traffic replay operation for the specified number of concurrent users.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `speed`: the [ReplaySpeed] to replay the requests at
+/// * `recorded_requests`: the [RecordedRequest]s to replay, which will be cycled through
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+///
+/// Returns a [ServerOperationMeasurement] with the results.
+#[tracing::instrument(level = "info", skip(app_state, server_handle, recorded_requests))]
+async fn benchmark_replay_for_users(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    speed: ReplaySpeed,
+    recorded_requests: &[RecordedRequest],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+) -> ServerOperationMeasurement {
+    let (measurement, ()) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || ServerOperationMeasurementRecorder::new(&app_state.config, SERVER_OP_NAME_REPLAY),
+        |length, mut recorder| async move {
+            run_operations_replay(
+                app_state,
+                server_handle,
+                speed,
+                recorded_requests,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, ())
+        },
+    )
+    .await;
+    measurement
+}
+
+/// Replays the recorded requests for the specified number of concurrent users, one per iteration (looping
+/// through the recording as needed), until the specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `speed`: the [ReplaySpeed] to replay the requests at
+/// * `recorded_requests`: the [RecordedRequest]s to replay, which will be cycled through
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+#[allow(clippy::too_many_arguments)]
+async fn run_operations_replay(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    speed: ReplaySpeed,
+    recorded_requests: &[RecordedRequest],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
+    /*
+     * Build an iterator: One element (the [RecordedRequest], and when to send it, if replaying at the
+     * recorded speed) for each iteration to run. The iterator is lazy, so whether or not to start each
+     * iteration is only decided once a virtual user is ready to run it.
+     */
+    let recording_duration = loop_duration(recorded_requests);
+    let circuit_breaker = recorder.circuit_breaker();
+    let started = Instant::now();
+    let iterations = (0..)
+        .map(|iteration| {
+            let index = usize::try_from(iteration).unwrap();
+            let recorded_request = &recorded_requests[index % recorded_requests.len()];
+            let send_at = match speed {
+                ReplaySpeed::Recorded => {
+                    let loops = u32::try_from(index / recorded_requests.len()).unwrap();
+                    Some(started + recording_duration * loops + recorded_request.offset)
+                }
+                ReplaySpeed::AsFastAsPossible => None,
+            };
+            (iteration, recorded_request, send_at)
+        })
+        .take_while(|(iteration, _, send_at)| {
+            // A scheduled request is only part of the measurement if it's scheduled to be sent during it.
+            let elapsed = match send_at {
+                Some(send_at) => chrono::Duration::from_std(*send_at - started)
+                    .expect("Unable to convert Duration."),
+                None => elapsed_since(started),
+            };
+            !circuit_breaker.is_tripped() && length.should_start_iteration(*iteration, elapsed)
+        })
+        .map(|(_, recorded_request, send_at)| (recorded_request, send_at));
+
+    /*
+     * Run those iterations with `concurrent_users` virtual users, recording the outcome of each iteration.
+     * When replaying at the recorded speed, the recorded times already include any client think time.
+     */
+    let think_time = match speed {
+        ReplaySpeed::Recorded => None,
+        ReplaySpeed::AsFastAsPossible => app_state.config.think_time.as_ref(),
+    };
+    run_virtual_users(
+        server_handle,
+        concurrent_users,
+        compression,
+        think_time,
+        iterations,
+        |user, (recorded_request, send_at)| async move {
+            /*
+             * Wait until the request's recorded time, if needed. Its latency is then measured from that
+             * scheduled time, rather than from when it's actually sent, so that a server slow enough to
+             * delay the following requests can't hide that delay (i.e. coordinated omission).
+             */
+            let (operation_state, schedule_lag) = match send_at {
+                Some(send_at) => {
+                    tokio::time::sleep_until(send_at.into()).await;
+                    (
+                        ServerOperationIterationState::scheduled(send_at),
+                        Some(send_at.elapsed()),
+                    )
+                }
+                None => (ServerOperationIterationState::new(), None),
+            };
+            let operation = run_operation_replay(
+                server_handle,
+                &user,
+                recorded_request,
+                schedule_lag,
+                operation_state.clone(),
+            );
+            run_operation_with_timeout(app_state, operation_state, operation).await
+        },
+        |operation_result| recorder.record(operation_result),
+    )
+    .await;
+}
+
+/// Returns how long each loop through the specified recording lasts, when replaying it at
+/// [ReplaySpeed::Recorded]: the last request's offset, plus the mean gap between the recorded requests, so
+/// that the next loop's first request isn't sent at the same time as the previous loop's last one.
+///
+/// Parameters:
+/// * `recorded_requests`: the [RecordedRequest]s that will be replayed
+fn loop_duration(recorded_requests: &[RecordedRequest]) -> Duration {
+    let last_offset = recorded_requests
+        .last()
+        .map(|recorded_request| recorded_request.offset)
+        .unwrap_or_default();
+    match u32::try_from(recorded_requests.len()) {
+        Ok(len) if len > 1 => last_offset + last_offset / (len - 1),
+        _ => last_offset,
+    }
+}
+
+/// Runs a single iteration of the traffic replay operation: replays the specified [RecordedRequest] and
+/// verifies that the server's response had the same class of status as the recorded one (or, if that wasn't
+/// recorded, a successful one).
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `user`: the [VirtualUser] to run the operation as
+/// * `recorded_request`: the [RecordedRequest] to replay
+/// * `schedule_lag`: how late the request is being sent, relative to its recorded time, if replaying at
+///   [ReplaySpeed::Recorded]
+/// * `operation_state`: the initial state machine for this operation iteration
+///
+/// Returns the final [ServerOperationIterationState] containing information about the operation's
+/// success or failure.
+async fn run_operation_replay(
+    server_handle: &dyn ServerHandle,
+    user: &VirtualUser,
+    recorded_request: &RecordedRequest,
+    schedule_lag: Option<Duration>,
+    operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
+) -> std::result::Result<
+    ServerOperationIterationState<ServerOperationIterationSucceeded>,
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+> {
+    let failed = |kind: ServerOperationFailureKind, error: eyre::Error| {
+        operation_state.clone().completed().failed(kind, error)
+    };
+
+    let url = server_handle
+        .base_url()
+        .join(&recorded_request.url)
+        .expect("Error parsing URL.");
+    let client = user
+        .client()
+        .map_err(|err| failed(ServerOperationFailureKind::ConnectionError, err))?;
+
+    let (request_builder, request_payload) = encode_request(
+        server_handle
+            .request_builder(client, recorded_request.method.clone(), url.clone())
+            .headers(build_headers(recorded_request)),
+        user.compression(),
+        recorded_request.body.clone(),
+    );
+    let sent = Instant::now();
+    let response = request_builder
+        .send()
+        .instrument(trace_span!("replayed request", method = %recorded_request.method, %url))
+        .await;
+
+    match response {
+        Ok(response) => {
+            let response_status = response.status();
+
+            // Always pull response body, to drain stream and release connection.
+            let ServerOperationResponse {
+                body: response_body,
+                payload,
+                phases,
+                received,
+            } = read_response_body(
+                response,
+                user.compression(),
+                user.connect_timer(),
+                request_payload,
+                sent,
+            )
+            .await
+            .unwrap_or_else(ServerOperationResponse::unreadable);
+            let operation_state = operation_state.completed_at(received);
+
+            let status_expected = match recorded_request.status {
+                Some(status) => response_status.as_u16() / 100 == status / 100,
+                None => response_status.is_success(),
+            };
+            if !status_expected {
+                let error = eyre!(
+                    "The replayed {} to '{}' returned status '{}' (recorded: '{:?}') and body: '{}'",
+                    recorded_request.method,
+                    &url,
+                    response_status,
+                    recorded_request.status,
+                    response_body
+                );
+                return Err(if response_status.is_success() {
+                    operation_state.failed(ServerOperationFailureKind::VerificationFailure, error)
+                } else {
+                    operation_state.failed_response(response_status, &response_body, error)
+                });
+            }
+
+            let steps = schedule_lag
+                .map(|schedule_lag| vec![(STEP_SCHEDULE_LAG.to_string(), schedule_lag)])
+                .unwrap_or_default();
+            Ok(operation_state
+                .succeeded_with_steps(steps)
+                .with_payload(payload)
+                .with_phases(phases))
+        }
+        Err(err) => Err(operation_state.completed().failed(
+            ServerOperationFailureKind::from_request_error(&err),
+            eyre!(format!("HTTP request failed: '{}'", err)),
+        )),
+    }
+}
+
+/// Builds the HTTP headers for the specified [RecordedRequest]: its recorded headers, defaulting `Accept`
+/// (and `Content-Type`, if it has a body) to `application/fhir+json` if they weren't recorded.
+///
+/// Parameters:
+/// * `recorded_request`: the [RecordedRequest] to build the headers for
+///
+/// Returns the [HeaderMap] to send.
+fn build_headers(recorded_request: &RecordedRequest) -> HeaderMap {
+    let mut headers = recorded_request.headers.clone();
+    let fhir_json = HeaderValue::from_static("application/fhir+json");
+    headers
+        .entry(http::header::ACCEPT)
+        .or_insert_with(|| fhir_json.clone());
+    if recorded_request.body.is_some() {
+        headers
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert(fhir_json);
+    }
+
+    headers
+}
+
+/// Unit tests for the traffic replay operation.
+#[cfg(test)]
+mod tests {
+    use super::RecordedRequest;
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Returns a [HeaderMap] with the specified headers.
+    ///
+    /// Parameters:
+    /// * `headers`: the headers to include, as name-value pairs
+    fn header_map(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    /// Verifies that [super::relative_fhir_url] strips everything up to the FHIR REST URL.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn relative_fhir_url() {
+        assert_eq!(
+            "Patient?name=Smith",
+            super::relative_fhir_url("https://example.com/fhir/r4/Patient?name=Smith")
+        );
+        assert_eq!(
+            "Patient/123",
+            super::relative_fhir_url("/baseR4/Patient/123")
+        );
+        assert_eq!("metadata", super::relative_fhir_url("metadata"));
+        assert_eq!(
+            "$export?_type=Patient",
+            super::relative_fhir_url("http://localhost:8080/fhir/$export?_type=Patient")
+        );
+        assert_eq!("", super::relative_fhir_url("http://localhost:8080/fhir/"));
+        assert_eq!(
+            "Patient/123",
+            super::relative_fhir_url("http://localhost:8080/FHIR/Patient/123")
+        );
+        assert_eq!(
+            "Patient?_id=123",
+            super::relative_fhir_url("/hapi_fhir/Patient?_id=123")
+        );
+        assert_eq!(
+            "_history?_count=10",
+            super::relative_fhir_url("/R4/_history?_count=10")
+        );
+    }
+
+    /// Verifies that [super::loop_duration] leaves a gap between the end of one loop and the start of the
+    /// next.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn loop_duration() {
+        let recorded_request = |offset_ms| RecordedRequest {
+            method: http::Method::GET,
+            url: "metadata".into(),
+            headers: HeaderMap::new(),
+            body: None,
+            offset: Duration::from_millis(offset_ms),
+            status: None,
+        };
+
+        assert_eq!(Duration::from_millis(0), super::loop_duration(&[]));
+        assert_eq!(
+            Duration::from_millis(0),
+            super::loop_duration(&[recorded_request(0)])
+        );
+        assert_eq!(
+            Duration::from_millis(1500),
+            super::loop_duration(&[
+                recorded_request(0),
+                recorded_request(200),
+                recorded_request(1000)
+            ])
+        );
+    }
+
+    /// Verifies that [super::parse_ndjson] parses recordings as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn parse_ndjson() {
+        let recording = [
+            json!({
+                "method": "GET",
+                "url": "/fhir/Patient?name=Smith",
+                "headers": { "Host": "example.com", "Prefer": "handling=strict" },
+                "timestamp_ms": 0,
+                "status": 200
+            }),
+            json!({
+                "method": "POST",
+                "url": "Observation",
+                "body": { "resourceType": "Observation" },
+                "timestamp_ms": 1500
+            }),
+        ]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+        let recorded_requests = super::parse_ndjson(&recording).expect("Unable to parse NDJSON.");
+        assert_eq!(
+            vec![
+                RecordedRequest {
+                    method: http::Method::GET,
+                    url: "Patient?name=Smith".into(),
+                    headers: header_map(&[("prefer", "handling=strict")]),
+                    body: None,
+                    offset: Duration::from_millis(0),
+                    status: Some(200),
+                },
+                RecordedRequest {
+                    method: http::Method::POST,
+                    url: "Observation".into(),
+                    headers: HeaderMap::new(),
+                    body: Some(r#"{"resourceType":"Observation"}"#.into()),
+                    offset: Duration::from_millis(1500),
+                    status: None,
+                },
+            ],
+            recorded_requests
+        );
+
+        // Requests that can't be replayed are rejected up front.
+        for invalid in [
+            json!({ "method": "GET /", "url": "metadata" }),
+            json!({ "method": "GET", "url": "metadata", "headers": { "Bad Name": "value" } }),
+            json!({ "method": "GET", "url": "metadata", "headers": { "Prefer": "a\nb" } }),
+        ] {
+            assert!(super::parse_ndjson(&invalid.to_string()).is_err());
+        }
+    }
+
+    /// Verifies that [super::parse_har] parses recordings as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn parse_har() {
+        let recording = serde_json::json!({
+            "log": {
+                "entries": [
+                    {
+                        "startedDateTime": "2021-06-01T12:00:02.250Z",
+                        "request": {
+                            "method": "PUT",
+                            "url": "https://example.com/fhir/Patient/123",
+                            "headers": [
+                                { "name": "Authorization", "value": "Bearer secret" },
+                                { "name": "If-Match", "value": "W/\"1\"" },
+                            ],
+                            "postData": { "text": "{\"resourceType\":\"Patient\"}" },
+                        },
+                        "response": { "status": 200 },
+                    },
+                    {
+                        "startedDateTime": "2021-06-01T12:00:00Z",
+                        "request": {
+                            "method": "GET",
+                            "url": "https://example.com/fhir/metadata",
+                            "headers": [{ "name": ":authority", "value": "example.com" }],
+                        },
+                        "response": { "status": 200 },
+                    },
+                ],
+            },
+        });
+        let recorded_requests =
+            super::parse_har(&recording.to_string()).expect("Unable to parse HAR.");
+        assert_eq!(
+            vec![
+                RecordedRequest {
+                    method: http::Method::PUT,
+                    url: "Patient/123".into(),
+                    headers: header_map(&[("if-match", "W/\"1\"")]),
+                    body: Some(r#"{"resourceType":"Patient"}"#.into()),
+                    offset: Duration::from_millis(2250),
+                    status: Some(200),
+                },
+                RecordedRequest {
+                    method: http::Method::GET,
+                    url: "metadata".into(),
+                    headers: HeaderMap::new(),
+                    body: None,
+                    offset: Duration::from_millis(0),
+                    status: Some(200),
+                },
+            ],
+            recorded_requests
+        );
+    }
+}
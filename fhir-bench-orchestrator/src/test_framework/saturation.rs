@@ -0,0 +1,350 @@
+//! Provides the saturation search mode, which ramps up the load on an operation until it no longer meets
+//! the configured service level objective (SLO), to find the highest load that it can sustain.
+
+use super::{ServerOperationMeasurement, ServerOperationSaturation, ServerOperationSaturationStop};
+use crate::config::SaturationConfig;
+use std::future::Future;
+use tracing::info;
+
+/// Runs measurements at increasing levels of concurrency, until one of them fails to meet the SLO in the
+/// specified [SaturationConfig], and then bisects between the highest passing and lowest failing levels, to
+/// find the knee to within the configured [SaturationConfig.resolution].
+///
+/// Parameters:
+/// * `saturation_config`: the [SaturationConfig] specifying the SLO and how to ramp up load
+/// * `measure`: runs and returns a [ServerOperationMeasurement] for the operation at the specified number of
+///   concurrent users
+/// * `measurements`: each [ServerOperationMeasurement] that is run will be added to this
+///
+/// Returns a [ServerOperationSaturation] detailing the highest load that met the SLO.
+pub async fn search_for_saturation<F, Fut>(
+    saturation_config: &SaturationConfig,
+    measure: &F,
+    measurements: &mut Vec<ServerOperationMeasurement>,
+) -> ServerOperationSaturation
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = ServerOperationMeasurement>,
+{
+    let mut saturation = ServerOperationSaturation {
+        max_concurrent_users: None,
+        max_throughput_per_second: None,
+        stop_reason: ServerOperationSaturationStop::MaxConcurrencyReached(),
+    };
+
+    // Ramp up the load geometrically (always by at least one user), until a step fails.
+    let mut concurrent_users: u32 = 1;
+    let mut first_failed: Option<u32> = None;
+    while concurrent_users <= saturation_config.max_concurrency {
+        if !run_step(
+            saturation_config,
+            measure,
+            concurrent_users,
+            measurements,
+            &mut saturation,
+        )
+        .await
+        {
+            first_failed = Some(concurrent_users);
+            break;
+        }
+        if concurrent_users == saturation_config.max_concurrency {
+            break;
+        }
+
+        let next_concurrent_users = (f64::from(concurrent_users) * saturation_config.step_factor)
+            .ceil()
+            .min(f64::from(saturation_config.max_concurrency))
+            as u32;
+        concurrent_users = std::cmp::max(concurrent_users.saturating_add(1), next_concurrent_users)
+            .min(saturation_config.max_concurrency);
+    }
+
+    // Bisect between the highest passing and lowest failing steps, to narrow down where the knee is.
+    if let (Some(mut passed), Some(mut failed)) = (saturation.max_concurrent_users, first_failed) {
+        while failed - passed > saturation_config.resolution.max(1) {
+            let concurrent_users = passed + (failed - passed) / 2;
+            if run_step(
+                saturation_config,
+                measure,
+                concurrent_users,
+                measurements,
+                &mut saturation,
+            )
+            .await
+            {
+                passed = concurrent_users;
+            } else {
+                failed = concurrent_users;
+            }
+        }
+    }
+
+    saturation
+}
+
+/// Runs a single step of a saturation search, recording it in the [ServerOperationSaturation] if it met the
+/// SLO, or as the reason to stop if it didn't.
+///
+/// Parameters:
+/// * `saturation_config`: the [SaturationConfig] specifying the SLO
+/// * `measure`: runs and returns a [ServerOperationMeasurement] for the operation at the specified number of
+///   concurrent users
+/// * `concurrent_users`: the number of concurrent users to measure the operation at
+/// * `measurements`: the step's [ServerOperationMeasurement] will be added to this
+/// * `saturation`: the [ServerOperationSaturation] for the search so far
+///
+/// Returns `true` if the step met the SLO, or `false` if it didn't.
+async fn run_step<F, Fut>(
+    saturation_config: &SaturationConfig,
+    measure: &F,
+    concurrent_users: u32,
+    measurements: &mut Vec<ServerOperationMeasurement>,
+    saturation: &mut ServerOperationSaturation,
+) -> bool
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = ServerOperationMeasurement>,
+{
+    let measurement = measure(concurrent_users).await;
+    let failure = check_slo(saturation_config, &measurement);
+    let throughput_per_second = measurement.metrics.throughput_per_second;
+    measurements.push(measurement);
+
+    match failure {
+        Some(failure) => {
+            info!(
+                concurrent_users,
+                ?failure,
+                "Saturation search: SLO not met."
+            );
+            saturation.stop_reason = failure;
+            false
+        }
+        None => {
+            // Bisection only measures levels above the highest one that's passed so far.
+            saturation.max_concurrent_users = Some(concurrent_users);
+            saturation.max_throughput_per_second = Some(throughput_per_second);
+            true
+        }
+    }
+}
+
+/// Checks whether or not the specified [ServerOperationMeasurement] met the SLO.
+///
+/// Parameters:
+/// * `saturation_config`: the [SaturationConfig] specifying the SLO
+/// * `measurement`: the [ServerOperationMeasurement] to check
+///
+/// Returns `None` if the SLO was met, or the [ServerOperationSaturationStop] detailing why not.
+fn check_slo(
+    saturation_config: &SaturationConfig,
+    measurement: &ServerOperationMeasurement,
+) -> Option<ServerOperationSaturationStop> {
+    if measurement.iterations_skipped > 0 {
+        return Some(ServerOperationSaturationStop::IterationsSkipped(
+            measurement.iterations_skipped,
+        ));
+    }
+
+    let error_rate = if measurement.iterations == 0 {
+        1.0
+    } else {
+        f64::from(measurement.iterations_failed) / f64::from(measurement.iterations)
+    };
+    if error_rate > saturation_config.max_error_rate {
+        return Some(ServerOperationSaturationStop::ErrorRateExceeded(error_rate));
+    }
+
+    let latency_p99 = measurement.metrics.latency_corrected_millis_p99;
+    let slo_p99_millis = saturation_config
+        .slo_p99
+        .num_microseconds()
+        .map(|micros| micros as f64 / 1_000.0)
+        .unwrap_or(f64::MAX);
+    if latency_p99 > slo_p99_millis {
+        return Some(ServerOperationSaturationStop::LatencyExceeded(latency_p99));
+    }
+
+    None
+}
+
+/// Unit tests for the saturation search.
+#[cfg(test)]
+mod tests {
+    use crate::config::SaturationConfig;
+    use crate::test_framework::payload::ServerOperationPayloadRecorder;
+    use crate::test_framework::{
+        ServerOperationFailures, ServerOperationIntervals, ServerOperationMeasurement,
+        ServerOperationMetrics, ServerOperationSaturationStop,
+    };
+    use chrono::prelude::*;
+    use chrono::Duration;
+    use hdrhistogram::Histogram;
+
+    /// Returns a fake [ServerOperationMeasurement] where every iteration took the specified latency (which
+    /// is recorded in microseconds, as the real measurements are).
+    fn fake_measurement(
+        concurrent_users: u32,
+        latency_millis: u64,
+        iterations_failed: u32,
+    ) -> ServerOperationMeasurement {
+        let mut histogram = Histogram::<u64>::new(3).expect("Error creating histogram.");
+        histogram
+            .record_n(latency_millis * 1_000, 100)
+            .expect("Error recording into histogram.");
+        ServerOperationMeasurement {
+            concurrent_users,
+            compression: None,
+            trial: 0,
+            started: Utc.ymd(2020, 1, 1).and_hms(15, 0, 0),
+            completed: Utc.ymd(2020, 1, 1).and_hms(15, 0, 1),
+            execution_duration: Duration::seconds(1),
+            iterations: 100,
+            iterations_failed,
+            iterations_skipped: 0,
+            skip_reason: None,
+            failures: ServerOperationFailures::default(),
+            warmup: None,
+            steps: vec![],
+            operations: vec![],
+            contention: None,
+            metrics: ServerOperationMetrics::new(
+                Duration::seconds(1),
+                100 - iterations_failed,
+                histogram.clone(),
+                histogram,
+                ServerOperationIntervals {
+                    window: Duration::seconds(1),
+                    intervals: vec![],
+                    latency_interval_log: "".into(),
+                },
+                ServerOperationPayloadRecorder::new().into_metrics(),
+                vec![],
+            ),
+        }
+    }
+
+    /// Verifies that [super::search_for_saturation] ramps up until a concurrency level exceeds the latency
+    /// SLO, and then bisects to find the highest level that doesn't.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn search_for_saturation_latency() {
+        let saturation_config = SaturationConfig {
+            slo_p99: Duration::milliseconds(50),
+            max_error_rate: 0.01,
+            step_factor: 2.0,
+            max_concurrency: 1024,
+            resolution: 1,
+        };
+        let mut measurements = vec![];
+        let saturation = super::search_for_saturation(
+            &saturation_config,
+            &|concurrent_users| async move {
+                fake_measurement(concurrent_users, u64::from(concurrent_users) * 10, 0)
+            },
+            &mut measurements,
+        )
+        .await;
+
+        assert_eq!(Some(4), saturation.max_concurrent_users);
+        assert!(matches!(
+            saturation.stop_reason,
+            ServerOperationSaturationStop::LatencyExceeded(_)
+        ));
+        let concurrency_levels: Vec<u32> =
+            measurements.iter().map(|m| m.concurrent_users).collect();
+        assert_eq!(vec![1, 2, 4, 8, 6, 5], concurrency_levels);
+
+        // With a coarser resolution, the bisection should stop sooner.
+        let saturation_config = SaturationConfig {
+            slo_p99: Duration::milliseconds(250),
+            resolution: 4,
+            ..saturation_config
+        };
+        let mut measurements = vec![];
+        let saturation = super::search_for_saturation(
+            &saturation_config,
+            &|concurrent_users| async move {
+                fake_measurement(concurrent_users, u64::from(concurrent_users) * 10, 0)
+            },
+            &mut measurements,
+        )
+        .await;
+        assert_eq!(Some(24), saturation.max_concurrent_users);
+        let concurrency_levels: Vec<u32> =
+            measurements.iter().map(|m| m.concurrent_users).collect();
+        assert_eq!(vec![1, 2, 4, 8, 16, 32, 24, 28], concurrency_levels);
+    }
+
+    /// Verifies that [super::search_for_saturation] stops once the error rate gets too high, or the maximum
+    /// concurrency level is reached.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn search_for_saturation_errors_and_max() {
+        let saturation_config = SaturationConfig {
+            slo_p99: Duration::milliseconds(50),
+            max_error_rate: 0.01,
+            step_factor: 1.5,
+            max_concurrency: 1024,
+            resolution: 1,
+        };
+        let mut measurements = vec![];
+        let saturation = super::search_for_saturation(
+            &saturation_config,
+            &|concurrent_users| async move {
+                fake_measurement(
+                    concurrent_users,
+                    1,
+                    if concurrent_users > 3 { 5 } else { 0 },
+                )
+            },
+            &mut measurements,
+        )
+        .await;
+        assert_eq!(Some(3), saturation.max_concurrent_users);
+        assert!(matches!(
+            saturation.stop_reason,
+            ServerOperationSaturationStop::ErrorRateExceeded(_)
+        ));
+        let concurrency_levels: Vec<u32> =
+            measurements.iter().map(|m| m.concurrent_users).collect();
+        assert_eq!(vec![1, 2, 3, 5, 4], concurrency_levels);
+
+        let saturation_config = SaturationConfig {
+            max_concurrency: 4,
+            ..saturation_config
+        };
+        let mut measurements = vec![];
+        let saturation = super::search_for_saturation(
+            &saturation_config,
+            &|concurrent_users| async move { fake_measurement(concurrent_users, 1, 0) },
+            &mut measurements,
+        )
+        .await;
+        assert_eq!(Some(4), saturation.max_concurrent_users);
+        assert!(matches!(
+            saturation.stop_reason,
+            ServerOperationSaturationStop::MaxConcurrencyReached()
+        ));
+        let concurrency_levels: Vec<u32> =
+            measurements.iter().map(|m| m.concurrent_users).collect();
+        assert_eq!(vec![1, 2, 3, 4], concurrency_levels);
+
+        // The ramp shouldn't overflow, even at the highest possible concurrency levels.
+        let saturation_config = SaturationConfig {
+            step_factor: 2.0,
+            max_concurrency: u32::MAX,
+            ..saturation_config
+        };
+        let mut measurements = vec![];
+        let saturation = super::search_for_saturation(
+            &saturation_config,
+            &|concurrent_users| async move { fake_measurement(concurrent_users, 1, 0) },
+            &mut measurements,
+        )
+        .await;
+        assert_eq!(Some(u32::MAX), saturation.max_concurrent_users);
+        assert_eq!(33, measurements.len());
+    }
+}
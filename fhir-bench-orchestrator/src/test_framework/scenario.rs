@@ -0,0 +1,1047 @@
+//! Contains the code to run multi-step scenario operations, each iteration of which runs a sequence of
+//! dependent requests as a single unit (e.g. all of the requests needed to open a patient's chart). Values
+//! can be extracted from each step's response, for use in building the requests for later steps.
+//!
+//! Besides the built-in scenarios here, scenarios (including single-step ones, i.e. plain operations) can be
+//! defined in TOML files in the benchmark directory's `scenarios/` directory, without writing any Rust: see
+//! [load_scenarios()] and the `README.md` in that directory.
+
+use super::{
+    elapsed_since, measure_operation, run_measurements, run_operation_with_timeout,
+    ServerOperationFailureKind, ServerOperationIterationFailed, ServerOperationIterationStarting,
+    ServerOperationIterationState, ServerOperationIterationSucceeded,
+    ServerOperationMeasurementLength, ServerOperationMeasurementRecorder,
+};
+use crate::config::ContentEncoding;
+use crate::servers::{ConnectTimer, ServerHandle};
+use crate::test_framework::payload::{
+    encode_request, read_response_body, ServerOperationPayload, ServerOperationResponse,
+};
+use crate::test_framework::phases::ServerOperationPhases;
+use crate::test_framework::users::{run_virtual_users, VirtualUser};
+use crate::test_framework::workload_plan;
+use crate::test_framework::{ServerOperationLog, ServerOperationMeasurement};
+use crate::AppState;
+use eyre::{eyre, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info_span, trace_span, Instrument};
+
+pub(super) static SERVER_OP_NAME_SCENARIO_OPEN_CHART: &str = "scenario_open_chart";
+
+/// Models a multi-step scenario operation.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// The unique name of the scenario, which is also used as its operation name.
+    pub name: String,
+
+    /// The [ScenarioStep]s to run, in order, for each iteration of the scenario.
+    pub steps: Vec<ScenarioStep>,
+}
+
+/// Models a single request in a [Scenario].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    /// The name of the step, which must be unique within its [Scenario].
+    pub name: String,
+
+    /// The HTTP method to call, e.g. `GET` (which is the default).
+    #[serde(default = "default_method")]
+    pub method: String,
+
+    /// The URL to call, relative to the server's base URL. Any `{variable}` placeholders in it will be
+    /// replaced with the (URL-encoded) value of that variable.
+    pub url: String,
+
+    /// Any additional HTTP headers to send, whose values may also contain `{variable}` placeholders (which
+    /// will not be URL-encoded).
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
+    /// The `resourceType` of the resource from the iteration's sample `Bundle` to send as the request body
+    /// (with its `id` removed), if any.
+    #[serde(default)]
+    pub body_sample: Option<String>,
+
+    /// The HTTP status code that the response must have, or `None` to accept any `2xx` status.
+    #[serde(default)]
+    pub expect_status: Option<u16>,
+
+    /// The [ScenarioAssertion]s that the step's JSON response must satisfy.
+    #[serde(default, rename = "assert")]
+    pub assertions: Vec<ScenarioAssertion>,
+
+    /// The [ScenarioExtraction]s to apply to the step's response, for use in later steps.
+    #[serde(default)]
+    pub extract: Vec<ScenarioExtraction>,
+}
+
+/// Returns the default [ScenarioStep.method], `GET`.
+fn default_method() -> String {
+    "GET".into()
+}
+
+/// Models a check on a [ScenarioStep]'s JSON response.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioAssertion {
+    /// The path to the value to check, as supported by [find_json_path()], e.g. `resourceType`.
+    pub path: String,
+
+    /// The (string, number, or boolean) value that must be found at the path, if any.
+    #[serde(default)]
+    pub equals: Option<String>,
+
+    /// Whether or not a value must be found at the path, which defaults to `true` (and is ignored if
+    /// `equals` is specified).
+    #[serde(default)]
+    pub exists: Option<bool>,
+}
+
+/// Models a value to be extracted from a [ScenarioStep]'s JSON response into a variable.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioExtraction {
+    /// The name of the variable to store the extracted value in.
+    pub variable: String,
+
+    /// The path to the value in the JSON response, as supported by [find_json_path()], e.g.
+    /// `entry[0].resource.id`.
+    pub path: String,
+}
+
+/// The starting point for a single iteration of a [Scenario]: one of the sample patients.
+#[derive(Clone)]
+pub(super) struct ScenarioInput {
+    /// The starting variables for the iteration (see [patient_variables()]).
+    variables: HashMap<String, String>,
+
+    /// The sample patient's `Bundle`, which [ScenarioStep.body_sample] request bodies are taken from.
+    bundle: Arc<serde_json::Value>,
+}
+
+/// Returns the built-in "open a patient's chart" [Scenario], which models the requests that a clinical
+/// application might make when a clinician opens a patient's chart: it searches for the patient by name,
+/// reads their `Patient` resource, lists their `Encounter`s, and then fetches their most recent
+/// `Observation`s.
+///
+/// Each iteration starts with the `family` and `given` name variables of one of the sample patients.
+pub fn open_chart_scenario() -> Scenario {
+    let step = |name: &str, url: &str, extract: Vec<ScenarioExtraction>| ScenarioStep {
+        name: name.into(),
+        method: default_method(),
+        url: url.into(),
+        headers: BTreeMap::new(),
+        body_sample: None,
+        expect_status: None,
+        assertions: vec![],
+        extract,
+    };
+
+    Scenario {
+        name: SERVER_OP_NAME_SCENARIO_OPEN_CHART.into(),
+        steps: vec![
+            step(
+                "search_patient",
+                "Patient?family={family}&given={given}",
+                vec![ScenarioExtraction {
+                    variable: "patient_id".into(),
+                    path: "entry.0.resource.id".into(),
+                }],
+            ),
+            step("read_patient", "Patient/{patient_id}", vec![]),
+            step("list_encounters", "Encounter?patient={patient_id}", vec![]),
+            step(
+                "recent_observations",
+                "Observation?patient={patient_id}&_sort=-date&_count=20",
+                vec![],
+            ),
+        ],
+    }
+}
+
+/// Verifies and benchmarks the built-in [open_chart_scenario()].
+pub async fn benchmark_open_chart(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> ServerOperationLog {
+    benchmark_scenario(app_state, server_handle, &open_chart_scenario()).await
+}
+
+/// Verifies and benchmarks the specified [Scenario], after loading the sample data into the server for it to
+/// run against.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `scenario`: the [Scenario] to benchmark
+///
+/// Returns the [ServerOperationLog] for the scenario.
+pub async fn benchmark_scenario(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    scenario: &Scenario,
+) -> ServerOperationLog {
+    let inputs = load_sample_data(app_state, server_handle)
+        .instrument(info_span!("load_sample_data", scenario = %scenario.name))
+        .await;
+    let inputs = match inputs {
+        Ok(inputs) => inputs,
+        Err(err) => {
+            let mut server_op_log = ServerOperationLog::new(scenario.name.as_str().into());
+            server_op_log.errors.push(format!("{:?}", err));
+            return server_op_log;
+        }
+    };
+
+    run_measurements(
+        app_state,
+        &scenario.name,
+        |concurrent_users, compression| {
+            benchmark_scenario_for_users(
+                app_state,
+                server_handle,
+                scenario,
+                &inputs,
+                concurrent_users,
+                compression,
+            )
+        },
+    )
+    .await
+}
+
+/// Wipes the server and then loads all of the sample data `Bundle`s into it.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+///
+/// Returns a [ScenarioInput] for each sample patient that was loaded, in the order given by
+/// [workload_plan::WorkloadPlan.patients], for use as the inputs to scenario iterations, or an error if
+/// there weren't any.
+pub(super) async fn load_sample_data(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+) -> Result<Vec<ScenarioInput>> {
+    server_handle.expunge_all_content(app_state).await?;
+
+    let client = server_handle.client()?;
+    let mut patient_inputs = vec![];
+    for bundle_file in app_state.sample_data.bundle_files() {
+        let bundle = std::fs::read_to_string(&bundle_file)
+            .with_context(|| format!("Unable to read sample Bundle '{:?}'.", bundle_file))?;
+        let bundle_json: serde_json::Value = serde_json::from_str(&bundle)
+            .with_context(|| format!("Unable to parse sample Bundle '{:?}'.", bundle_file))?;
+
+        post_bundle(server_handle, client.clone(), &bundle_file, bundle).await?;
+        if let Some(variables) = patient_variables(&bundle_json) {
+            let file_name = bundle_file
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let input = ScenarioInput {
+                variables,
+                bundle: Arc::new(bundle_json),
+            };
+            patient_inputs.push((file_name, input));
+        }
+    }
+    let inputs: Vec<ScenarioInput> = workload_plan::order_by_plan(
+        &app_state.workload_plan.patients,
+        patient_inputs,
+        |(file_name, _)| file_name,
+    )
+    .into_iter()
+    .map(|(_, input)| input)
+    .collect();
+
+    if inputs.is_empty() {
+        return Err(eyre!("No sample patients were found."));
+    }
+
+    Ok(inputs)
+}
+
+/// POSTs the specified sample `Bundle` to the server.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `client`: the [reqwest::Client] to use
+/// * `bundle_file`: the file that the `Bundle` was read from, which is only used in error messages
+/// * `bundle`: the `Bundle` JSON to POST
+async fn post_bundle(
+    server_handle: &dyn ServerHandle,
+    client: reqwest::Client,
+    bundle_file: &Path,
+    bundle: String,
+) -> Result<()> {
+    let url = server_handle.base_url();
+    let response = server_handle
+        .request_builder(client, http::Method::POST, url.clone())
+        .header("Content-Type", "application/fhir+json")
+        .body(bundle)
+        .send()
+        .instrument(trace_span!("POST request", %url))
+        .await?;
+
+    let response_status = response.status();
+    if !response_status.is_success() {
+        let response_body = response.text().await?;
+        return Err(eyre!(
+            "The POST of sample Bundle '{:?}' failed, with status '{}' and body: '{}'",
+            bundle_file,
+            response_status,
+            response_body
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the starting scenario variables for the patient in the specified sample `Bundle`, if it has one:
+/// `family` and `given`, which are the patient's first family and given names.
+///
+/// Parameters:
+/// * `bundle`: the sample `Bundle` JSON to check
+fn patient_variables(bundle: &serde_json::Value) -> Option<HashMap<String, String>> {
+    let patient = bundle["entry"]
+        .as_array()?
+        .iter()
+        .map(|entry| &entry["resource"])
+        .find(|resource| resource["resourceType"] == "Patient")?;
+
+    let mut variables = HashMap::new();
+    variables.insert(
+        "family".into(),
+        extract_json_path(patient, "name.0.family")?,
+    );
+    variables.insert(
+        "given".into(),
+        extract_json_path(patient, "name.0.given.0")?,
+    );
+    Some(variables)
+}
+
+/// Verifies and benchmarks the specified [Scenario] for the specified number of concurrent users.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `scenario`: the [Scenario] to benchmark
+/// * `inputs`: the [ScenarioInput] for each iteration, which will be cycled through
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+///
+/// Returns a [ServerOperationMeasurement] with the results.
+#[tracing::instrument(
+    level = "info",
+    skip(app_state, server_handle, scenario, inputs),
+    fields(scenario = %scenario.name)
+)]
+async fn benchmark_scenario_for_users(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    scenario: &Scenario,
+    inputs: &[ScenarioInput],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+) -> ServerOperationMeasurement {
+    let (measurement, ()) = measure_operation(
+        app_state,
+        concurrent_users,
+        compression,
+        || ServerOperationMeasurementRecorder::new(&app_state.config, &scenario.name),
+        |length, mut recorder| async move {
+            run_operations_scenario(
+                app_state,
+                server_handle,
+                scenario,
+                inputs,
+                concurrent_users,
+                compression,
+                length,
+                &mut recorder,
+            )
+            .await;
+            (recorder, ())
+        },
+    )
+    .await;
+    measurement
+}
+
+/// Runs iterations of the specified [Scenario] for the specified number of concurrent users, until the
+/// specified [ServerOperationMeasurementLength] is reached.
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server implementation instance being tested
+/// * `scenario`: the [Scenario] to run
+/// * `inputs`: the [ScenarioInput] for each iteration, which will be cycled through
+/// * `concurrent_users`: the number of users to try and test with concurrently
+/// * `compression`: the [ContentEncoding] for the users' requests to ask for, if any
+/// * `length`: the [ServerOperationMeasurementLength] that determines how many iterations to run
+/// * `recorder`: the [ServerOperationMeasurementRecorder] to record each iteration's outcome in
+#[allow(clippy::too_many_arguments)]
+async fn run_operations_scenario(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    scenario: &Scenario,
+    inputs: &[ScenarioInput],
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    length: ServerOperationMeasurementLength,
+    recorder: &mut ServerOperationMeasurementRecorder,
+) {
+    /*
+     * Build an iterator: One element (the [ScenarioInput]) for each iteration to run. The iterator is
+     * lazy, so whether or not to start each iteration is only decided once a virtual user is ready to run it.
+     */
+    let circuit_breaker = recorder.circuit_breaker();
+    let started = Instant::now();
+    let iterations = (0..)
+        .take_while(|iteration| {
+            !circuit_breaker.is_tripped()
+                && length.should_start_iteration(*iteration, elapsed_since(started))
+        })
+        .map(|iteration| inputs[usize::try_from(iteration).unwrap() % inputs.len()].clone());
+
+    /*
+     * Run those iterations with `concurrent_users` virtual users, recording the outcome of each iteration.
+     */
+    run_virtual_users(
+        server_handle,
+        concurrent_users,
+        compression,
+        app_state.config.think_time.as_ref(),
+        iterations,
+        |user, input| {
+            run_operation_scenario_with_timeout(app_state, server_handle, user, scenario, input)
+        },
+        |operation_result| recorder.record(operation_result),
+    )
+    .await;
+}
+
+/// Runs a single iteration of the specified [Scenario], subject to the [AppConfig.operation_timeout].
+///
+/// Parameters:
+/// * `app_state`: the application's [AppState]
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `user`: the [VirtualUser] to run the operation as
+/// * `scenario`: the [Scenario] to run
+/// * `input`: the [ScenarioInput] for this iteration
+///
+/// Returns the final [ServerOperationIterationState] containing information about the operation's
+/// success or failure.
+pub(super) async fn run_operation_scenario_with_timeout(
+    app_state: &AppState,
+    server_handle: &dyn ServerHandle,
+    user: VirtualUser,
+    scenario: &Scenario,
+    input: ScenarioInput,
+) -> std::result::Result<
+    ServerOperationIterationState<ServerOperationIterationSucceeded>,
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+> {
+    let operation_state = ServerOperationIterationState::new();
+    let operation = run_operation_scenario(
+        server_handle,
+        &user,
+        scenario,
+        input,
+        operation_state.clone(),
+    );
+    run_operation_with_timeout(app_state, operation_state, operation).await
+}
+
+/// Runs a single iteration of the specified [Scenario], running each of its steps in order, and verifies
+/// its result.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `user`: the [VirtualUser] to run the operation as
+/// * `scenario`: the [Scenario] to run
+/// * `input`: the [ScenarioInput] for this iteration
+/// * `operation_state`: the initial state machine for this operation iteration
+///
+/// Returns the final [ServerOperationIterationState] containing information about the operation's
+/// success or failure.
+async fn run_operation_scenario(
+    server_handle: &dyn ServerHandle,
+    user: &VirtualUser,
+    scenario: &Scenario,
+    input: ScenarioInput,
+    operation_state: ServerOperationIterationState<ServerOperationIterationStarting>,
+) -> std::result::Result<
+    ServerOperationIterationState<ServerOperationIterationSucceeded>,
+    ServerOperationIterationState<ServerOperationIterationFailed>,
+> {
+    let client = match user.client() {
+        Ok(client) => client,
+        Err(err) => {
+            return Err(operation_state
+                .completed()
+                .failed(ServerOperationFailureKind::ConnectionError, err));
+        }
+    };
+
+    let mut variables = input.variables.clone();
+    let mut steps = Vec::with_capacity(scenario.steps.len());
+    let mut payload = ServerOperationPayload::default();
+    let mut phases = ServerOperationPhases::default();
+    let mut completed = Instant::now();
+    for step in &scenario.steps {
+        let step_started = Instant::now();
+        completed = run_scenario_step(
+            server_handle,
+            client.clone(),
+            user.compression(),
+            user.connect_timer(),
+            step,
+            &input,
+            &mut variables,
+            &operation_state,
+            &mut payload,
+            &mut phases,
+        )
+        .instrument(trace_span!("scenario step", step = %step.name))
+        .await?;
+        steps.push((step.name.clone(), completed - step_started));
+    }
+
+    Ok(operation_state
+        .completed_at(completed)
+        .succeeded_with_steps(steps)
+        .with_payload(payload)
+        .with_phases(phases))
+}
+
+/// Runs a single [ScenarioStep], storing any values extracted from its response in the specified variables.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server to test
+/// * `client`: the [reqwest::Client] to use
+/// * `compression`: the [ContentEncoding] for the step's request to ask for, if any
+/// * `connect_timer`: the [ConnectTimer] of `client`
+/// * `step`: the [ScenarioStep] to run
+/// * `input`: the [ScenarioInput] for the operation iteration, whose sample `Bundle` request bodies are taken
+///   from
+/// * `variables`: the scenario variables, which will be added to
+/// * `operation_state`: the initial state machine for the operation iteration, which is used to build the
+///   failure state if the step fails
+/// * `payload`: the [ServerOperationPayload] for the operation iteration, which the step's request and
+///   response sizes will be added to
+/// * `phases`: the [ServerOperationPhases] for the operation iteration, which the step's latency phases will
+///   be added to
+///
+/// Returns when the step's response was received (before it was decoded), in monotonic time, or the final
+/// [ServerOperationIterationState] for the operation iteration, if the step failed.
+#[allow(clippy::too_many_arguments)]
+async fn run_scenario_step(
+    server_handle: &dyn ServerHandle,
+    client: reqwest::Client,
+    compression: Option<ContentEncoding>,
+    connect_timer: &ConnectTimer,
+    step: &ScenarioStep,
+    input: &ScenarioInput,
+    variables: &mut HashMap<String, String>,
+    operation_state: &ServerOperationIterationState<ServerOperationIterationStarting>,
+    payload: &mut ServerOperationPayload,
+    phases: &mut ServerOperationPhases,
+) -> std::result::Result<Instant, ServerOperationIterationState<ServerOperationIterationFailed>> {
+    let failed = |kind: ServerOperationFailureKind, error: eyre::Error| {
+        operation_state.clone().completed().failed(
+            kind,
+            error.wrap_err(format!("Scenario step '{}' failed.", step.name)),
+        )
+    };
+
+    let url = render_template(&step.url, variables, true)
+        .and_then(|url| Ok(server_handle.base_url().join(&url)?))
+        .map_err(|err| failed(ServerOperationFailureKind::VerificationFailure, err))?;
+    let method = http::Method::from_bytes(step.method.as_bytes())
+        .map_err(|err| failed(ServerOperationFailureKind::VerificationFailure, err.into()))?;
+    let headers = build_headers(step, variables)
+        .map_err(|err| failed(ServerOperationFailureKind::VerificationFailure, err))?;
+
+    let body = match &step.body_sample {
+        Some(resource_type) => Some(sample_resource(&input.bundle, resource_type).ok_or_else(
+            || {
+                failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!("The sample Bundle did not contain a '{}'.", resource_type),
+                )
+            },
+        )?),
+        None => None,
+    };
+    let (request, request_payload) = encode_request(
+        server_handle
+            .request_builder(client, method, url.clone())
+            .headers(headers),
+        compression,
+        body,
+    );
+    let sent = Instant::now();
+    let response = request.send().await.map_err(|err| {
+        failed(
+            ServerOperationFailureKind::from_request_error(&err),
+            eyre!("HTTP request failed: '{}'", err),
+        )
+    })?;
+
+    // Always pull response body, to drain stream and release connection.
+    let response_status = response.status();
+    let response = read_response_body(response, compression, connect_timer, request_payload, sent)
+        .await
+        .unwrap_or_else(ServerOperationResponse::unreadable);
+    payload.add(&response.payload);
+    phases.add(&response.phases);
+    let response_body = response.body;
+
+    let status_expected = match step.expect_status {
+        Some(expect_status) => response_status.as_u16() == expect_status,
+        None => response_status.is_success(),
+    };
+    if !status_expected {
+        let error = eyre!(
+            "The {} to '{}' failed, with status '{}' and body: '{}'",
+            step.method,
+            &url,
+            response_status,
+            response_body
+        );
+        return Err(operation_state.clone().completed().failed_response(
+            response_status,
+            &response_body,
+            error.wrap_err(format!("Scenario step '{}' failed.", step.name)),
+        ));
+    }
+
+    if !step.extract.is_empty() || !step.assertions.is_empty() {
+        let response_json: serde_json::Value =
+            serde_json::from_str(&response_body).map_err(|err| {
+                failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!("Unable to parse response from '{}': '{}'", &url, err),
+                )
+            })?;
+        for assertion in &step.assertions {
+            if !assertion.holds(&response_json) {
+                return Err(failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!(
+                        "The response from '{}' failed assertion '{:?}': '{}'",
+                        &url,
+                        assertion,
+                        response_body
+                    ),
+                ));
+            }
+        }
+        for extraction in &step.extract {
+            let value = extract_json_path(&response_json, &extraction.path).ok_or_else(|| {
+                failed(
+                    ServerOperationFailureKind::VerificationFailure,
+                    eyre!(
+                        "The response from '{}' did not contain a value at '{}': '{}'",
+                        &url,
+                        extraction.path,
+                        response_body
+                    ),
+                )
+            })?;
+            variables.insert(extraction.variable.clone(), value);
+        }
+    }
+
+    Ok(response.received)
+}
+
+/// Builds the HTTP headers for the specified [ScenarioStep]: `application/fhir+json` for `Accept` (and for
+/// `Content-Type`, if the step has a body), overridden by any of the step's own headers.
+///
+/// Parameters:
+/// * `step`: the [ScenarioStep] to build the headers for
+/// * `variables`: the scenario variables, which are available to the step's header templates
+///
+/// Returns the [HeaderMap] to send, or an error if any header was invalid.
+fn build_headers(step: &ScenarioStep, variables: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    let fhir_json = HeaderValue::from_static("application/fhir+json");
+    headers.insert(http::header::ACCEPT, fhir_json.clone());
+    if step.body_sample.is_some() {
+        headers.insert(http::header::CONTENT_TYPE, fhir_json);
+    }
+
+    for (name, value) in &step.headers {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(&render_template(value, variables, false)?)?,
+        );
+    }
+
+    Ok(headers)
+}
+
+/// Returns the first resource of the specified type in the specified sample `Bundle`, with its `id`
+/// removed, for use as a request body.
+///
+/// Parameters:
+/// * `bundle`: the sample `Bundle` JSON to search
+/// * `resource_type`: the `resourceType` of the resource to find
+///
+/// Returns the resource JSON, or `None` if the `Bundle` did not contain any resources of that type.
+fn sample_resource(bundle: &serde_json::Value, resource_type: &str) -> Option<String> {
+    let mut resource = bundle["entry"]
+        .as_array()?
+        .iter()
+        .map(|entry| &entry["resource"])
+        .find(|resource| resource["resourceType"] == resource_type)?
+        .clone();
+    resource.as_object_mut()?.remove("id");
+
+    Some(resource.to_string())
+}
+
+impl ScenarioAssertion {
+    /// Returns `true` if this [ScenarioAssertion] holds for the specified response JSON.
+    ///
+    /// Parameters:
+    /// * `json`: the response JSON to check
+    fn holds(&self, json: &serde_json::Value) -> bool {
+        match (&self.equals, self.exists) {
+            (Some(equals), _) => extract_json_path(json, &self.path).as_ref() == Some(equals),
+            (None, exists) => find_json_path(json, &self.path).is_some() == exists.unwrap_or(true),
+        }
+    }
+}
+
+/// Loads all of the [Scenario]s defined in the TOML files in the specified directory.
+///
+/// Parameters:
+/// * `scenarios_dir`: the directory to load `*.toml` scenario definitions from, which need not exist
+///
+/// Returns the [Scenario]s found, in file name order, or an error if any of them were invalid.
+pub fn load_scenarios(scenarios_dir: &Path) -> Result<Vec<Scenario>> {
+    if !scenarios_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut scenario_files = vec![];
+    for entry in std::fs::read_dir(scenarios_dir)
+        .with_context(|| format!("Unable to read scenarios directory '{:?}'.", scenarios_dir))?
+    {
+        let path = entry?.path();
+        if path.extension() == Some(std::ffi::OsStr::new("toml")) {
+            scenario_files.push(path);
+        }
+    }
+    scenario_files.sort();
+
+    let mut scenarios: Vec<Scenario> = vec![];
+    for scenario_file in scenario_files {
+        let scenario = std::fs::read_to_string(&scenario_file)
+            .map_err(eyre::Error::from)
+            .and_then(|scenario| parse_scenario(&scenario))
+            .with_context(|| format!("Invalid scenario file '{:?}'.", scenario_file))?;
+        if super::built_in_operation_names().contains(&scenario.name.as_str())
+            || scenarios.iter().any(|other| other.name == scenario.name)
+        {
+            return Err(eyre!(
+                "Scenario file '{:?}' reuses scenario name '{}'.",
+                scenario_file,
+                scenario.name
+            ));
+        }
+        scenarios.push(scenario);
+    }
+
+    Ok(scenarios)
+}
+
+/// Parses and validates a TOML [Scenario] definition.
+///
+/// Parameters:
+/// * `scenario`: the TOML to parse
+///
+/// Returns the parsed [Scenario], or an error if it was invalid.
+fn parse_scenario(scenario: &str) -> Result<Scenario> {
+    let scenario: Scenario = toml::from_str(scenario)?;
+    if scenario.steps.is_empty() {
+        return Err(eyre!("Scenario '{}' has no steps.", scenario.name));
+    }
+    for (step_index, step) in scenario.steps.iter().enumerate() {
+        http::Method::from_bytes(step.method.as_bytes())
+            .with_context(|| format!("Scenario step '{}' has an invalid method.", step.name))?;
+
+        // Steps are reported by name, so their names must be unique.
+        if scenario.steps[..step_index]
+            .iter()
+            .any(|other| other.name == step.name)
+        {
+            return Err(eyre!(
+                "Scenario '{}' has more than one step named '{}'.",
+                scenario.name,
+                step.name
+            ));
+        }
+    }
+
+    Ok(scenario)
+}
+
+/// Replaces each `{variable}` placeholder in the specified template with the value of that variable.
+///
+/// Parameters:
+/// * `template`: the template to render
+/// * `variables`: the variables available to the template
+/// * `url_encode`: whether or not to URL-encode the variables' values
+///
+/// Returns the rendered template, or an error if it refers to an unknown variable.
+fn render_template(
+    template: &str,
+    variables: &HashMap<String, String>,
+    url_encode: bool,
+) -> Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut remaining = template;
+    while let Some(start) = remaining.find('{') {
+        let end = remaining[start..]
+            .find('}')
+            .ok_or_else(|| eyre!("Unclosed placeholder in template: '{}'", template))?
+            + start;
+        let variable = &remaining[start + 1..end];
+        let value = variables.get(variable).ok_or_else(|| {
+            eyre!(
+                "Unknown variable '{}' in template: '{}'",
+                variable,
+                template
+            )
+        })?;
+
+        rendered.push_str(&remaining[..start]);
+        if url_encode {
+            rendered.extend(url::form_urlencoded::byte_serialize(value.as_bytes()));
+        } else {
+            rendered.push_str(value);
+        }
+        remaining = &remaining[end + 1..];
+    }
+    rendered.push_str(remaining);
+
+    Ok(rendered)
+}
+
+/// Returns the value found at the specified path in the specified JSON, if any.
+///
+/// Parameters:
+/// * `json`: the JSON to search
+/// * `path`: the path to the value, in a small subset of JSONPath/FHIRPath: dot-separated field names,
+///   optionally prefixed with `$.`, where array elements are selected with either `[0]` or `.0` (and field
+///   names applied to an array select from its first element), e.g. `entry[0].resource.id`
+///
+/// Returns the value found, or `None` if there was no such value.
+fn find_json_path<'a>(json: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let path = path.replace('[', ".").replace(']', "");
+    let mut value = json;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        value = match segment.parse::<usize>() {
+            Ok(index) => value.get(index)?,
+            Err(_) if value.is_array() => value.get(0)?.get(segment)?,
+            Err(_) => value.get(segment)?,
+        };
+    }
+
+    Some(value)
+}
+
+/// Returns the scalar value found at the specified path in the specified JSON, if any.
+///
+/// Parameters:
+/// * `json`: the JSON to search
+/// * `path`: the path to the value, as supported by [find_json_path()]
+///
+/// Returns the value found as a [String], or `None` if there was no such (string, number, or boolean) value.
+fn extract_json_path(json: &serde_json::Value, path: &str) -> Option<String> {
+    match find_json_path(json, path)? {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        serde_json::Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Unit tests for the scenario support code.
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Verifies that [super::render_template] works as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn render_template() {
+        let mut variables = HashMap::new();
+        variables.insert("family".to_string(), "O'Hara".to_string());
+        variables.insert("id".to_string(), "123".to_string());
+
+        assert_eq!(
+            "Patient?family=O%27Hara&_id=123",
+            super::render_template("Patient?family={family}&_id={id}", &variables, true).unwrap()
+        );
+        assert_eq!(
+            "O'Hara",
+            super::render_template("{family}", &variables, false).unwrap()
+        );
+        assert_eq!(
+            "metadata",
+            super::render_template("metadata", &variables, true).unwrap()
+        );
+        assert!(super::render_template("Patient/{foo}", &variables, true).is_err());
+        assert!(super::render_template("Patient/{id", &variables, true).is_err());
+    }
+
+    /// Verifies that [super::extract_json_path], [super::ScenarioAssertion], and [super::patient_variables]
+    /// work as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn extract_json_path() {
+        let bundle = json!({
+            "resourceType": "Bundle",
+            "total": 1,
+            "entry": [{
+                "resource": {
+                    "resourceType": "Patient",
+                    "id": "abc",
+                    "name": [{ "family": "Smith", "given": ["Jane", "Q"] }],
+                }
+            }]
+        });
+
+        for path in &[
+            "entry.0.resource.id",
+            "entry[0].resource.id",
+            "$.entry[0].resource.id",
+            "entry.resource.id",
+        ] {
+            assert_eq!(
+                Some("abc".to_string()),
+                super::extract_json_path(&bundle, path),
+                "Unexpected result for '{}'.",
+                path
+            );
+        }
+        assert_eq!(
+            Some("1".to_string()),
+            super::extract_json_path(&bundle, "total")
+        );
+        assert_eq!(
+            None,
+            super::extract_json_path(&bundle, "entry.1.resource.id")
+        );
+        assert_eq!(None, super::extract_json_path(&bundle, "entry.0.resource"));
+
+        let assertion = |path: &str, equals: Option<&str>, exists: Option<bool>| {
+            super::ScenarioAssertion {
+                path: path.into(),
+                equals: equals.map(String::from),
+                exists,
+            }
+            .holds(&bundle)
+        };
+        assert!(assertion("resourceType", Some("Bundle"), None));
+        assert!(!assertion("resourceType", Some("Patient"), None));
+        assert!(assertion("entry[0].resource", None, None));
+        assert!(assertion("link", None, Some(false)));
+        assert!(!assertion("link", None, Some(true)));
+
+        let variables = super::patient_variables(&bundle).unwrap();
+        assert_eq!("Smith", variables["family"]);
+        assert_eq!("Jane", variables["given"]);
+
+        assert_eq!(
+            json!({ "resourceType": "Patient", "name": [{ "family": "Smith", "given": ["Jane", "Q"] }] })
+                .to_string(),
+            super::sample_resource(&bundle, "Patient").unwrap()
+        );
+        assert_eq!(None, super::sample_resource(&bundle, "Encounter"));
+    }
+
+    /// Verifies that [super::load_scenarios] loads scenario files as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn load_scenarios() {
+        let scenarios_dir = tempfile::tempdir().expect("Unable to create temp directory.");
+        std::fs::write(
+            scenarios_dir.path().join("create_patient.toml"),
+            r#"
+name = "create_patient"
+
+[[steps]]
+name = "create"
+method = "POST"
+url = "Patient"
+body_sample = "Patient"
+expect_status = 201
+headers = { Prefer = "return=representation" }
+assert = [{ path = "resourceType", equals = "Patient" }]
+extract = [{ variable = "patient_id", path = "id" }]
+
+[[steps]]
+name = "read"
+url = "Patient/{patient_id}"
+"#,
+        )
+        .unwrap();
+        std::fs::write(scenarios_dir.path().join("README.md"), "Not a scenario.").unwrap();
+
+        let scenarios = super::load_scenarios(scenarios_dir.path()).unwrap();
+        assert_eq!(1, scenarios.len());
+        assert_eq!("create_patient", scenarios[0].name);
+        assert_eq!(2, scenarios[0].steps.len());
+        assert_eq!("POST", scenarios[0].steps[0].method);
+        assert_eq!(Some(201), scenarios[0].steps[0].expect_status);
+        assert_eq!(1, scenarios[0].steps[0].assertions.len());
+        assert_eq!("GET", scenarios[0].steps[1].method);
+
+        std::fs::write(
+            scenarios_dir.path().join("duplicate.toml"),
+            "name = \"create_patient\"\n[[steps]]\nname = \"read\"\nurl = \"metadata\"\n",
+        )
+        .unwrap();
+        assert!(super::load_scenarios(scenarios_dir.path()).is_err());
+
+        for built_in_name in crate::test_framework::built_in_operation_names() {
+            std::fs::write(
+                scenarios_dir.path().join("duplicate.toml"),
+                format!(
+                    "name = \"{}\"\n[[steps]]\nname = \"read\"\nurl = \"metadata\"\n",
+                    built_in_name
+                ),
+            )
+            .unwrap();
+            assert!(super::load_scenarios(scenarios_dir.path()).is_err());
+        }
+
+        assert!(super::parse_scenario("name = \"empty\"\nsteps = []\n").is_err());
+        assert!(super::parse_scenario(
+            "name = \"twice\"\n[[steps]]\nname = \"read\"\nurl = \"metadata\"\n\
+             [[steps]]\nname = \"read\"\nurl = \"metadata\"\n"
+        )
+        .is_err());
+        assert!(super::load_scenarios(&scenarios_dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+}
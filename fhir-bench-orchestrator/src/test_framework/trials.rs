@@ -0,0 +1,115 @@
+//! Summarizes the repeated trials of an operation's measurements, so that it's possible to tell whether or
+//! not the differences between results are actually meaningful.
+
+use super::{ConfidenceInterval, ServerOperationMeasurement, ServerOperationSummary};
+use crate::config::ContentEncoding;
+
+/// The two-sided 95% critical values of Student's t-distribution, for 1 through 30 degrees of freedom.
+const T_CRITICAL_VALUES_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// The two-sided 95% critical value of the normal distribution, which is used to approximate Student's
+/// t-distribution past the end of [T_CRITICAL_VALUES_95].
+const Z_CRITICAL_VALUE_95: f64 = 1.960;
+
+/// Summarizes the trials of each concurrency level (and compression) in the specified
+/// [ServerOperationMeasurement]s.
+///
+/// Parameters:
+/// * `measurements`: the [ServerOperationMeasurement]s to summarize
+///
+/// Returns a [ServerOperationSummary] for each concurrency level (and compression) that had more than one
+/// trial, in the order that they were first measured.
+pub fn summarize_trials(
+    measurements: &[ServerOperationMeasurement],
+) -> Vec<ServerOperationSummary> {
+    let mut concurrency_levels: Vec<(u32, Option<ContentEncoding>)> = vec![];
+    for measurement in measurements {
+        let concurrency_level = (measurement.concurrent_users, measurement.compression);
+        if !concurrency_levels.contains(&concurrency_level) {
+            concurrency_levels.push(concurrency_level);
+        }
+    }
+
+    concurrency_levels
+        .into_iter()
+        .filter_map(|(concurrent_users, compression)| {
+            let trials: Vec<&ServerOperationMeasurement> = measurements
+                .iter()
+                .filter(|m| m.concurrent_users == concurrent_users && m.compression == compression)
+                .collect();
+            if trials.len() < 2 {
+                return None;
+            }
+
+            let interval = |value: fn(&ServerOperationMeasurement) -> f64| {
+                ConfidenceInterval::from_samples(
+                    &trials.iter().map(|m| value(m)).collect::<Vec<f64>>(),
+                )
+            };
+            Some(ServerOperationSummary {
+                concurrent_users,
+                compression,
+                trials: trials.len() as u32,
+                throughput_per_second: interval(|m| m.metrics.throughput_per_second),
+                latency_millis_p50: interval(|m| m.metrics.latency_millis_p50),
+                latency_millis_p90: interval(|m| m.metrics.latency_millis_p90),
+                latency_millis_p99: interval(|m| m.metrics.latency_millis_p99),
+                latency_corrected_millis_p99: interval(|m| m.metrics.latency_corrected_millis_p99),
+            })
+        })
+        .collect()
+}
+
+impl ConfidenceInterval {
+    /// Calculates the mean and 95% [ConfidenceInterval] of the specified samples, using Student's
+    /// t-distribution.
+    ///
+    /// Parameters:
+    /// * `samples`: the sample values, of which there must be at least two
+    fn from_samples(samples: &[f64]) -> ConfidenceInterval {
+        assert!(samples.len() >= 2, "At least two samples are required.");
+        let count = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / count;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / (count - 1.0);
+        let critical_value = T_CRITICAL_VALUES_95
+            .get(samples.len() - 2)
+            .copied()
+            .unwrap_or(Z_CRITICAL_VALUE_95);
+        let margin = critical_value * (variance / count).sqrt();
+
+        ConfidenceInterval {
+            mean,
+            lower: mean - margin,
+            upper: mean + margin,
+        }
+    }
+}
+
+/// Unit tests for the trial summaries.
+#[cfg(test)]
+mod tests {
+    use crate::test_framework::ConfidenceInterval;
+
+    /// Verifies that [ConfidenceInterval::from_samples] calculates intervals as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn confidence_interval_from_samples() {
+        let interval = ConfidenceInterval::from_samples(&[10.0, 12.0, 14.0]);
+        assert!((interval.mean - 12.0).abs() < f64::EPSILON);
+        // The sample standard deviation is 2.0, so the margin is 4.303 * 2.0 / sqrt(3).
+        assert!((interval.lower - 7.031).abs() < 0.001);
+        assert!((interval.upper - 16.969).abs() < 0.001);
+
+        let interval = ConfidenceInterval::from_samples(&[5.0, 5.0]);
+        assert!((interval.lower - 5.0).abs() < f64::EPSILON);
+        assert!((interval.upper - 5.0).abs() < f64::EPSILON);
+    }
+}
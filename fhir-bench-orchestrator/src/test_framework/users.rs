@@ -0,0 +1,188 @@
+//! Models the load generated for a measurement as a set of virtual users, each of which runs one iteration
+//! at a time, with its own HTTP client, and (optionally) pauses to "think" between its iterations. This way,
+//! a measurement's `concurrent_users` really are users, rather than just a limit on concurrent requests.
+
+use crate::config::{ContentEncoding, ThinkTime, ThinkTimeDistribution};
+use crate::servers::{ConnectTimer, ServerHandle};
+use futures::prelude::*;
+use rand::Rng;
+use std::sync::Mutex;
+use tracing::{trace_span, Instrument};
+
+/// Represents a single virtual user's session with the server being tested. Clones of it share the same
+/// session.
+#[derive(Clone)]
+pub struct VirtualUser {
+    /// This user's own [reqwest::Client], or the message of the error encountered when trying to create it.
+    client: std::result::Result<reqwest::Client, String>,
+
+    /// The [ConnectTimer] that records how long this user's client spends establishing new connections.
+    connect_timer: ConnectTimer,
+
+    /// The [ContentEncoding] that this user's requests ask for (and compress their bodies with), if any.
+    compression: Option<ContentEncoding>,
+}
+
+impl VirtualUser {
+    /// Constructs a new [VirtualUser], with its own [reqwest::Client] for the specified server.
+    ///
+    /// Parameters:
+    /// * `server_handle`: the [ServerHandle] for the server being tested
+    /// * `compression`: the [ContentEncoding] that the user's requests should ask for, if any
+    pub fn new(
+        server_handle: &dyn ServerHandle,
+        compression: Option<ContentEncoding>,
+    ) -> VirtualUser {
+        let connect_timer = ConnectTimer::default();
+        VirtualUser {
+            client: server_handle
+                .new_client(connect_timer.clone())
+                .map_err(|err| format!("{:?}", err)),
+            connect_timer,
+            compression,
+        }
+    }
+
+    /// Returns the [ContentEncoding] that this user's requests ask for (and compress their bodies with), if
+    /// any.
+    pub fn compression(&self) -> Option<ContentEncoding> {
+        self.compression
+    }
+
+    /// Returns the [ConnectTimer] that records how long this user's client spends establishing new
+    /// connections.
+    pub fn connect_timer(&self) -> &ConnectTimer {
+        &self.connect_timer
+    }
+
+    /// Returns this user's [reqwest::Client], or an error if it could not be created.
+    pub fn client(&self) -> eyre::Result<reqwest::Client> {
+        match &self.client {
+            Ok(client) => Ok(client.clone()),
+            Err(err) => Err(eyre::eyre!("Unable to create client: '{}'", err)),
+        }
+    }
+}
+
+/// Runs the specified work with the specified number of [VirtualUser]s, each of which repeatedly claims the
+/// next element of work, runs the operation for it, and then thinks for a bit, until the work runs out.
+///
+/// Parameters:
+/// * `server_handle`: the [ServerHandle] for the server being tested
+/// * `concurrent_users`: the number of [VirtualUser]s to run
+/// * `compression`: the [ContentEncoding] that each user's requests should ask for, if any
+/// * `think_time`: the [ThinkTime] for each user to pause for between iterations, if any
+/// * `work`: an [Iterator] with one element for each iteration to run, which is only advanced once a user
+///   is ready to start another iteration
+/// * `operation`: runs a single iteration as (a clone of) the specified [VirtualUser]
+/// * `record`: records the outcome of each iteration, as it completes
+pub async fn run_virtual_users<W, F, Fut, R>(
+    server_handle: &dyn ServerHandle,
+    concurrent_users: u32,
+    compression: Option<ContentEncoding>,
+    think_time: Option<&ThinkTime>,
+    work: W,
+    operation: F,
+    record: R,
+) where
+    W: Iterator,
+    F: Fn(VirtualUser, W::Item) -> Fut,
+    Fut: Future,
+    R: FnMut(Fut::Output),
+{
+    /*
+     * The users all run concurrently within this one task, so the locks here are never contended for long:
+     * they're only held to claim or record a single iteration, never across an `await`.
+     */
+    let work = Mutex::new(work);
+    let record = Mutex::new(record);
+    let users = (0..concurrent_users).map(|user_index| {
+        let work = &work;
+        let record = &record;
+        let operation = &operation;
+        async move {
+            let user = VirtualUser::new(server_handle, compression);
+            let mut iterations: u32 = 0;
+            loop {
+                if iterations > 0 {
+                    if let Some(think_time) = think_time {
+                        tokio::time::sleep(sample_think_time(think_time)).await;
+                    }
+                }
+
+                let item = work.lock().expect("Unable to lock work.").next();
+                let item = match item {
+                    Some(item) => item,
+                    None => break,
+                };
+                let result = operation(user.clone(), item).await;
+                (record.lock().expect("Unable to lock recorder."))(result);
+                iterations += 1;
+            }
+        }
+        .instrument(trace_span!("virtual_user", user_index))
+    });
+
+    futures::future::join_all(users).await;
+}
+
+/// Draws a random think time from the specified [ThinkTime]'s distribution.
+///
+/// Parameters:
+/// * `think_time`: the [ThinkTime] to draw from
+///
+/// Returns the think time to pause for.
+fn sample_think_time(think_time: &ThinkTime) -> std::time::Duration {
+    let mean = think_time
+        .mean
+        .to_std()
+        .expect("Unable to convert Duration.");
+    match think_time.distribution {
+        ThinkTimeDistribution::Constant => mean,
+        ThinkTimeDistribution::Uniform => mean.mul_f64(rand::thread_rng().gen_range(0.0..2.0)),
+        ThinkTimeDistribution::Exponential => {
+            // Inverse transform sampling: `1.0 - x` keeps the argument to `ln` in `(0.0, 1.0]`.
+            let uniform: f64 = rand::thread_rng().gen();
+            mean.mul_f64(-(1.0 - uniform).ln())
+        }
+    }
+}
+
+/// Unit tests for the virtual users.
+#[cfg(test)]
+mod tests {
+    use crate::config::{ThinkTime, ThinkTimeDistribution};
+    use chrono::Duration;
+
+    /// Verifies that [super::sample_think_time] draws think times from the expected distributions.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn sample_think_time() {
+        let mean = std::time::Duration::from_millis(100);
+        let think_time = |distribution| ThinkTime {
+            mean: Duration::milliseconds(100),
+            distribution,
+        };
+
+        let constant = think_time(ThinkTimeDistribution::Constant);
+        assert_eq!(mean, super::sample_think_time(&constant));
+
+        let samples = 10_000;
+        for distribution in [
+            ThinkTimeDistribution::Uniform,
+            ThinkTimeDistribution::Exponential,
+        ] {
+            let think_time = think_time(distribution);
+            let total: std::time::Duration = (0..samples)
+                .map(|_| super::sample_think_time(&think_time))
+                .sum();
+            let sample_mean = total / samples;
+            assert!(
+                sample_mean > mean.mul_f64(0.9) && sample_mean < mean.mul_f64(1.1),
+                "Unexpected mean for {:?}: {:?}",
+                distribution,
+                sample_mean
+            );
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Contains the [WorkloadPlan]: the seeded, reproducible choices of which sample data each operation's
+//! iterations will use, and in what order. It's generated once per benchmark run and shared by every server,
+//! so that they all get the same requests in the same order, and it's included in the results, so that any
+//! result can be replayed exactly (by re-running with the same [AppConfig.workload_seed] and sample data).
+
+use crate::sample_data::SampleData;
+use eyre::{Context, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The seeded choices of which sample data each operation's iterations will use, and in what order.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct WorkloadPlan {
+    /// The [AppConfig.workload_seed] that the plan was generated from.
+    pub seed: u64,
+
+    /// The [SampleData::fingerprint] of the sample data that the plan was generated from, which must match
+    /// for the plan to be replayed exactly.
+    pub sample_data_fingerprint: String,
+
+    /// The original ID of each sample `Organization`, in the order that `POST /Organization` iterations will
+    /// use them.
+    pub organizations: Vec<String>,
+
+    /// The file name of each sample patient `Bundle`, in the order that scenario iterations will use them
+    /// (and thus their search values, e.g. the patients' names).
+    pub patients: Vec<String>,
+}
+
+impl WorkloadPlan {
+    /// Generates the [WorkloadPlan] for the specified seed and sample data. The same inputs will always
+    /// generate the same plan, regardless of e.g. the order that the sample data files were listed in.
+    ///
+    /// Parameters:
+    /// * `seed`: the [AppConfig.workload_seed] to generate the plan from
+    /// * `sample_data`: the [SampleData] that the plan's iterations will use
+    pub fn generate(seed: u64, sample_data: &SampleData) -> Result<WorkloadPlan> {
+        let sample_data_fingerprint = sample_data
+            .fingerprint()
+            .context("Unable to fingerprint sample data.")?;
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut organizations: Vec<String> = sample_data
+            .iter_orgs()
+            .map(|org| org.metadata.source_id)
+            .collect();
+        organizations.sort();
+        organizations.shuffle(&mut rng);
+
+        let mut patients: Vec<String> = sample_data
+            .patient_files()
+            .iter()
+            .filter_map(|patient_file| patient_file.file_name())
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .collect();
+        patients.sort();
+        patients.shuffle(&mut rng);
+
+        Ok(WorkloadPlan {
+            seed,
+            sample_data_fingerprint,
+            organizations,
+            patients,
+        })
+    }
+
+    /// Returns a new random number generator for the specified operation's random choices (e.g. which
+    /// scenario each mixed-workload iteration runs), seeded from this plan, so that every measurement of the
+    /// operation makes the same choices, in the same order.
+    ///
+    /// Parameters:
+    /// * `operation`: the name of the operation that the random number generator is for
+    pub fn rng(&self, operation: &str) -> StdRng {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(operation.as_bytes());
+        StdRng::seed_from_u64(self.seed ^ u64::from(hasher.finalize()))
+    }
+}
+
+/// Returns the specified items, sorted into the order of their keys in the specified [WorkloadPlan] list
+/// (e.g. [WorkloadPlan.patients]). Any items that aren't in the plan are dropped.
+///
+/// Parameters:
+/// * `planned_keys`: the keys of the items, in their planned order
+/// * `items`: the items to sort
+/// * `key`: returns the key of each item
+pub fn order_by_plan<T, K>(planned_keys: &[String], items: Vec<T>, key: K) -> Vec<T>
+where
+    K: Fn(&T) -> &str,
+{
+    let plan_indices: HashMap<&str, usize> = planned_keys
+        .iter()
+        .enumerate()
+        .map(|(index, planned_key)| (planned_key.as_str(), index))
+        .collect();
+    let mut items: Vec<(usize, T)> = items
+        .into_iter()
+        .filter_map(|item| plan_indices.get(key(&item)).map(|index| (*index, item)))
+        .collect();
+    items.sort_by_key(|(index, _)| *index);
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Unit tests for the [WorkloadPlan].
+#[cfg(test)]
+mod tests {
+    use super::WorkloadPlan;
+    use rand::Rng;
+
+    /// Verifies that [super::order_by_plan] sorts items into their planned order.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn order_by_plan() {
+        let planned_keys: Vec<String> = vec!["c".into(), "a".into(), "b".into()];
+        let items = vec![("a", 1), ("b", 2), ("d", 4), ("c", 3)];
+
+        let ordered = super::order_by_plan(&planned_keys, items, |item| item.0);
+        assert_eq!(vec![("c", 3), ("a", 1), ("b", 2)], ordered);
+    }
+
+    /// Verifies that [WorkloadPlan::rng] makes the same choices for the same plan and operation.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn rng_is_reproducible() {
+        let plan = |seed| WorkloadPlan {
+            seed,
+            sample_data_fingerprint: "0badf00d".into(),
+            organizations: vec![],
+            patients: vec![],
+        };
+        let choices = |plan: &WorkloadPlan, operation: &str| -> Vec<u32> {
+            let mut rng = plan.rng(operation);
+            (0..16).map(|_| rng.gen_range(0..100)).collect()
+        };
+
+        assert_eq!(choices(&plan(42), "a"), choices(&plan(42), "a"));
+        assert_ne!(choices(&plan(42), "a"), choices(&plan(42), "b"));
+        assert_ne!(choices(&plan(42), "a"), choices(&plan(43), "a"));
+    }
+}
@@ -33,7 +33,17 @@ const PERCENTILE_TICKS_PER_HALF_DISTANCE: u32 = 5;
 
 /// Output histogram data in a format similar to the Java impl's
 /// `AbstractHistogram#outputPercentileDistribution`, but gzip'd and Base64-encoded.
-pub fn export_to_hgrm_gzip(histogram: &Histogram<u64>) -> Result<String> {
+///
+/// Parameters:
+/// * `histogram`: the [Histogram] to export
+/// * `value_unit_scaling_ratio`: the ratio to divide each of the [Histogram]'s values by on output, e.g.
+///   `1000.0` to output microsecond values as milliseconds
+///
+/// Returns the gzip'd and Base64-encoded export.
+pub fn export_to_hgrm_gzip(
+    histogram: &Histogram<u64>,
+    value_unit_scaling_ratio: f64,
+) -> Result<String> {
     let mut export: String = String::new();
     export.push_str(&format!(
         "{:>12} {:>OUTPUT_VALUE_UNIT_SCALING_RATIO$} {:>10} {:>14}\n\n",
@@ -48,8 +58,8 @@ pub fn export_to_hgrm_gzip(histogram: &Histogram<u64>) -> Result<String> {
         sum += v.count_since_last_iteration();
         if v.quantile_iterated_to() < 1.0 {
             export.push_str(&format!(
-                "{:12} {:1.*} {:10} {:14.2}\n",
-                v.value_iterated_to(),
+                "{:12.3} {:1.*} {:10} {:14.2}\n",
+                v.value_iterated_to() as f64 / value_unit_scaling_ratio,
                 OUTPUT_VALUE_UNIT_SCALING_RATIO,
                 v.quantile_iterated_to(),
                 sum,
@@ -57,8 +67,8 @@ pub fn export_to_hgrm_gzip(histogram: &Histogram<u64>) -> Result<String> {
             ));
         } else {
             export.push_str(&format!(
-                "{:12} {:1.*} {:10} {:>14}\n",
-                v.value_iterated_to(),
+                "{:12.3} {:1.*} {:10} {:>14}\n",
+                v.value_iterated_to() as f64 / value_unit_scaling_ratio,
                 OUTPUT_VALUE_UNIT_SCALING_RATIO,
                 v.quantile_iterated_to(),
                 sum,
@@ -81,13 +91,13 @@ pub fn export_to_hgrm_gzip(histogram: &Histogram<u64>) -> Result<String> {
 
     export.push_str(&format_extra_data(
         "Mean",
-        histogram.mean(),
+        histogram.mean() / value_unit_scaling_ratio,
         "StdDeviation",
-        histogram.stdev(),
+        histogram.stdev() / value_unit_scaling_ratio,
     ));
     export.push_str(&format_extra_data(
         "Max",
-        histogram.max(),
+        histogram.max() as f64 / value_unit_scaling_ratio,
         "Total count",
         histogram.len(),
     ));
@@ -1,4 +1,5 @@
 pub mod histogram_hgrm_export;
 pub mod serde_duration_iso8601;
 pub mod serde_duration_millis;
+pub mod serde_duration_millis_option;
 pub mod serde_histogram;
@@ -0,0 +1,94 @@
+//! A Serde serializer/deserializer for optional chrono [Duration] instances that uses millisecond values.
+
+use chrono::Duration;
+use serde::{self, Deserialize, Deserializer, Serializer};
+
+/// Converts optional [Duration] instances to millisecond numeric values (or `null`), for use in JSON. This
+/// conversion is lossy: any fractional milliseconds in the [Duration] (i.e. extra nanoseconds) will be
+/// discarded.
+///
+/// Parameters:
+/// * `duration`: the optional [Duration] instance to be serialized
+/// * `serializer`: the Serde [Serializer] to use
+///
+/// Returns the [Serializer] result.
+pub fn serialize<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(duration) => serializer.serialize_some(&duration.num_milliseconds()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Converts serialized JSON milliseconds (or `null`) back to optional [Duration] instances.
+///
+/// Parameters:
+/// * `deserializer`: the Serde [Deserializer] to use
+///
+/// Returns the deserialized optional [Duration].
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let milliseconds = Option::<i64>::deserialize(deserializer)?;
+    Ok(milliseconds.map(Duration::milliseconds))
+}
+
+/// Unit tests for the optional [Duration] serializer & deserialzer.
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    /// Just used to test Serde against.
+    #[derive(Deserialize, Serialize)]
+    struct DurationStruct {
+        #[serde(with = "super")]
+        duration: Option<Duration>,
+    }
+
+    /// Verifies that optional [Duration] values serialize as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn serialize() {
+        let expected = json!({
+            "duration": 1000,
+        });
+        let expected = serde_json::to_string(&expected).unwrap();
+        let actual = DurationStruct {
+            duration: Some(Duration::milliseconds(1000)),
+        };
+        let actual = serde_json::to_string(&actual).unwrap();
+        assert_eq!(expected, actual);
+
+        let expected = json!({
+            "duration": null,
+        });
+        let expected = serde_json::to_string(&expected).unwrap();
+        let actual = DurationStruct { duration: None };
+        let actual = serde_json::to_string(&actual).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    /// Verifies that optional [Duration] values deserialize as expected.
+    #[tracing::instrument(level = "info")]
+    #[test_env_log::test(tokio::test)]
+    async fn deserialize() {
+        let actual = json!({
+            "duration": 1000,
+        });
+        let actual = serde_json::to_string(&actual).unwrap();
+        let actual: DurationStruct = serde_json::from_str(&actual).unwrap();
+        assert_eq!(Some(Duration::milliseconds(1000)), actual.duration);
+
+        let actual = json!({
+            "duration": null,
+        });
+        let actual = serde_json::to_string(&actual).unwrap();
+        let actual: DurationStruct = serde_json::from_str(&actual).unwrap();
+        assert_eq!(None, actual.duration);
+    }
+}
@@ -38,8 +38,7 @@ async fn benchmark_small() {
     println!("STDOUT:\n{}", stdout);
 
     // Verify that the bechmarks ran to completion.
-    assert_eq!(
-        true,
+    assert!(
         output.status.success(),
         "benchmark process exited with '{}'",
         output.status